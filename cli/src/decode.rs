@@ -0,0 +1,142 @@
+//! Checks a query response against its type and prints it normalized.
+
+use crate::error::Error;
+use clap::ValueEnum;
+use cosmwasm_std::{Binary, HumanAddr};
+use cw721::{
+    AllNftInfoResponse, ApprovedForAllResponse, ContractInfoResponse, NftInfoResponse,
+    NumTokensResponse, OwnerOfResponse, TokensResponse,
+};
+use governance::msg::{
+    AllowlistEntry, AllowlistResponse, PaidAmountResponse, PaymentsResponse, PriceResponse,
+    ReferralResponse, ReferrersResponse, SubscriptionInfoResponse, SubscriptionStatusResponse,
+    UnbondingResponse, VoucherResponse,
+};
+use name_service::msg::{
+    NameInfoResponse, NameResponse, NamesResponse, OwnerResponse, VersionHistoryResponse,
+};
+use name_service::state::{NameStatus, Record};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Named after the query subcommand that produces the response.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum ResponseKind {
+    PaidAmount,
+    AddressExists,
+    NameServiceAddress,
+    Price,
+    SubscriptionInfo,
+    SubscriptionStatus,
+    Payments,
+    ReferralEarnings,
+    Referrers,
+    Allowance,
+    Allowlist,
+    VoucherStatus,
+    Unbonding,
+    VersionHistory,
+    NameExists,
+    Owner,
+    Value,
+    NameStatus,
+    Records,
+    NameInfo,
+    ListNames,
+    NamesByOwner,
+    OwnerOf,
+    ApprovedForAll,
+    NumTokens,
+    ContractInfo,
+    NftInfo,
+    AllNftInfo,
+    Tokens,
+    AllTokens,
+}
+
+/// Accepts the response as JSON, as base64 of the JSON, or wrapped in the LCD's
+/// `{"height": .., "result": ..}` envelope.
+pub fn decode(kind: ResponseKind, input: &[u8]) -> Result<Value, Error> {
+    let text = String::from_utf8_lossy(input);
+    let mut value: Value = match serde_json::from_str(text.trim()) {
+        Ok(value) => value,
+        Err(_) => match Binary::from_base64(text.trim()) {
+            Ok(bytes) => serde_json::from_slice(bytes.as_slice())?,
+            Err(_) => return Err(Error::UnreadableInput {}),
+        },
+    };
+    if value.get("height").is_some() {
+        if let Some(result) = value.get_mut("result").map(Value::take) {
+            value = result;
+        }
+    }
+    match kind {
+        ResponseKind::PaidAmount => typed::<PaidAmountResponse>(value),
+        ResponseKind::AddressExists | ResponseKind::NameExists => typed::<bool>(value),
+        ResponseKind::NameServiceAddress => typed::<HumanAddr>(value),
+        ResponseKind::Price => typed::<PriceResponse>(value),
+        ResponseKind::SubscriptionInfo => typed::<SubscriptionInfoResponse>(value),
+        ResponseKind::SubscriptionStatus => typed::<SubscriptionStatusResponse>(value),
+        ResponseKind::Payments => typed::<PaymentsResponse>(value),
+        ResponseKind::ReferralEarnings => typed::<ReferralResponse>(value),
+        ResponseKind::Referrers => typed::<ReferrersResponse>(value),
+        ResponseKind::Allowance => typed::<AllowlistEntry>(value),
+        ResponseKind::Allowlist => typed::<AllowlistResponse>(value),
+        ResponseKind::VoucherStatus => typed::<VoucherResponse>(value),
+        ResponseKind::Unbonding => typed::<UnbondingResponse>(value),
+        ResponseKind::VersionHistory => typed::<VersionHistoryResponse>(value),
+        ResponseKind::Owner => typed::<OwnerResponse>(value),
+        ResponseKind::Value => typed::<NameResponse>(value),
+        ResponseKind::NameStatus => typed::<NameStatus>(value),
+        ResponseKind::Records => typed::<Vec<Record>>(value),
+        ResponseKind::NameInfo => typed::<NameInfoResponse>(value),
+        ResponseKind::ListNames | ResponseKind::NamesByOwner => typed::<NamesResponse>(value),
+        ResponseKind::OwnerOf => typed::<OwnerOfResponse>(value),
+        ResponseKind::ApprovedForAll => typed::<ApprovedForAllResponse>(value),
+        ResponseKind::NumTokens => typed::<NumTokensResponse>(value),
+        ResponseKind::ContractInfo => typed::<ContractInfoResponse>(value),
+        ResponseKind::NftInfo => typed::<NftInfoResponse>(value),
+        ResponseKind::AllNftInfo => typed::<AllNftInfoResponse>(value),
+        ResponseKind::Tokens | ResponseKind::AllTokens => typed::<TokensResponse>(value),
+    }
+}
+
+fn typed<T: DeserializeOwned + Serialize>(value: Value) -> Result<Value, Error> {
+    let typed: T = serde_json::from_value(value)?;
+    Ok(serde_json::to_value(typed)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn decodes_every_encoding() {
+        let response = json!({"amount": "1000", "found": true});
+        let expected = Ok(response.clone());
+        let raw = response.to_string();
+        let res = decode(ResponseKind::PaidAmount, raw.as_bytes()).map_err(|e| e.to_string());
+        assert_eq!(res, expected);
+
+        let encoded = Binary(raw.clone().into_bytes()).to_base64();
+        let res = decode(ResponseKind::PaidAmount, encoded.as_bytes()).map_err(|e| e.to_string());
+        assert_eq!(res, expected);
+
+        let wrapped = json!({"height": "12", "result": response}).to_string();
+        let res = decode(ResponseKind::PaidAmount, wrapped.as_bytes()).map_err(|e| e.to_string());
+        assert_eq!(res, expected);
+    }
+
+    #[test]
+    fn rejects_responses_of_another_type() {
+        let names = json!({"names": []}).to_string();
+        assert!(decode(ResponseKind::Payments, names.as_bytes()).is_err());
+        assert!(decode(ResponseKind::ListNames, names.as_bytes()).is_ok());
+        assert!(matches!(
+            decode(ResponseKind::Price, b"not json!"),
+            Err(Error::UnreadableInput {})
+        ));
+    }
+}
@@ -0,0 +1,212 @@
+//! Runs a script of messages against governance and the name service in process, so a
+//! sequence can be checked before anything is signed.
+//!
+//! ```json
+//! {
+//!   "governance": {"base_price": "100", "registration_period": 1000, "grace_period": 100},
+//!   "balances": [{"address": "alice", "coins": [{"denom": "uluna", "amount": "1000"}]}],
+//!   "steps": [
+//!     {"execute": {"sender": "alice", "contract": "governance",
+//!                  "msg": {"subscribe": {"name": "alice"}},
+//!                  "funds": [{"denom": "uluna", "amount": "100"}]}},
+//!     {"query": {"contract": "name_service", "msg": {"owner_is": {"value": "alice"}}}},
+//!     {"advance": {"seconds": 1100}},
+//!     {"balance": {"address": "alice", "denom": "uluna"}}
+//!   ]
+//! }
+//! ```
+
+use crate::error::Error;
+use cosmwasm_std::{Binary, Coin, HumanAddr, StdResult};
+use governance::msg::{HandleMsg as GovHandleMsg, InitMsg as GovInitMsg, QueryMsg as GovQueryMsg};
+use multitest::{Code, Router};
+use name_service::msg::{HandleMsg as NSHandleMsg, QueryMsg as NSQueryMsg};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Script {
+    /// Governance's init message; `nameservice_code_id` may be left out.
+    governance: Value,
+    /// Instantiates governance and so owns it.
+    #[serde(default = "default_admin")]
+    admin: HumanAddr,
+    #[serde(default)]
+    balances: Vec<Balance>,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Balance {
+    address: HumanAddr,
+    coins: Vec<Coin>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+enum Step {
+    Execute {
+        sender: HumanAddr,
+        contract: Target,
+        msg: Value,
+        #[serde(default)]
+        funds: Vec<Coin>,
+    },
+    Query {
+        contract: Target,
+        msg: Value,
+    },
+    Advance {
+        seconds: u64,
+    },
+    Balance {
+        address: HumanAddr,
+        denom: String,
+    },
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum Target {
+    Governance,
+    NameService,
+}
+
+fn default_admin() -> HumanAddr {
+    HumanAddr::from("admin")
+}
+
+/// Reports each step in order. A failed execute is reported and rolled back, and the
+/// script goes on; a message that does not parse stops it.
+pub fn run(script: Script) -> Result<Value, Error> {
+    let mut init = script.governance;
+    if let Some(init) = init.as_object_mut() {
+        init.insert(
+            "nameservice_code_id".into(),
+            json!(Code::NameService as u64),
+        );
+    }
+    let init: GovInitMsg = serde_json::from_value(init)?;
+
+    let mut router = Router::new();
+    let governance = router.instantiate(Code::Governance, &script.admin, &init, &[])?;
+    let name_service: HumanAddr =
+        router.query(&governance, &GovQueryMsg::GetNameServiceAddress {})?;
+    for balance in &script.balances {
+        router.set_balance(&balance.address, &balance.coins);
+    }
+    let address = |target| match target {
+        Target::Governance => &governance,
+        Target::NameService => &name_service,
+    };
+
+    let mut reports = vec![];
+    for (index, step) in script.steps.into_iter().enumerate() {
+        let report = match step {
+            Step::Execute {
+                sender,
+                contract,
+                msg,
+                funds,
+            } => {
+                let res = match contract {
+                    Target::Governance => {
+                        let msg: GovHandleMsg = serde_json::from_value(msg)?;
+                        router.execute(&sender, address(contract), &msg, &funds)
+                    }
+                    Target::NameService => {
+                        let msg: NSHandleMsg = serde_json::from_value(msg)?;
+                        router.execute(&sender, address(contract), &msg, &funds)
+                    }
+                };
+                match res {
+                    Ok(res) => json!({
+                        "log": res.log,
+                        "data": res.data.as_ref().map(readable),
+                    }),
+                    Err(err) => json!({ "error": err.to_string() }),
+                }
+            }
+            Step::Query { contract, msg } => {
+                let res = match contract {
+                    Target::Governance => {
+                        let msg: GovQueryMsg = serde_json::from_value(msg)?;
+                        router.query_binary(address(contract), &msg)
+                    }
+                    Target::NameService => {
+                        let msg: NSQueryMsg = serde_json::from_value(msg)?;
+                        router.query_binary(address(contract), &msg)
+                    }
+                };
+                report_query(res)
+            }
+            Step::Advance { seconds } => {
+                router.advance(seconds);
+                json!({ "time": router.block_time() })
+            }
+            Step::Balance { address, denom } => {
+                let amount = router.balance(&address, &denom);
+                json!({ "balance": Coin::new(amount, &denom) })
+            }
+        };
+        reports.push(json!({ "step": index, "report": report }));
+    }
+    Ok(Value::Array(reports))
+}
+
+fn report_query(res: StdResult<Binary>) -> Value {
+    match res {
+        Ok(res) => json!({ "result": readable(&res) }),
+        Err(err) => json!({ "error": err.to_string() }),
+    }
+}
+
+/// Response data as JSON when it is JSON, base64 otherwise.
+fn readable(data: &Binary) -> Value {
+    serde_json::from_slice(data.as_slice()).unwrap_or_else(|_| json!(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_a_script_and_rolls_back_failures() {
+        let script: Script = serde_json::from_value(json!({
+            "governance": {"base_price": "100", "registration_period": 1000, "grace_period": 100},
+            "balances": [{"address": "alice", "coins": [{"denom": "uluna", "amount": "150"}]}],
+            "steps": [
+                {"execute": {"sender": "alice", "contract": "governance",
+                             "msg": {"subscribe": {"name": "alice"}},
+                             "funds": [{"denom": "uluna", "amount": "100"}]}},
+                {"execute": {"sender": "alice", "contract": "governance",
+                             "msg": {"subscribe": {"name": "bob"}},
+                             "funds": [{"denom": "uluna", "amount": "50"}]}},
+                {"query": {"contract": "name_service", "msg": {"owner_is": {"value": "alice"}}}},
+                {"balance": {"address": "alice", "denom": "uluna"}},
+            ],
+        }))
+        .unwrap();
+        let reports = run(script).unwrap();
+
+        assert_eq!(reports[0]["report"]["log"][0]["value"], json!("subscribe"));
+        assert!(reports[1]["report"]["error"].is_string());
+        assert_eq!(reports[2]["report"]["result"]["owner"], json!("alice"));
+        assert_eq!(reports[3]["report"]["balance"]["amount"], json!("50"));
+    }
+
+    #[test]
+    fn stops_on_malformed_messages() {
+        let script: Script = serde_json::from_value(json!({
+            "governance": {"base_price": "100", "registration_period": 1000, "grace_period": 100},
+            "steps": [
+                {"execute": {"sender": "alice", "contract": "governance",
+                             "msg": {"subscribe": {"names": ["alice"]}}}},
+            ],
+        }))
+        .unwrap();
+        assert!(matches!(run(script), Err(Error::Json { .. })));
+    }
+}
@@ -0,0 +1,87 @@
+use cosmwasm_std::StdError;
+use snafu::Snafu;
+use std::path::PathBuf;
+
+#[derive(Snafu, Debug)]
+#[snafu(visibility = "pub")]
+pub enum Error {
+    #[snafu(display("Invalid name {:?}: {}.", name, reason))]
+    InvalidName { name: String, reason: &'static str },
+
+    #[snafu(display("{} appears twice in the batch.", name))]
+    DuplicateName { name: String },
+
+    #[snafu(display("Invalid address {}: {}.", address, reason))]
+    InvalidAddress { address: String, reason: String },
+
+    #[snafu(display(
+        "Invalid coin {:?}, expected an amount and a denom like 1000uluna.",
+        coin
+    ))]
+    InvalidCoin { coin: String },
+
+    #[snafu(display("Premium needs both --premium-start and --premium-window, both positive."))]
+    InvalidPremium {},
+
+    #[snafu(display(
+        "Staking needs at least one --validator, a positive --stake and --unbonding-period."
+    ))]
+    InvalidStaking {},
+
+    #[snafu(display("Invalid referral share {:?}, expected a decimal from 0 to 1.", share))]
+    InvalidReferralShare { share: String },
+
+    #[snafu(display("Invalid discount {:?}, expected a decimal from 0 to 1.", discount))]
+    InvalidDiscount { discount: String },
+
+    #[snafu(display("Invalid allowlist entry {:?}, expected ADDRESS=QUOTA.", entry))]
+    InvalidAllowlistEntry { entry: String },
+
+    #[snafu(display("{} only applies together with --contract.", flag))]
+    NeedsContract { flag: &'static str },
+
+    #[snafu(display("Only execute messages can be wrapped in a transaction."))]
+    NotExecutable {},
+
+    #[snafu(display("--chain-id is required to sign."))]
+    MissingChainId {},
+
+    #[snafu(display("Either --sender or --key-file is required with --contract."))]
+    MissingSender {},
+
+    #[snafu(display("--sender {} is not the key's address {}.", sender, derived))]
+    SenderMismatch { sender: String, derived: String },
+
+    #[snafu(display("Invalid key file: {}.", reason))]
+    InvalidKey { reason: String },
+
+    #[snafu(display("Invalid --voucher-signer: {}.", reason))]
+    InvalidVoucherSigner { reason: String },
+
+    #[snafu(display("Input is neither JSON nor base64."))]
+    UnreadableInput {},
+
+    #[snafu(display("Cannot read {}: {}", path.display(), source))]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("{}", source))]
+    Json { source: serde_json::Error },
+
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Json { source }
+    }
+}
+
+impl From<StdError> for Error {
+    fn from(source: StdError) -> Self {
+        Error::Std { source }
+    }
+}
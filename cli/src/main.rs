@@ -0,0 +1,99 @@
+mod decode;
+mod dry_run;
+mod error;
+mod msgs;
+mod tx;
+
+use crate::error::{Error, Io};
+use crate::msgs::{Built, GovernanceCmd, NameServiceCmd, QueryCmd};
+use clap::{Parser, Subcommand};
+use snafu::ResultExt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process;
+
+/// Builds, signs, decodes and dry-runs messages for governance and the name service.
+#[derive(Parser)]
+#[command(name = "tns", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Governance messages, printed bare or as a transaction
+    Governance {
+        #[command(subcommand)]
+        msg: Box<GovernanceCmd>,
+        #[command(flatten)]
+        tx: tx::TxArgs,
+    },
+    /// Name service messages, printed bare or as a transaction
+    NameService {
+        #[command(subcommand)]
+        msg: NameServiceCmd,
+        #[command(flatten)]
+        tx: tx::TxArgs,
+    },
+    /// Query messages for either contract
+    #[command(subcommand)]
+    Query(QueryCmd),
+    /// Checks a query response from FILE, or stdin, and prints it
+    Decode {
+        kind: decode::ResponseKind,
+        file: Option<PathBuf>,
+    },
+    /// Runs a JSON script of messages against both contracts in process
+    DryRun { script: PathBuf },
+    /// Signs a governance voucher
+    Voucher(tx::VoucherArgs),
+    /// Prints the public key of a key file, for --voucher-signer
+    SignerKey { key_file: PathBuf },
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    let output = match cli.command {
+        Command::Governance { msg, tx } => render(msg.build()?, &tx)?,
+        Command::NameService { msg, tx } => render(msg.build()?, &tx)?,
+        Command::Query(query) => query.build()?,
+        Command::Decode { kind, file } => decode::decode(kind, &read_input(file)?)?,
+        Command::DryRun { script } => {
+            let script = fs::read(&script).context(Io { path: script })?;
+            dry_run::run(serde_json::from_slice(&script)?)?
+        }
+        Command::Voucher(args) => tx::voucher(args)?,
+        Command::SignerKey { key_file } => tx::signer_key(&key_file)?,
+    };
+    println!("{}", serde_json::to_string_pretty(&output)?);
+    Ok(())
+}
+
+fn render(built: Built, args: &tx::TxArgs) -> Result<serde_json::Value, Error> {
+    match built {
+        Built::Execute(msg) => tx::render(msg, args),
+        Built::Instantiate(_) | Built::Migrate(_) if args.wraps() => Err(Error::NotExecutable {}),
+        Built::Instantiate(msg) | Built::Migrate(msg) => Ok(msg),
+    }
+}
+
+fn read_input(file: Option<PathBuf>) -> Result<Vec<u8>, Error> {
+    match file {
+        Some(path) => fs::read(&path).context(Io { path }),
+        None => {
+            let mut input = vec![];
+            io::stdin()
+                .read_to_end(&mut input)
+                .context(Io { path: "stdin" })?;
+            Ok(input)
+        }
+    }
+}
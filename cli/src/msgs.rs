@@ -0,0 +1,945 @@
+//! Subcommands for every message an operator or user sends, built from the contracts' own
+//! `msg` types. Messages only the contracts send each other (`Signup`, `NameRegistered`,
+//! `NameTransferred`, and the name service's `Register` family) are left out.
+
+use crate::error::Error;
+use clap::{Args, Subcommand, ValueEnum};
+use cosmwasm_std::{Binary, Decimal, HumanAddr, Uint128};
+use cw0::Expiration;
+use governance::msg::{
+    AllowlistEntry, HandleMsg as GovHandleMsg, InitMsg as GovInitMsg, MigrateMsg as GovMigrateMsg,
+    QueryMsg as GovQueryMsg, Voucher,
+};
+use governance::state::{Decay, Premium, Staking};
+use k256::ecdsa::VerifyingKey;
+use name_service::msg::{HandleMsg as NSHandleMsg, QueryMsg as NSQueryMsg};
+use name_service::state::NameStatus;
+use serde_json::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A built message and what a chain would do with it.
+pub enum Built {
+    Instantiate(Value),
+    Migrate(Value),
+    Execute(Value),
+}
+
+#[derive(Subcommand)]
+pub enum GovernanceCmd {
+    /// Instantiates governance, which instantiates the name service from its code id
+    Init {
+        #[arg(long)]
+        nameservice_code_id: u64,
+        /// Receives premiums; defaults to the instantiator
+        #[arg(long)]
+        treasury: Option<String>,
+        #[arg(long)]
+        base_price: u128,
+        /// Term of a subscription, in seconds
+        #[arg(long)]
+        registration_period: u64,
+        /// Time after the term before anyone else may subscribe, in seconds
+        #[arg(long)]
+        grace_period: u64,
+        #[command(flatten)]
+        premium: PremiumArgs,
+        /// Paid to whoever sweeps a lapsed name, out of what the name paid
+        #[arg(long)]
+        sweep_reward: Option<u128>,
+        /// Part of each payment credited to the subscriber's referrer, like 0.05
+        #[arg(long)]
+        referral_share: Option<String>,
+        /// Hex public key that signs vouchers, as printed by `tns signer-key`
+        #[arg(long)]
+        voucher_signer: Option<String>,
+        #[command(flatten)]
+        staking: StakingArgs,
+    },
+    /// Replaces any pricing given; required in full when migrating from 0.1
+    Migrate {
+        #[arg(long)]
+        treasury: Option<String>,
+        #[arg(long)]
+        base_price: Option<u128>,
+        #[arg(long)]
+        registration_period: Option<u64>,
+        #[arg(long)]
+        grace_period: Option<u64>,
+        #[command(flatten)]
+        premium: PremiumArgs,
+        #[arg(long)]
+        sweep_reward: Option<u128>,
+        #[arg(long)]
+        referral_share: Option<String>,
+        #[arg(long)]
+        voucher_signer: Option<String>,
+        #[command(flatten)]
+        staking: StakingArgs,
+    },
+    Subscribe {
+        name: String,
+        /// Registration periods to pay for up front
+        #[arg(long)]
+        periods: Option<u64>,
+        /// Address that earns the referral share of the payment
+        #[arg(long)]
+        referrer: Option<String>,
+        /// Signed voucher JSON, as printed by `tns voucher`
+        #[arg(long)]
+        voucher: Option<String>,
+    },
+    Unsubscribe {
+        name: String,
+    },
+    /// Extends the term of a name the sender owns, until its grace period ends
+    Renew {
+        name: String,
+        /// Registration periods to pay for
+        #[arg(long)]
+        periods: Option<u64>,
+    },
+    /// Holds a name by delegating the sent stake instead of paying for periods
+    Stake {
+        name: String,
+    },
+    /// Collects stakes whose unbonding period has passed
+    ClaimStake,
+    /// Sends the staking rewards of every delegation to the treasury
+    WithdrawRewards,
+    /// One payment covering the price of every name; the batch succeeds or fails as a whole
+    SubscribeBatch {
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Registration periods to pay for up front, for every name
+        #[arg(long)]
+        periods: Option<u64>,
+        #[arg(long)]
+        referrer: Option<String>,
+    },
+    /// One payment covering the renewal of every name; the batch succeeds or fails as a whole
+    RenewBatch {
+        #[arg(required = true)]
+        names: Vec<String>,
+        /// Registration periods to pay for, for every name
+        #[arg(long)]
+        periods: Option<u64>,
+    },
+    UnsubscribeBatch {
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Deregisters names past their grace period, for the sweep reward
+    Sweep {
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Collects what the sender has earned from referrals
+    ClaimReferral,
+    /// Owner only: sets how many discounted subscriptions each address has left
+    SetAllowlist {
+        /// ADDRESS=QUOTA pairs; a zero quota removes the address
+        #[arg(required = true)]
+        entries: Vec<String>,
+        /// Part of the base price waived, like 0.5
+        #[arg(long, default_value = "1")]
+        discount: String,
+    },
+    /// Owner only
+    SetNameStatus {
+        status: Status,
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Owner only: assigns a reserved name without payment
+    RegisterReserved {
+        name: String,
+        owner: String,
+    },
+}
+
+#[derive(Args)]
+pub struct PremiumArgs {
+    /// Premium charged right after a name's grace period, decaying to zero
+    #[arg(long)]
+    premium_start: Option<u128>,
+    /// Seconds over which the premium decays
+    #[arg(long)]
+    premium_window: Option<u64>,
+    /// Halve the premium every this many seconds instead of decaying linearly
+    #[arg(long)]
+    premium_half_life: Option<u64>,
+}
+
+#[derive(Args)]
+pub struct StakingArgs {
+    /// Validator stakes are delegated to; repeat for a set
+    #[arg(long = "validator")]
+    validators: Vec<String>,
+    /// Least amount delegated to hold a name
+    #[arg(long)]
+    stake: Option<u128>,
+    /// Seconds before an undelegated stake can be claimed
+    #[arg(long)]
+    unbonding_period: Option<u64>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Status {
+    Open,
+    Reserved,
+    Blocked,
+}
+
+#[derive(Subcommand)]
+pub enum NameServiceCmd {
+    /// Sets a record on a name, or removes it when no value is given
+    SetRecord {
+        name: String,
+        key: String,
+        value: Option<String>,
+    },
+    /// Makes a name of yours the one your address resolves back to
+    SetPrimary {
+        name: String,
+    },
+    Transfer {
+        name: String,
+        recipient: String,
+    },
+    /// Transfers a name to a contract and calls it with `msg`
+    Send {
+        name: String,
+        contract: String,
+        /// JSON message for the receiving contract
+        #[arg(long)]
+        msg: Option<String>,
+    },
+    Approve {
+        name: String,
+        spender: String,
+        #[command(flatten)]
+        expires: ExpiresArgs,
+    },
+    Revoke {
+        name: String,
+        spender: String,
+    },
+    ApproveAll {
+        operator: String,
+        #[command(flatten)]
+        expires: ExpiresArgs,
+    },
+    RevokeAll {
+        operator: String,
+    },
+}
+
+#[derive(Args)]
+pub struct ExpiresArgs {
+    #[arg(long, conflicts_with = "expires_at_time")]
+    expires_at_height: Option<u64>,
+    /// Block time in seconds
+    #[arg(long)]
+    expires_at_time: Option<u64>,
+}
+
+#[derive(Subcommand)]
+pub enum QueryCmd {
+    #[command(subcommand)]
+    Governance(GovernanceQuery),
+    #[command(subcommand)]
+    NameService(NameServiceQuery),
+}
+
+#[derive(Subcommand)]
+pub enum GovernanceQuery {
+    PaidAmount {
+        address: String,
+    },
+    AddressExists {
+        address: String,
+    },
+    NameServiceAddress,
+    Price {
+        name: String,
+        /// Block time to price at, in seconds; defaults to that of the latest transaction
+        #[arg(long)]
+        time: Option<u64>,
+    },
+    SubscriptionInfo {
+        name: String,
+    },
+    /// Whether a subscription is active, in grace or lapsed
+    SubscriptionStatus {
+        name: String,
+        /// Block time to check at, in seconds; defaults to now
+        #[arg(long)]
+        time: Option<u64>,
+    },
+    Payments {
+        #[arg(long)]
+        start_after: Option<String>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    ReferralEarnings {
+        referrer: String,
+    },
+    Referrers {
+        #[arg(long)]
+        start_after: Option<String>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    Allowance {
+        address: String,
+    },
+    Allowlist {
+        #[arg(long)]
+        start_after: Option<String>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Who redeemed a voucher, if anyone
+    VoucherStatus {
+        id: String,
+    },
+    /// Undelegated stakes waiting to be claimed
+    Unbonding {
+        address: String,
+    },
+    /// Versions the contract has been migrated to, with the heights they took over at
+    VersionHistory,
+}
+
+#[derive(Subcommand)]
+pub enum NameServiceQuery {
+    NameExists {
+        name: String,
+    },
+    Owner {
+        name: String,
+    },
+    /// The first name held by an address
+    Value {
+        owner: String,
+    },
+    NameStatus {
+        name: String,
+    },
+    Records {
+        name: String,
+    },
+    NameInfo {
+        name: String,
+    },
+    /// Versions the contract has been migrated to, with the heights they took over at
+    VersionHistory,
+    ListNames {
+        #[command(flatten)]
+        page: Page,
+    },
+    NamesByOwner {
+        owner: String,
+        #[command(flatten)]
+        page: Page,
+    },
+    OwnerOf {
+        name: String,
+    },
+    ApprovedForAll {
+        owner: String,
+        #[command(flatten)]
+        page: Page,
+    },
+    NumTokens,
+    ContractInfo,
+    NftInfo {
+        name: String,
+    },
+    AllNftInfo {
+        name: String,
+    },
+    Tokens {
+        owner: String,
+        #[command(flatten)]
+        page: Page,
+    },
+    AllTokens {
+        #[command(flatten)]
+        page: Page,
+    },
+}
+
+#[derive(Args)]
+pub struct Page {
+    #[arg(long)]
+    start_after: Option<String>,
+    #[arg(long)]
+    limit: Option<u32>,
+}
+
+impl GovernanceCmd {
+    pub fn build(self) -> Result<Built, Error> {
+        let msg = match self {
+            GovernanceCmd::Init {
+                nameservice_code_id,
+                treasury,
+                base_price,
+                registration_period,
+                grace_period,
+                premium,
+                sweep_reward,
+                referral_share: share,
+                voucher_signer: signer,
+                staking,
+            } => {
+                return Ok(Built::Instantiate(serde_json::to_value(GovInitMsg {
+                    nameservice_code_id,
+                    treasury: treasury.map(|treasury| address(&treasury)).transpose()?,
+                    base_price: Uint128(base_price),
+                    registration_period,
+                    grace_period,
+                    premium: premium.build()?,
+                    sweep_reward: sweep_reward.map(Uint128),
+                    referral_share: referral_share(share)?,
+                    voucher_signer: voucher_signer(signer)?,
+                    staking: staking.build()?,
+                })?))
+            }
+            GovernanceCmd::Migrate {
+                treasury,
+                base_price,
+                registration_period,
+                grace_period,
+                premium,
+                sweep_reward,
+                referral_share: share,
+                voucher_signer: signer,
+                staking,
+            } => {
+                return Ok(Built::Migrate(serde_json::to_value(GovMigrateMsg {
+                    treasury: treasury.map(|treasury| address(&treasury)).transpose()?,
+                    base_price: base_price.map(Uint128),
+                    registration_period,
+                    grace_period,
+                    premium: premium.build()?,
+                    sweep_reward: sweep_reward.map(Uint128),
+                    referral_share: referral_share(share)?,
+                    voucher_signer: voucher_signer(signer)?,
+                    staking: staking.build()?,
+                })?))
+            }
+            GovernanceCmd::Subscribe {
+                name: value,
+                periods,
+                referrer,
+                voucher,
+            } => GovHandleMsg::Subscribe {
+                name: name(value)?,
+                periods,
+                referrer: referrer.map(|referrer| address(&referrer)).transpose()?,
+                voucher: voucher
+                    .map(|voucher| serde_json::from_str::<Voucher>(&voucher))
+                    .transpose()?,
+            },
+            GovernanceCmd::Unsubscribe { name: value } => {
+                GovHandleMsg::Unsubscribe { name: name(value)? }
+            }
+            GovernanceCmd::Renew {
+                name: value,
+                periods,
+            } => GovHandleMsg::Renew {
+                name: name(value)?,
+                periods,
+            },
+            GovernanceCmd::SubscribeBatch {
+                names: values,
+                periods,
+                referrer,
+            } => GovHandleMsg::SubscribeBatch {
+                names: names(values)?,
+                periods,
+                referrer: referrer.map(|referrer| address(&referrer)).transpose()?,
+            },
+            GovernanceCmd::RenewBatch {
+                names: values,
+                periods,
+            } => GovHandleMsg::RenewBatch {
+                names: names(values)?,
+                periods,
+            },
+            GovernanceCmd::UnsubscribeBatch { names: values } => GovHandleMsg::UnsubscribeBatch {
+                names: names(values)?,
+            },
+            GovernanceCmd::Sweep { limit } => GovHandleMsg::Sweep { limit },
+            GovernanceCmd::Stake { name: value } => GovHandleMsg::Stake { name: name(value)? },
+            GovernanceCmd::ClaimStake => GovHandleMsg::ClaimStake {},
+            GovernanceCmd::WithdrawRewards => GovHandleMsg::WithdrawRewards {},
+            GovernanceCmd::ClaimReferral => GovHandleMsg::ClaimReferral {},
+            GovernanceCmd::SetAllowlist { entries, discount } => {
+                let discount = self::discount(discount)?;
+                GovHandleMsg::SetAllowlist {
+                    entries: entries
+                        .into_iter()
+                        .map(|entry| allowlist_entry(entry, discount))
+                        .collect::<Result<_, _>>()?,
+                }
+            }
+            GovernanceCmd::SetNameStatus {
+                status,
+                names: values,
+            } => GovHandleMsg::SetNameStatus {
+                names: names(values)?,
+                status: status.into(),
+            },
+            GovernanceCmd::RegisterReserved { name: value, owner } => {
+                GovHandleMsg::RegisterReserved {
+                    name: name(value)?,
+                    owner: address(&owner)?,
+                }
+            }
+        };
+        Ok(Built::Execute(serde_json::to_value(msg)?))
+    }
+}
+
+impl PremiumArgs {
+    fn build(self) -> Result<Option<Premium>, Error> {
+        let (start, window) = match (self.premium_start, self.premium_window) {
+            (None, None) if self.premium_half_life.is_none() => return Ok(None),
+            (Some(start), Some(window)) if window > 0 => (start, window),
+            _ => return Err(Error::InvalidPremium {}),
+        };
+        let decay = match self.premium_half_life {
+            None => Decay::Linear,
+            Some(0) => return Err(Error::InvalidPremium {}),
+            Some(half_life) => Decay::Exponential { half_life },
+        };
+        Ok(Some(Premium {
+            start: Uint128(start),
+            window,
+            decay,
+        }))
+    }
+}
+
+impl StakingArgs {
+    fn build(self) -> Result<Option<Staking>, Error> {
+        match (self.stake, self.unbonding_period) {
+            (None, None) if self.validators.is_empty() => Ok(None),
+            (Some(stake), Some(unbonding_period))
+                if stake > 0 && unbonding_period > 0 && !self.validators.is_empty() =>
+            {
+                Ok(Some(Staking {
+                    validators: self
+                        .validators
+                        .iter()
+                        .map(|validator| address(validator))
+                        .collect::<Result<_, _>>()?,
+                    stake: Uint128(stake),
+                    unbonding_period,
+                }))
+            }
+            _ => Err(Error::InvalidStaking {}),
+        }
+    }
+}
+
+/// Governance rejects shares and discounts above one.
+fn fraction(value: &str) -> Option<Decimal> {
+    value
+        .parse::<Decimal>()
+        .ok()
+        .filter(|parsed| *parsed <= Decimal::one())
+}
+
+fn referral_share(share: Option<String>) -> Result<Option<Decimal>, Error> {
+    match share {
+        Some(share) => match fraction(&share) {
+            Some(parsed) => Ok(Some(parsed)),
+            None => Err(Error::InvalidReferralShare { share }),
+        },
+        None => Ok(None),
+    }
+}
+
+pub fn discount(discount: String) -> Result<Decimal, Error> {
+    fraction(&discount).ok_or(Error::InvalidDiscount { discount })
+}
+
+/// Parses an `ADDRESS=QUOTA` pair.
+fn allowlist_entry(entry: String, discount: Decimal) -> Result<AllowlistEntry, Error> {
+    let parsed = entry
+        .split_once('=')
+        .and_then(|(value, quota)| Some((value, quota.parse::<u32>().ok()?)));
+    match parsed {
+        Some((value, quota)) => Ok(AllowlistEntry {
+            address: address(value)?,
+            quota,
+            discount,
+        }),
+        None => Err(Error::InvalidAllowlistEntry { entry }),
+    }
+}
+
+/// Checks the hex public key is one governance can verify vouchers with.
+fn voucher_signer(signer: Option<String>) -> Result<Option<Binary>, Error> {
+    let signer = match signer {
+        Some(signer) => signer,
+        None => return Ok(None),
+    };
+    let invalid = |reason: String| Error::InvalidVoucherSigner { reason };
+    let bytes = hex::decode(signer.trim()).map_err(|err| invalid(err.to_string()))?;
+    VerifyingKey::from_sec1_bytes(&bytes).map_err(|err| invalid(err.to_string()))?;
+    Ok(Some(Binary(bytes)))
+}
+
+impl From<Status> for NameStatus {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Open => NameStatus::Open,
+            Status::Reserved => NameStatus::Reserved,
+            Status::Blocked => NameStatus::Blocked,
+        }
+    }
+}
+
+impl NameServiceCmd {
+    pub fn build(self) -> Result<Built, Error> {
+        let msg = match self {
+            NameServiceCmd::SetRecord {
+                name: value,
+                key,
+                value: record,
+            } => NSHandleMsg::SetRecord {
+                name: name(value)?,
+                key,
+                value: record,
+            },
+            NameServiceCmd::SetPrimary { name: value } => {
+                NSHandleMsg::SetPrimary { name: name(value)? }
+            }
+            NameServiceCmd::Transfer {
+                name: value,
+                recipient,
+            } => NSHandleMsg::TransferNft {
+                recipient: address(&recipient)?,
+                token_id: name(value)?,
+            },
+            NameServiceCmd::Send {
+                name: value,
+                contract,
+                msg,
+            } => {
+                let msg = match msg {
+                    Some(msg) => {
+                        serde_json::from_str::<Value>(&msg)?;
+                        Some(Binary(msg.into_bytes()))
+                    }
+                    None => None,
+                };
+                NSHandleMsg::SendNft {
+                    contract: address(&contract)?,
+                    token_id: name(value)?,
+                    msg,
+                }
+            }
+            NameServiceCmd::Approve {
+                name: value,
+                spender,
+                expires,
+            } => NSHandleMsg::Approve {
+                spender: address(&spender)?,
+                token_id: name(value)?,
+                expires: expires.build(),
+            },
+            NameServiceCmd::Revoke {
+                name: value,
+                spender,
+            } => NSHandleMsg::Revoke {
+                spender: address(&spender)?,
+                token_id: name(value)?,
+            },
+            NameServiceCmd::ApproveAll { operator, expires } => NSHandleMsg::ApproveAll {
+                operator: address(&operator)?,
+                expires: expires.build(),
+            },
+            NameServiceCmd::RevokeAll { operator } => NSHandleMsg::RevokeAll {
+                operator: address(&operator)?,
+            },
+        };
+        Ok(Built::Execute(serde_json::to_value(msg)?))
+    }
+}
+
+impl ExpiresArgs {
+    fn build(self) -> Option<Expiration> {
+        match (self.expires_at_height, self.expires_at_time) {
+            (Some(height), _) => Some(Expiration::AtHeight(height)),
+            (None, Some(time)) => Some(Expiration::AtTime(time)),
+            (None, None) => None,
+        }
+    }
+}
+
+impl QueryCmd {
+    pub fn build(self) -> Result<Value, Error> {
+        let msg = match self {
+            QueryCmd::Governance(query) => serde_json::to_value(query.build()?)?,
+            QueryCmd::NameService(query) => serde_json::to_value(query.build()?)?,
+        };
+        Ok(msg)
+    }
+}
+
+impl GovernanceQuery {
+    fn build(self) -> Result<GovQueryMsg, Error> {
+        Ok(match self {
+            GovernanceQuery::PaidAmount { address: value } => GovQueryMsg::PaidAmountIs {
+                address: address(&value)?,
+            },
+            GovernanceQuery::AddressExists { address: value } => GovQueryMsg::AddressExists {
+                address: address(&value)?,
+            },
+            GovernanceQuery::NameServiceAddress => GovQueryMsg::GetNameServiceAddress {},
+            GovernanceQuery::Price { name: value, time } => GovQueryMsg::PriceIs {
+                name: name(value)?,
+                time,
+            },
+            GovernanceQuery::SubscriptionInfo { name: value } => {
+                GovQueryMsg::SubscriptionInfo { name: name(value)? }
+            }
+            GovernanceQuery::SubscriptionStatus { name: value, time } => {
+                GovQueryMsg::SubscriptionStatus {
+                    name: name(value)?,
+                    time: time.unwrap_or_else(now),
+                }
+            }
+            GovernanceQuery::Payments { start_after, limit } => GovQueryMsg::ListPayments {
+                start_after: start_after.map(|start| address(&start)).transpose()?,
+                limit,
+            },
+            GovernanceQuery::ReferralEarnings { referrer } => GovQueryMsg::ReferralEarnings {
+                referrer: address(&referrer)?,
+            },
+            GovernanceQuery::Referrers { start_after, limit } => GovQueryMsg::ListReferrers {
+                start_after: start_after.map(|start| address(&start)).transpose()?,
+                limit,
+            },
+            GovernanceQuery::Allowance { address: value } => GovQueryMsg::Allowance {
+                address: address(&value)?,
+            },
+            GovernanceQuery::Allowlist { start_after, limit } => GovQueryMsg::ListAllowlist {
+                start_after: start_after.map(|start| address(&start)).transpose()?,
+                limit,
+            },
+            GovernanceQuery::VoucherStatus { id } => GovQueryMsg::VoucherStatus { id },
+            GovernanceQuery::Unbonding { address: value } => GovQueryMsg::Unbonding {
+                address: address(&value)?,
+            },
+            GovernanceQuery::VersionHistory => GovQueryMsg::VersionHistory {},
+        })
+    }
+}
+
+impl NameServiceQuery {
+    fn build(self) -> Result<NSQueryMsg, Error> {
+        Ok(match self {
+            NameServiceQuery::NameExists { name: value } => NSQueryMsg::NameExists {
+                value: name(value)?,
+            },
+            NameServiceQuery::Owner { name: value } => NSQueryMsg::OwnerIs {
+                value: name(value)?,
+            },
+            NameServiceQuery::Value { owner } => NSQueryMsg::ValueIs {
+                owner: address(&owner)?,
+            },
+            NameServiceQuery::NameStatus { name: value } => NSQueryMsg::NameStatusIs {
+                value: name(value)?,
+            },
+            NameServiceQuery::Records { name: value } => NSQueryMsg::RecordsOf {
+                value: name(value)?,
+            },
+            NameServiceQuery::NameInfo { name: value } => {
+                NSQueryMsg::NameInfo { name: name(value)? }
+            }
+            NameServiceQuery::VersionHistory => NSQueryMsg::VersionHistory {},
+            NameServiceQuery::ListNames { page } => NSQueryMsg::ListNames {
+                start_after: page.start_after,
+                limit: page.limit,
+            },
+            NameServiceQuery::NamesByOwner { owner, page } => NSQueryMsg::NamesByOwner {
+                owner: address(&owner)?,
+                start_after: page.start_after,
+                limit: page.limit,
+            },
+            NameServiceQuery::OwnerOf { name: value } => NSQueryMsg::OwnerOf {
+                token_id: name(value)?,
+            },
+            NameServiceQuery::ApprovedForAll { owner, page } => NSQueryMsg::ApprovedForAll {
+                owner: address(&owner)?,
+                start_after: page.start_after.map(|start| address(&start)).transpose()?,
+                limit: page.limit,
+            },
+            NameServiceQuery::NumTokens => NSQueryMsg::NumTokens {},
+            NameServiceQuery::ContractInfo => NSQueryMsg::ContractInfo {},
+            NameServiceQuery::NftInfo { name: value } => NSQueryMsg::NftInfo {
+                token_id: name(value)?,
+            },
+            NameServiceQuery::AllNftInfo { name: value } => NSQueryMsg::AllNftInfo {
+                token_id: name(value)?,
+            },
+            NameServiceQuery::Tokens { owner, page } => NSQueryMsg::Tokens {
+                owner: address(&owner)?,
+                start_after: page.start_after,
+                limit: page.limit,
+            },
+            NameServiceQuery::AllTokens { page } => NSQueryMsg::AllTokens {
+                start_after: page.start_after,
+                limit: page.limit,
+            },
+        })
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|since| since.as_secs())
+        .unwrap_or_default()
+}
+
+/// Rejects names the contracts would store but no one could type or look up reliably.
+fn name(name: String) -> Result<String, Error> {
+    let reason = if name.is_empty() {
+        "names cannot be empty"
+    } else if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        "names cannot contain whitespace"
+    } else if name.starts_with('.') || name.ends_with('.') || name.contains("..") {
+        "labels between dots cannot be empty"
+    } else {
+        return Ok(name);
+    };
+    Err(Error::InvalidName { name, reason })
+}
+
+fn names(names: Vec<String>) -> Result<Vec<String>, Error> {
+    let mut checked: Vec<String> = vec![];
+    for value in names {
+        let value = name(value)?;
+        if checked.contains(&value) {
+            return Err(Error::DuplicateName { name: value });
+        }
+        checked.push(value);
+    }
+    Ok(checked)
+}
+
+/// Addresses must be bech32, the format every Terra account and contract uses.
+pub fn address(address: &str) -> Result<HumanAddr, Error> {
+    match bech32::decode(address) {
+        Ok(_) => Ok(HumanAddr::from(address)),
+        Err(err) => Err(Error::InvalidAddress {
+            address: address.to_string(),
+            reason: err.to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "terra1x46rqay4d3cssq8gxxvqz8xt6nwlz4td20k38v";
+
+    #[test]
+    fn builds_the_contract_messages() {
+        let cmd = GovernanceCmd::SubscribeBatch {
+            names: vec!["alice".to_string(), "bob".to_string()],
+            periods: Some(2),
+            referrer: Some(ADDRESS.to_string()),
+        };
+        let msg = match cmd.build().unwrap() {
+            Built::Execute(msg) => msg,
+            _ => panic!("expected an execute message"),
+        };
+        assert_eq!(
+            msg,
+            serde_json::json!({"subscribe_batch": {
+                "names": ["alice", "bob"],
+                "periods": 2,
+                "referrer": ADDRESS,
+            }})
+        );
+
+        let cmd = NameServiceCmd::Approve {
+            name: "alice".to_string(),
+            spender: ADDRESS.to_string(),
+            expires: ExpiresArgs {
+                expires_at_height: None,
+                expires_at_time: Some(100),
+            },
+        };
+        let msg = match cmd.build().unwrap() {
+            Built::Execute(msg) => msg,
+            _ => panic!("expected an execute message"),
+        };
+        assert_eq!(
+            msg,
+            serde_json::json!({"approve": {
+                "spender": ADDRESS,
+                "token_id": "alice",
+                "expires": {"at_time": 100},
+            }})
+        );
+    }
+
+    #[test]
+    fn rejects_what_the_contracts_would_misread() {
+        assert!(name("".to_string()).is_err());
+        assert!(name("al ice".to_string()).is_err());
+        assert!(name("alice..terra".to_string()).is_err());
+        assert!(name("pay.alice".to_string()).is_ok());
+        assert!(address("terra1notbech32").is_err());
+        assert!(address(ADDRESS).is_ok());
+
+        let duplicated = GovernanceCmd::UnsubscribeBatch {
+            names: vec!["alice".to_string(), "alice".to_string()],
+        };
+        assert!(matches!(
+            duplicated.build(),
+            Err(Error::DuplicateName { .. })
+        ));
+        let premium = PremiumArgs {
+            premium_start: Some(100),
+            premium_window: None,
+            premium_half_life: None,
+        };
+        assert!(matches!(premium.build(), Err(Error::InvalidPremium {})));
+        let staking = StakingArgs {
+            validators: vec![],
+            stake: Some(100),
+            unbonding_period: Some(1000),
+        };
+        assert!(matches!(staking.build(), Err(Error::InvalidStaking {})));
+        assert_eq!(
+            referral_share(Some("0.05".to_string())).unwrap(),
+            Some(Decimal::percent(5))
+        );
+        assert!(matches!(
+            referral_share(Some("1.5".to_string())),
+            Err(Error::InvalidReferralShare { .. })
+        ));
+        let entry = allowlist_entry(format!("{}=2", ADDRESS), Decimal::one()).unwrap();
+        assert_eq!(entry.quota, 2);
+        assert!(matches!(
+            allowlist_entry(ADDRESS.to_string(), Decimal::one()),
+            Err(Error::InvalidAllowlistEntry { .. })
+        ));
+        assert!(matches!(
+            voucher_signer(Some("02".to_string())),
+            Err(Error::InvalidVoucherSigner { .. })
+        ));
+    }
+}
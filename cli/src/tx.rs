@@ -0,0 +1,339 @@
+//! Wraps an execute message in a `wasm/MsgExecuteContract` transaction and signs it in the
+//! legacy amino JSON format Terra accepts.
+
+use crate::error::{Error, Io};
+use crate::msgs::{address, discount};
+use bech32::{Bech32, Hrp};
+use clap::Args;
+use cosmwasm_std::{Binary, Coin};
+use governance::msg::Voucher;
+use k256::ecdsa::signature::Signer;
+use k256::ecdsa::{Signature, SigningKey};
+use ripemd::Ripemd160;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use snafu::ResultExt;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Args)]
+pub struct TxArgs {
+    /// Contract to execute; without it only the message is printed
+    #[arg(long, global = true)]
+    contract: Option<String>,
+    /// Defaults to the address of --key-file
+    #[arg(long, global = true)]
+    sender: Option<String>,
+    /// Coins sent along, like 1000uluna; may be repeated
+    #[arg(long = "funds", global = true)]
+    funds: Vec<String>,
+    /// File holding a hex encoded secp256k1 private key to sign with
+    #[arg(long, global = true)]
+    key_file: Option<PathBuf>,
+    #[arg(long, global = true)]
+    chain_id: Option<String>,
+    #[arg(long, global = true, default_value_t = 0)]
+    account_number: u64,
+    #[arg(long, global = true, default_value_t = 0)]
+    sequence: u64,
+    #[arg(long, global = true, default_value_t = 200_000)]
+    gas: u64,
+    /// Fee coins, like 3000uluna; may be repeated
+    #[arg(long = "fee", global = true)]
+    fee: Vec<String>,
+    #[arg(long, global = true, default_value = "")]
+    memo: String,
+    /// Bech32 prefix of addresses derived from --key-file
+    #[arg(long, global = true, default_value = "terra")]
+    prefix: String,
+}
+
+impl TxArgs {
+    /// Whether anything beyond the bare message was asked for.
+    pub fn wraps(&self) -> bool {
+        self.contract.is_some() || self.key_file.is_some() || !self.funds.is_empty()
+    }
+}
+
+/// The bare message, or a transaction executing it when `--contract` is given.
+pub fn render(msg: Value, args: &TxArgs) -> Result<Value, Error> {
+    let contract = match &args.contract {
+        Some(contract) => address(contract)?,
+        None if !args.funds.is_empty() => return Err(Error::NeedsContract { flag: "--funds" }),
+        None if args.key_file.is_some() => return Err(Error::NeedsContract { flag: "--key-file" }),
+        None => return Ok(msg),
+    };
+    let key = args.key_file.as_ref().map(read_key).transpose()?;
+    let sender = match (&args.sender, &key) {
+        (Some(sender), Some(key)) => {
+            let derived = account_address(key, &args.prefix)?;
+            if sender != &derived {
+                return Err(Error::SenderMismatch {
+                    sender: sender.clone(),
+                    derived,
+                });
+            }
+            derived
+        }
+        (Some(sender), None) => address(sender)?.0,
+        (None, Some(key)) => account_address(key, &args.prefix)?,
+        (None, None) => return Err(Error::MissingSender {}),
+    };
+
+    let execute = json!({
+        "type": "wasm/MsgExecuteContract",
+        "value": {
+            "sender": sender,
+            "contract": contract,
+            "execute_msg": Binary(serde_json::to_vec(&msg)?),
+            "coins": coins(&args.funds)?,
+        },
+    });
+    let fee = json!({
+        "amount": coins(&args.fee)?,
+        "gas": args.gas.to_string(),
+    });
+    let signatures = match key {
+        None => vec![],
+        Some(key) => {
+            let chain_id = args.chain_id.as_ref().ok_or(Error::MissingChainId {})?;
+            let sign_doc = json!({
+                "account_number": args.account_number.to_string(),
+                "chain_id": chain_id,
+                "fee": fee,
+                "memo": args.memo,
+                "msgs": [execute],
+                "sequence": args.sequence.to_string(),
+            });
+            vec![sign(&key, &sign_doc)?]
+        }
+    };
+    Ok(json!({
+        "type": "core/StdTx",
+        "value": {
+            "msg": [execute],
+            "fee": fee,
+            "signatures": signatures,
+            "memo": args.memo,
+        },
+    }))
+}
+
+#[derive(Args)]
+pub struct VoucherArgs {
+    id: String,
+    /// File holding the hex encoded secp256k1 private key of the voucher signer
+    #[arg(long)]
+    key_file: PathBuf,
+    /// Governance instance the voucher is redeemed at
+    #[arg(long)]
+    governance: String,
+    /// Part of the base price waived, like 0.5
+    #[arg(long, default_value = "1")]
+    discount: String,
+    /// The only address allowed to redeem the voucher
+    #[arg(long)]
+    recipient: Option<String>,
+    /// Block time in seconds after which the voucher is refused
+    #[arg(long)]
+    expires: Option<u64>,
+}
+
+/// A voucher signed with `--key-file`, ready for `subscribe --voucher`.
+pub fn voucher(args: VoucherArgs) -> Result<Value, Error> {
+    let key = read_key(&args.key_file)?;
+    let mut voucher = Voucher {
+        id: args.id,
+        discount: discount(args.discount)?,
+        recipient: args.recipient.map(|value| address(&value)).transpose()?,
+        expires: args.expires,
+        signature: Binary::default(),
+    };
+    let signature: Signature = key.sign(&voucher.message(&address(&args.governance)?)?);
+    voucher.signature = Binary(signature.to_vec());
+    Ok(serde_json::to_value(voucher)?)
+}
+
+/// The hex compressed public key of `path`, to configure as the voucher signer.
+pub fn signer_key(path: &PathBuf) -> Result<Value, Error> {
+    let public = read_key(path)?.verifying_key().to_encoded_point(true);
+    Ok(Value::String(hex::encode(public.as_bytes())))
+}
+
+/// Parses `1000uluna` style coins, sorted by denom as the chain requires.
+pub fn coins(values: &[String]) -> Result<Vec<Coin>, Error> {
+    let mut coins = vec![];
+    for value in values {
+        let split = value
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(value.len());
+        let (amount, denom) = value.split_at(split);
+        let valid_denom = denom.len() >= 3 && denom.chars().all(|c| c.is_ascii_lowercase());
+        match amount.parse::<u128>() {
+            Ok(amount) if valid_denom => coins.push(Coin::new(amount, denom)),
+            _ => {
+                return Err(Error::InvalidCoin {
+                    coin: value.clone(),
+                })
+            }
+        }
+    }
+    coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+    Ok(coins)
+}
+
+fn read_key(path: &PathBuf) -> Result<SigningKey, Error> {
+    let contents = fs::read_to_string(path).context(Io { path: path.clone() })?;
+    let invalid = |reason: String| Error::InvalidKey { reason };
+    let bytes = hex::decode(contents.trim()).map_err(|err| invalid(err.to_string()))?;
+    SigningKey::from_slice(&bytes).map_err(|err| invalid(err.to_string()))
+}
+
+/// Bech32 of the RIPEMD-160 of the SHA-256 of the compressed public key.
+fn account_address(key: &SigningKey, prefix: &str) -> Result<String, Error> {
+    let public = key.verifying_key().to_encoded_point(true);
+    let hash = Ripemd160::digest(Sha256::digest(public.as_bytes()));
+    let invalid = |reason: String| Error::InvalidAddress {
+        address: prefix.to_string(),
+        reason,
+    };
+    let hrp = Hrp::parse(prefix).map_err(|err| invalid(err.to_string()))?;
+    bech32::encode::<Bech32>(hrp, &hash).map_err(|err| invalid(err.to_string()))
+}
+
+/// Signs the SHA-256 of the sign doc with its keys sorted, as amino JSON signing expects.
+fn sign(key: &SigningKey, sign_doc: &Value) -> Result<Value, Error> {
+    let signature: Signature = key.sign(&serde_json::to_vec(sign_doc)?);
+    let public = key.verifying_key().to_encoded_point(true);
+    Ok(json!({
+        "pub_key": {
+            "type": "tendermint/PubKeySecp256k1",
+            "value": Binary::from(public.as_bytes()),
+        },
+        "signature": Binary(signature.to_vec()),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k256::ecdsa::signature::Verifier;
+    use k256::ecdsa::VerifyingKey;
+
+    const KEY: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn args() -> TxArgs {
+        TxArgs {
+            contract: None,
+            sender: None,
+            funds: vec![],
+            key_file: None,
+            chain_id: Some("columbus-4".to_string()),
+            account_number: 7,
+            sequence: 3,
+            gas: 200_000,
+            fee: vec!["3000uluna".to_string()],
+            memo: String::new(),
+            prefix: "terra".to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_coins() {
+        let parsed = coins(&["5ukrw".to_string(), "1000uluna".to_string()]).unwrap();
+        assert_eq!(parsed, vec![Coin::new(5, "ukrw"), Coin::new(1000, "uluna")]);
+        assert!(coins(&["uluna".to_string()]).is_err());
+        assert!(coins(&["1000".to_string()]).is_err());
+        assert!(coins(&["10 uluna".to_string()]).is_err());
+    }
+
+    #[test]
+    fn bare_message_without_contract() {
+        let msg = json!({"subscribe": {"name": "alice"}});
+        assert_eq!(render(msg.clone(), &args()).unwrap(), msg);
+
+        let mut with_funds = args();
+        with_funds.funds = vec!["100uluna".to_string()];
+        assert!(matches!(
+            render(msg, &with_funds),
+            Err(Error::NeedsContract { .. })
+        ));
+    }
+
+    #[test]
+    fn signs_with_the_key_file() {
+        let path = std::env::temp_dir().join(format!("tns-key-{}", std::process::id()));
+        fs::write(&path, KEY).unwrap();
+        let key = read_key(&path).unwrap();
+        let sender = account_address(&key, "terra").unwrap();
+        assert!(sender.starts_with("terra1"));
+
+        let mut tx = args();
+        tx.key_file = Some(path.clone());
+        tx.contract = Some(sender.clone());
+        tx.funds = vec!["100uluna".to_string()];
+        let msg = json!({"subscribe": {"name": "alice"}});
+        let signed = render(msg.clone(), &tx).unwrap();
+
+        let execute = &signed["value"]["msg"][0]["value"];
+        assert_eq!(execute["sender"], json!(sender));
+        assert_eq!(
+            execute["execute_msg"],
+            json!(Binary(serde_json::to_vec(&msg).unwrap()))
+        );
+        assert_eq!(
+            execute["coins"],
+            json!([{"denom": "uluna", "amount": "100"}])
+        );
+
+        let sign_doc = json!({
+            "account_number": "7",
+            "chain_id": "columbus-4",
+            "fee": signed["value"]["fee"],
+            "memo": "",
+            "msgs": signed["value"]["msg"],
+            "sequence": "3",
+        });
+        let signature: Binary =
+            serde_json::from_value(signed["value"]["signatures"][0]["signature"].clone()).unwrap();
+        let signature = Signature::from_slice(signature.as_slice()).unwrap();
+        let verifier = VerifyingKey::from(&key);
+        assert!(verifier
+            .verify(&serde_json::to_vec(&sign_doc).unwrap(), &signature)
+            .is_ok());
+
+        tx.sender = Some("terra1x46rqay4d3cssq8gxxvqz8xt6nwlz4td20k38v".to_string());
+        assert!(matches!(
+            render(msg, &tx),
+            Err(Error::SenderMismatch { .. })
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn signs_vouchers_governance_accepts() {
+        let path = std::env::temp_dir().join(format!("tns-voucher-key-{}", std::process::id()));
+        fs::write(&path, KEY).unwrap();
+        let governance = "terra1x46rqay4d3cssq8gxxvqz8xt6nwlz4td20k38v";
+        let args = VoucherArgs {
+            id: "launch-1".to_string(),
+            key_file: path.clone(),
+            governance: governance.to_string(),
+            discount: "0.5".to_string(),
+            recipient: None,
+            expires: Some(100),
+        };
+        let voucher: Voucher = serde_json::from_value(voucher(args).unwrap()).unwrap();
+        assert_eq!(voucher.expires, Some(100));
+
+        let signer = match signer_key(&path).unwrap() {
+            Value::String(signer) => hex::decode(signer).unwrap(),
+            other => panic!("expected a hex key, got {}", other),
+        };
+        let verifier = VerifyingKey::from_sec1_bytes(&signer).unwrap();
+        let signature = Signature::from_slice(voucher.signature.as_slice()).unwrap();
+        let message = voucher.message(&governance.into()).unwrap();
+        assert!(verifier.verify(&message, &signature).is_ok());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,36 +1,79 @@
+use crate::error::ContractError;
+use crate::legacy;
 use crate::msg::HandleMsg::Signup;
-use crate::msg::{HandleMsg, InitMsg, QueryMsg};
+use crate::msg::{
+    AllowlistEntry, AllowlistResponse, HandleMsg, InitMsg, MigrateMsg, PaidAmountResponse,
+    PaymentEntry, PaymentsResponse, PriceResponse, QueryMsg, ReferralResponse, ReferrersResponse,
+    RenewBatchEntry, RenewBatchResponse, SubscribeBatchEntry, SubscribeBatchResponse,
+    SubscriptionInfoResponse, SubscriptionState, SubscriptionStatusResponse, SweepResponse,
+    UnbondingResponse, UnsubscribeBatchEntry, UnsubscribeBatchResponse, VersionHistoryResponse,
+    Voucher, VoucherResponse,
+};
 use crate::state::{
-    owner_cfg_read, owner_cfg_store, payments_delete, payments_read, payments_store, Config,
+    allowlist_read, allowlist_store, contract_version_read, delegations_read, delegations_store,
+    expiries_read, expiries_store, expiry_key, lapsed_read, lapsed_store, last_seen_read,
+    last_seen_store, owner_cfg_read, owner_cfg_store, payments_delete, payments_range,
+    payments_read, payments_store, pending_read, pending_store, referrals_read, referrals_store,
+    registrations_read, registrations_store, save_version, unbonding_read, unbonding_store,
+    version_history_read, vouchers_read, vouchers_store, Allowance, Config, Decay, Premium,
+    Referral, Registration, Stake, Staking, Unbonding, CONTRACT_NAME,
 };
 use cosmwasm_std::{
-    to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
-    InitResponse, Querier, StdError, StdResult, Storage, Uint128, WasmMsg,
+    log, to_binary, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, Env, Extern, HandleResponse,
+    HumanAddr, InitResponse, LogAttribute, MigrateResponse, MigrateResult, Order, Querier,
+    StakingMsg, StdError, StdResult, Storage, Uint128, WasmMsg,
+};
+use k256::ecdsa::signature::Verifier;
+use k256::ecdsa::{Signature, VerifyingKey};
+use name_service::msg::HandleMsg::{
+    Deregister, DeregisterBatch, Register, RegisterBatch, RegisterReserved, Renew as NSRenew,
+    SetNameStatus,
 };
-use name_service::msg::HandleMsg::{Deregister, Register};
 use name_service::msg::{InitHook, NSInitMsg};
-use name_service::state::Name;
+use name_service::state::{Name, NameStatus};
 
 const LUNA: &str = "uluna";
+const DEFAULT_LIMIT: u32 = 10;
+const MAX_LIMIT: u32 = 30;
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: InitMsg,
 ) -> StdResult<InitResponse> {
+    if let Some(premium) = &msg.premium {
+        if premium.window == 0 || premium.decay == (Decay::Exponential { half_life: 0 }) {
+            return Err(ContractError::InvalidPremium {}.into());
+        }
+    }
+    check_referral_share(&msg.referral_share)?;
+    check_voucher_signer(&msg.voucher_signer)?;
+    check_staking(&msg.staking)?;
     let config = Config {
-        owner: env.message.sender,
+        owner: env.message.sender.clone(),
         name_service_address: Default::default(),
+        treasury: msg.treasury.unwrap_or_else(|| env.message.sender.clone()),
+        base_price: msg.base_price,
+        registration_period: msg.registration_period,
+        grace_period: msg.grace_period,
+        premium: msg.premium,
+        sweep_reward: msg.sweep_reward,
+        referral_share: msg.referral_share,
+        voucher_signer: msg.voucher_signer,
+        staking: msg.staking,
     };
-    owner_cfg_store(&mut deps.storage).save(&config).unwrap();
+    owner_cfg_store(&mut deps.storage).save(&config)?;
+    save_version(&mut deps.storage, &env)?;
+    last_seen_store(&mut deps.storage).save(&env.block.time)?;
     let response = InitResponse {
         messages: vec![CosmosMsg::Wasm(WasmMsg::Instantiate {
             code_id: msg.nameservice_code_id,
             msg: to_binary(&NSInitMsg {
                 hook: Some(InitHook {
-                    contract_addr: env.contract.address,
-                    msg: to_binary(&Signup {}).unwrap(),
+                    contract_addr: env.contract.address.clone(),
+                    msg: to_binary(&Signup {})?,
                 }),
+                listener: Some(env.contract.address),
             })?,
             send: vec![],
             label: None,
@@ -40,94 +83,974 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     Ok(response)
 }
 
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    msg: MigrateMsg,
+) -> MigrateResult {
+    check_referral_share(&msg.referral_share)?;
+    check_voucher_signer(&msg.voucher_signer)?;
+    check_staking(&msg.staking)?;
+    match contract_version_read(&deps.storage).may_load()? {
+        None => legacy::migrate_v0(deps, &env, msg)?,
+        Some(stored) if stored.contract != CONTRACT_NAME => {
+            return Err(ContractError::CannotMigrate {
+                from: stored.contract,
+                to: CONTRACT_NAME.to_string(),
+            }
+            .into())
+        }
+        Some(_) => {
+            let mut config = owner_cfg_read(&deps.storage).load()?;
+            config.treasury = msg.treasury.unwrap_or(config.treasury);
+            config.base_price = msg.base_price.unwrap_or(config.base_price);
+            config.registration_period = msg
+                .registration_period
+                .unwrap_or(config.registration_period);
+            config.grace_period = msg.grace_period.unwrap_or(config.grace_period);
+            config.premium = msg.premium.or(config.premium);
+            config.sweep_reward = msg.sweep_reward.or(config.sweep_reward);
+            config.referral_share = msg.referral_share.or(config.referral_share);
+            config.voucher_signer = msg.voucher_signer.or(config.voucher_signer);
+            config.staking = msg.staking.or(config.staking);
+            owner_cfg_store(&mut deps.storage).save(&config)?;
+        }
+    }
+    index_expiries(&mut deps.storage)?;
+    let version = save_version(&mut deps.storage, &env)?;
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![log("action", "migrate"), log("version", version.version)],
+        data: None,
+    })
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    match msg {
-        HandleMsg::Subscribe { name } => handle_subscribe(deps, env, name),
+    last_seen_store(&mut deps.storage).save(&env.block.time)?;
+    let res = match msg {
+        HandleMsg::Subscribe {
+            name,
+            periods,
+            referrer,
+            voucher,
+        } => handle_subscribe(deps, env, name, periods, referrer, voucher),
         HandleMsg::Unsubscribe { name } => handle_unsubscribe(deps, env, name),
+        HandleMsg::Renew { name, periods } => handle_renew(deps, env, name, periods),
+        HandleMsg::RenewBatch { names, periods } => handle_renew_batch(deps, env, names, periods),
+        HandleMsg::SubscribeBatch {
+            names,
+            periods,
+            referrer,
+        } => handle_subscribe_batch(deps, env, names, periods, referrer),
+        HandleMsg::UnsubscribeBatch { names } => handle_unsubscribe_batch(deps, env, names),
         HandleMsg::Signup {} => handle_signup(deps, env),
+        HandleMsg::Sweep { limit } => handle_sweep(deps, env, limit),
+        HandleMsg::ClaimReferral {} => handle_claim_referral(deps, env),
+        HandleMsg::Stake { name } => handle_stake(deps, env, name),
+        HandleMsg::ClaimStake {} => handle_claim_stake(deps, env),
+        HandleMsg::WithdrawRewards {} => handle_withdraw_rewards(deps, env),
+        HandleMsg::SetAllowlist { entries } => handle_set_allowlist(deps, env, entries),
+        HandleMsg::SetNameStatus { names, status } => {
+            handle_set_name_status(deps, env, names, status)
+        }
+        HandleMsg::RegisterReserved { name, owner } => {
+            handle_register_reserved(deps, env, name, owner)
+        }
+        HandleMsg::NameRegistered { name, owner } => handle_name_registered(deps, env, name, owner),
+        HandleMsg::NameTransferred { name, owner } => {
+            handle_name_transferred(deps, env, name, owner)
+        }
+    };
+    Ok(res?)
+}
+
+pub fn handle_name_registered<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    if env.message.sender != config.name_service_address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let registration = match pending_read(&deps.storage).may_load(name.as_bytes())? {
+        Some(registration) if registration.owner == owner => registration,
+        _ => return Err(ContractError::NoPendingRegistration {}),
+    };
+    pending_store(&mut deps.storage).remove(name.as_bytes());
+    if !registration.paid.is_zero() {
+        let paid = payments_read(&deps.storage, registration.payer.clone())
+            .unwrap_or_else(|_| Uint128::zero());
+        payments_store(
+            &mut deps.storage,
+            registration.payer.clone(),
+            paid + registration.paid,
+        )?;
+    }
+    save_registration(&mut deps.storage, &name, &registration)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "name_registered"),
+            log("name", name),
+            log("owner", registration.owner),
+            log("expires", registration.expires),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_name_transferred<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    if env.message.sender != config.name_service_address {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut registrations = registrations_store(&mut deps.storage);
+    if let Some(mut registration) = registrations.may_load(name.as_bytes())? {
+        registration.owner = owner.clone();
+        registrations.save(name.as_bytes(), &registration)?;
+    }
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "name_transferred"),
+            log("name", name),
+            log("owner", owner),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_set_name_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<String>,
+    status: NameStatus,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    if env.message.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let log = vec![
+        log("action", "set_name_status"),
+        log("name", names.join(",")),
+        log("status", status.as_str()),
+    ];
+    let message = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
+        msg: to_binary(&SetNameStatus { names, status })?,
+        send: vec![],
+    });
+    Ok(HandleResponse {
+        messages: vec![message],
+        log,
+        data: None,
+    })
+}
+
+pub fn handle_register_reserved<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    owner: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    if env.message.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
     }
+    let expires = env.block.time + config.registration_period;
+    pending_store(&mut deps.storage).save(
+        name.as_bytes(),
+        &Registration {
+            owner: owner.clone(),
+            expires,
+            payer: owner.clone(),
+            paid: Uint128::zero(),
+            stake: None,
+        },
+    )?;
+    let message = CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
+        msg: to_binary(&RegisterReserved {
+            name_c: Name {
+                value: name.clone(),
+                owner: deps.api.canonical_address(&owner)?,
+            },
+            expires: Some(expires),
+        })?,
+        send: vec![],
+    });
+    Ok(HandleResponse {
+        messages: vec![message],
+        log: vec![
+            log("action", "register_reserved"),
+            log("name", name),
+            log("owner", owner),
+            log("expires", expires),
+        ],
+        data: None,
+    })
 }
 
 pub fn handle_signup<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-) -> StdResult<HandleResponse> {
-    let mut config = owner_cfg_read(&deps.storage).load().unwrap();
-    config.name_service_address = env.message.sender;
+) -> Result<HandleResponse, ContractError> {
+    let mut config = owner_cfg_read(&deps.storage).load()?;
+    config.name_service_address = env.message.sender.clone();
     owner_cfg_store(&mut deps.storage).save(&config)?;
-    Ok(Default::default())
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![log("action", "signup"), log("owner", env.message.sender)],
+        data: None,
+    })
 }
 
 pub fn handle_subscribe<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     name: String,
-) -> StdResult<HandleResponse> {
-    let sent_value = env.message.sent_funds.get(0);
-    if sent_value.is_none()
-        || sent_value.unwrap().denom != LUNA
-        || sent_value.unwrap().amount <= Uint128::zero()
-    {
-        return Err(StdError::generic_err("No sent value found."));
+    periods: Option<u64>,
+    referrer: Option<HumanAddr>,
+    voucher: Option<Voucher>,
+) -> Result<HandleResponse, ContractError> {
+    let sent = sent_luna(&env);
+    let config = initialized_config(&deps.storage)?;
+    check_referrer(&deps.api, &referrer, &env.message.sender)?;
+    let (base_price, expires) = term(&config, periods, env.block.time)?;
+    let share = match &voucher {
+        Some(voucher) => check_voucher(&deps.storage, &env, &config, voucher)?,
+        None => allowlist_read(&deps.storage)
+            .may_load(env.message.sender.as_str().as_bytes())?
+            .map_or_else(Decimal::zero, |allowance| allowance.discount),
+    };
+    let Quote {
+        previous,
+        premium,
+        discount,
+        price,
+    } = quote(
+        &deps.storage,
+        &config,
+        &name,
+        base_price,
+        share,
+        env.block.time,
+    )?;
+    // Free subscriptions need not send anything.
+    let sent_amount = match sent {
+        Err(ContractError::NoFunds {}) if price.is_zero() => Uint128::zero(),
+        sent => sent?,
+    };
+    if sent_amount < price {
+        return Err(ContractError::InsufficientFunds {
+            price,
+            sent: sent_amount,
+        });
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if let Some(previous) = previous {
+        messages.push(replace_lapsed(deps, &config, &name, &previous)?);
     }
-    let sent_amount = sent_value.unwrap().amount;
 
-    payments_store(&mut deps.storage, env.message.sender.clone(), sent_amount).unwrap();
-    let adr = owner_cfg_read(&deps.storage)
-        .load()
-        .unwrap()
-        .name_service_address;
+    let held = Uint128(sent_amount.u128() - premium.u128());
+    let referral = credit_referral(&mut deps.storage, &config, &referrer, held)?;
+    redeem_discount(&mut deps.storage, &env.message.sender, &voucher)?;
+    lapsed_store(&mut deps.storage).remove(name.as_bytes());
+    pending_store(&mut deps.storage).save(
+        name.as_bytes(),
+        &Registration {
+            owner: env.message.sender.clone(),
+            expires,
+            payer: env.message.sender.clone(),
+            paid: Uint128(held.u128() - referral.u128()),
+            stake: None,
+        },
+    )?;
+    let mut log = vec![
+        log("action", "subscribe"),
+        log("name", &name),
+        log("owner", &env.message.sender),
+        log("amount", sent_amount),
+        log("denom", LUNA),
+        log("premium", premium),
+        log("expires", expires),
+    ];
+    log.extend(referral_log(&referrer, referral));
+    log.extend(discount_log(&voucher, discount));
     let msg = Register {
         name_c: Name {
             value: name,
             owner: deps.api.canonical_address(&env.message.sender)?,
         },
+        expires: Some(expires),
     };
-    let message = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: adr,
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
         msg: to_binary(&msg)?,
         send: vec![],
-    });
+    }));
+    if !premium.is_zero() {
+        messages.push(
+            BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: config.treasury,
+                amount: vec![Coin {
+                    denom: LUNA.to_string(),
+                    amount: premium,
+                }],
+            }
+            .into(),
+        );
+    }
     let res = HandleResponse {
-        messages: vec![message],
-        log: vec![],
+        messages,
+        log,
         data: None,
     };
 
     Ok(res)
 }
 
+/// Extends the term of `name` for its owner while it is within its term or grace period.
+/// The payment joins what the registration holds, which moves to the owner if someone
+/// else paid for it.
+pub fn handle_renew<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    periods: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    let sent = sent_luna(&env);
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let (registration, price, expires) = renewal(&deps.storage, &config, &env, &name, periods)?;
+    let sent_amount = match sent {
+        Err(ContractError::NoFunds {}) if price.is_zero() => Uint128::zero(),
+        sent => sent?,
+    };
+    if sent_amount < price {
+        return Err(ContractError::InsufficientFunds {
+            price,
+            sent: sent_amount,
+        });
+    }
+
+    let renewed = Registration {
+        expires,
+        payer: env.message.sender.clone(),
+        paid: registration.paid + sent_amount,
+        ..registration.clone()
+    };
+    save_renewal(&mut deps.storage, &name, &registration, &renewed)?;
+    Ok(HandleResponse {
+        messages: vec![renew_message(&config, &name, expires)?],
+        log: vec![
+            log("action", "renew"),
+            log("name", name),
+            log("owner", env.message.sender),
+            log("amount", sent_amount),
+            log("denom", LUNA),
+            log("expires", expires),
+        ],
+        data: None,
+    })
+}
+
+/// Renews every name or none of them, each priced as by `Renew`. Anything sent beyond the
+/// total is held for the first name.
+pub fn handle_renew_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<String>,
+    periods: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    check_batch(&names)?;
+    let sent = sent_luna(&env);
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let mut renewals = vec![];
+    let mut total = 0u128;
+    for name in &names {
+        let renewal = renewal(&deps.storage, &config, &env, name, periods)?;
+        total = total
+            .checked_add(renewal.1.u128())
+            .ok_or(ContractError::InvalidPeriods {})?;
+        renewals.push(renewal);
+    }
+    let price = Uint128(total);
+    let sent_amount = match sent {
+        Err(ContractError::NoFunds {}) if price.is_zero() => Uint128::zero(),
+        sent => sent?,
+    };
+    if sent_amount < price {
+        return Err(ContractError::InsufficientFunds {
+            price,
+            sent: sent_amount,
+        });
+    }
+
+    let mut excess = sent_amount.u128() - total;
+    let mut messages = vec![];
+    let mut results = vec![];
+    for (name, (registration, price, expires)) in names.into_iter().zip(renewals) {
+        let paid = Uint128(price.u128() + excess);
+        excess = 0;
+        let renewed = Registration {
+            expires,
+            payer: env.message.sender.clone(),
+            paid: registration.paid + paid,
+            ..registration.clone()
+        };
+        save_renewal(&mut deps.storage, &name, &registration, &renewed)?;
+        messages.push(renew_message(&config, &name, expires)?);
+        results.push(RenewBatchEntry {
+            name,
+            paid,
+            expires,
+        });
+    }
+
+    let paid: Vec<_> = results.iter().map(|entry| entry.paid.to_string()).collect();
+    let expires: Vec<_> = results
+        .iter()
+        .map(|entry| entry.expires.to_string())
+        .collect();
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "renew_batch"),
+            log("name", batch_names(results.iter().map(|entry| &entry.name))),
+            log("owner", &env.message.sender),
+            log("amount", sent_amount),
+            log("denom", LUNA),
+            log("paid", paid.join(",")),
+            log("expires", expires.join(",")),
+        ],
+        data: Some(to_binary(&RenewBatchResponse { results })?),
+    })
+}
+
+/// Checks that the sender may renew `name` now, returning its registration with the price
+/// and end of `periods` more registration periods.
+fn renewal<S: Storage>(
+    storage: &S,
+    config: &Config,
+    env: &Env,
+    name: &str,
+    periods: Option<u64>,
+) -> Result<(Registration, Uint128, u64), ContractError> {
+    let registration = match registrations_read(storage).may_load(name.as_bytes())? {
+        None => return Err(ContractError::NameNotFound {}),
+        Some(registration) if registration.owner != env.message.sender => {
+            return Err(ContractError::NotOwner {})
+        }
+        Some(registration) => registration,
+    };
+    if registration.stake.is_some() {
+        return Err(ContractError::NameStaked {});
+    }
+    if env.block.time >= registration.expires.saturating_add(config.grace_period) {
+        return Err(ContractError::NameLapsed {});
+    }
+    let (price, expires) = term(config, periods, registration.expires.max(env.block.time))?;
+    Ok((registration, price, expires))
+}
+
+/// Replaces `registration` of `name` by its `renewed` term, moving the payment to the new
+/// payer and the name within the expiry index.
+fn save_renewal<S: Storage>(
+    storage: &mut S,
+    name: &str,
+    registration: &Registration,
+    renewed: &Registration,
+) -> StdResult<()> {
+    release_payment(storage, registration)?;
+    remove_registration(storage, name, registration);
+    if !renewed.paid.is_zero() {
+        let paid =
+            payments_read(storage, renewed.payer.clone()).unwrap_or_else(|_| Uint128::zero());
+        payments_store(storage, renewed.payer.clone(), paid + renewed.paid)?;
+    }
+    save_registration(storage, name, renewed)
+}
+
+fn renew_message(config: &Config, name: &str, expires: u64) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address.clone(),
+        msg: to_binary(&NSRenew {
+            name: name.to_string(),
+            expires: Some(expires),
+        })?,
+        send: vec![],
+    }))
+}
+
+/// Holds `name` with the sent funds, less any premium, delegated to the configured validator
+/// holding the least stake.
+pub fn handle_stake<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> Result<HandleResponse, ContractError> {
+    let sent_amount = sent_luna(&env)?;
+    let config = initialized_config(&deps.storage)?;
+    let staking = match &config.staking {
+        Some(staking) => staking,
+        None => return Err(ContractError::StakingDisabled {}),
+    };
+    let (previous, premium) = current_premium(&deps.storage, &config, &name, env.block.time)?;
+    let price = staking.stake + premium;
+    if sent_amount < price {
+        return Err(ContractError::InsufficientFunds {
+            price,
+            sent: sent_amount,
+        });
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if let Some(previous) = previous {
+        messages.push(replace_lapsed(deps, &config, &name, &previous)?);
+    }
+
+    let stake = Stake {
+        validator: least_delegated(&deps.storage, staking)?,
+        amount: Uint128(sent_amount.u128() - premium.u128()),
+    };
+    delegations_store(&mut deps.storage).update(stake.validator.as_str().as_bytes(), |total| {
+        Ok(total.unwrap_or_default() + stake.amount)
+    })?;
+    lapsed_store(&mut deps.storage).remove(name.as_bytes());
+    pending_store(&mut deps.storage).save(
+        name.as_bytes(),
+        &Registration {
+            owner: env.message.sender.clone(),
+            expires: u64::MAX,
+            payer: env.message.sender.clone(),
+            paid: Uint128::zero(),
+            stake: Some(stake.clone()),
+        },
+    )?;
+    let log = vec![
+        log("action", "stake"),
+        log("name", &name),
+        log("owner", &env.message.sender),
+        log("amount", sent_amount),
+        log("denom", LUNA),
+        log("premium", premium),
+        log("validator", &stake.validator),
+        log("stake", stake.amount),
+    ];
+    let msg = Register {
+        name_c: Name {
+            value: name,
+            owner: deps.api.canonical_address(&env.message.sender)?,
+        },
+        expires: None,
+    };
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
+        msg: to_binary(&msg)?,
+        send: vec![],
+    }));
+    // Withdrawing with a recipient also points the contract's withdraw address at the
+    // treasury, so rewards paid out whenever a delegation changes go there as well.
+    messages.push(
+        StakingMsg::Delegate {
+            validator: stake.validator.clone(),
+            amount: Coin {
+                denom: LUNA.to_string(),
+                amount: stake.amount,
+            },
+        }
+        .into(),
+    );
+    messages.push(
+        StakingMsg::Withdraw {
+            validator: stake.validator,
+            recipient: Some(config.treasury.clone()),
+        }
+        .into(),
+    );
+    if !premium.is_zero() {
+        messages.push(
+            BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: config.treasury,
+                amount: vec![Coin {
+                    denom: LUNA.to_string(),
+                    amount: premium,
+                }],
+            }
+            .into(),
+        );
+    }
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: None,
+    })
+}
+
+/// Drops the lapsed registration a new subscription to `name` replaces, returning the
+/// message deregistering it from the name service.
+fn replace_lapsed<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    config: &Config,
+    name: &str,
+    previous: &Registration,
+) -> StdResult<CosmosMsg> {
+    release_payment(&mut deps.storage, previous)?;
+    remove_registration(&mut deps.storage, name, previous);
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address.clone(),
+        msg: to_binary(&Deregister {
+            name_c: Name {
+                value: name.to_string(),
+                owner: deps.api.canonical_address(&previous.owner)?,
+            },
+        })?,
+        send: vec![],
+    }))
+}
+
+/// The first of the configured validators holding the least stake.
+fn least_delegated<S: Storage>(storage: &S, staking: &Staking) -> Result<HumanAddr, ContractError> {
+    let mut least: Option<(Uint128, &HumanAddr)> = None;
+    for validator in &staking.validators {
+        let total = delegations_read(storage)
+            .may_load(validator.as_str().as_bytes())?
+            .unwrap_or_default();
+        if least.map_or(true, |(lowest, _)| total < lowest) {
+            least = Some((total, validator));
+        }
+    }
+    match least {
+        Some((_, validator)) => Ok(validator.clone()),
+        None => Err(ContractError::InvalidStaking {}),
+    }
+}
+
+/// What undelegating `stake` released and the message doing it, `None` if nothing is left
+/// to undelegate.
+struct Unstaked {
+    amount: Uint128,
+    message: Option<CosmosMsg>,
+}
+
+/// Undelegates `stake` and records it for `staker` to claim after the unbonding period. The
+/// chain reports less bonded to a validator than the stakes delegated to it once the
+/// validator was slashed, and each stake leaving it then takes its share of what is left.
+fn unstake<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    config: &Config,
+    staker: &HumanAddr,
+    stake: &Stake,
+) -> StdResult<Unstaked> {
+    let key = stake.validator.as_str().as_bytes();
+    let total = delegations_read(&deps.storage)
+        .may_load(key)?
+        .unwrap_or_default();
+    let bonded = deps
+        .querier
+        .query_delegation(env.contract.address.clone(), stake.validator.clone())?
+        .map_or(0, |delegation| delegation.amount.amount.u128());
+    let amount = if bonded >= total.u128() {
+        stake.amount
+    } else {
+        stake.amount * Decimal::from_ratio(bonded, total)
+    };
+    match total.u128().saturating_sub(stake.amount.u128()) {
+        0 => delegations_store(&mut deps.storage).remove(key),
+        rest => delegations_store(&mut deps.storage).save(key, &Uint128(rest))?,
+    }
+    if amount.is_zero() {
+        return Ok(Unstaked {
+            amount,
+            message: None,
+        });
+    }
+    let unbonding_period = config
+        .staking
+        .as_ref()
+        .map_or(0, |staking| staking.unbonding_period);
+    let entry = Unbonding {
+        amount,
+        release: env.block.time + unbonding_period,
+    };
+    unbonding_store(&mut deps.storage).update(staker.as_str().as_bytes(), |entries| {
+        let mut entries = entries.unwrap_or_default();
+        entries.push(entry);
+        Ok(entries)
+    })?;
+    let message = StakingMsg::Undelegate {
+        validator: stake.validator.clone(),
+        amount: Coin {
+            denom: LUNA.to_string(),
+            amount,
+        },
+    }
+    .into();
+    Ok(Unstaked {
+        amount,
+        message: Some(message),
+    })
+}
+
+/// Pays the sender's stakes whose unbonding period has passed, in full. They are only paid
+/// once the contract holds them on top of what it owes for refunds and referrals, and stay
+/// waiting until then.
+pub fn handle_claim_stake<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let key = env.message.sender.as_str().as_bytes();
+    let entries = unbonding_read(&deps.storage)
+        .may_load(key)?
+        .unwrap_or_default();
+    let (released, waiting): (Vec<_>, Vec<_>) = entries
+        .into_iter()
+        .partition(|entry| entry.release <= env.block.time);
+    let amount: u128 = released.iter().map(|entry| entry.amount.u128()).sum();
+    if amount == 0 {
+        return Err(ContractError::NoUnbondedStake {});
+    }
+    let balance = deps
+        .querier
+        .query_balance(&env.contract.address, LUNA)?
+        .amount
+        .u128();
+    if balance.saturating_sub(reserved_funds(&deps.storage)?) < amount {
+        return Err(ContractError::StakeNotReleased {});
+    }
+    if waiting.is_empty() {
+        unbonding_store(&mut deps.storage).remove(key);
+    } else {
+        unbonding_store(&mut deps.storage).save(key, &waiting)?;
+    }
+    Ok(HandleResponse {
+        messages: vec![BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount: vec![Coin::new(amount, LUNA)],
+        }
+        .into()],
+        log: vec![
+            log("action", "claim_stake"),
+            log("owner", env.message.sender),
+            log("amount", amount),
+            log("denom", LUNA),
+        ],
+        data: None,
+    })
+}
+
+/// What the contract owes besides stakes: unclaimed referral earnings and the refunds the
+/// held payments may still be unsubscribed for.
+fn reserved_funds<S: Storage>(storage: &S) -> StdResult<u128> {
+    let mut reserved = 0u128;
+    for item in referrals_read(storage).range(None, None, Order::Ascending) {
+        let (_, referral) = item?;
+        reserved += referral
+            .earned
+            .u128()
+            .saturating_sub(referral.claimed.u128());
+    }
+    for (_, paid) in payments_range(storage, None, usize::MAX)? {
+        reserved += paid.u128() / 10;
+    }
+    Ok(reserved)
+}
+
+pub fn handle_withdraw_rewards<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    _env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let validators = delegations_read(&deps.storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| {
+            let (key, _) = item?;
+            let validator = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok(HumanAddr(validator))
+        })
+        .collect::<StdResult<Vec<_>>>()?;
+    let messages = validators
+        .iter()
+        .map(|validator| {
+            StakingMsg::Withdraw {
+                validator: validator.clone(),
+                recipient: Some(config.treasury.clone()),
+            }
+            .into()
+        })
+        .collect();
+    let validators: Vec<&str> = validators.iter().map(HumanAddr::as_str).collect();
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "withdraw_rewards"),
+            log("validator", validators.join(",")),
+            log("treasury", config.treasury),
+        ],
+        data: None,
+    })
+}
+
+fn check_staking(staking: &Option<Staking>) -> Result<(), ContractError> {
+    match staking {
+        Some(staking)
+            if staking.validators.is_empty()
+                || staking.stake.is_zero()
+                || staking.unbonding_period == 0 =>
+        {
+            Err(ContractError::InvalidStaking {})
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The base price of `periods` registration periods and the end of a term bought with them
+/// at `now`.
+fn term(config: &Config, periods: Option<u64>, now: u64) -> Result<(Uint128, u64), ContractError> {
+    let periods = periods.unwrap_or(1);
+    let length = match periods {
+        0 => None,
+        _ => config.registration_period.checked_mul(periods),
+    };
+    let expires = length.and_then(|length| now.checked_add(length));
+    let price = config.base_price.u128().checked_mul(periods.into());
+    match (price, expires) {
+        (Some(price), Some(expires)) => Ok((Uint128(price), expires)),
+        _ => Err(ContractError::InvalidPeriods {}),
+    }
+}
+
+/// What subscribing to a name costs at a given time.
+struct Quote {
+    /// The lapsed registration the subscription replaces.
+    previous: Option<Registration>,
+    premium: Uint128,
+    /// The part of the base price waived.
+    discount: Uint128,
+    /// The base price less the discount, plus the premium.
+    price: Uint128,
+}
+
+/// Prices `name` at `now` for a term costing `base_price`, of which `share` is waived.
+fn quote<S: Storage>(
+    storage: &S,
+    config: &Config,
+    name: &str,
+    base_price: Uint128,
+    share: Decimal,
+    now: u64,
+) -> Result<Quote, ContractError> {
+    let (previous, premium) = current_premium(storage, config, name, now)?;
+    let discount = base_price * share;
+    Ok(Quote {
+        previous,
+        premium,
+        discount,
+        price: Uint128(base_price.u128() - discount.u128()) + premium,
+    })
+}
+
+/// Looks up the previous registration of `name` and the premium owed on it at `now`.
+/// Fails while the previous registration is still within its term or grace period. A name
+/// that was swept still owes the premium from when it lapsed.
+fn current_premium<S: Storage>(
+    storage: &S,
+    config: &Config,
+    name: &str,
+    now: u64,
+) -> Result<(Option<Registration>, Uint128), ContractError> {
+    match registrations_read(storage).may_load(name.as_bytes())? {
+        Some(previous) => {
+            let released = previous.expires.saturating_add(config.grace_period);
+            if now < released {
+                return Err(ContractError::NameTaken {});
+            }
+            let premium = premium_at(&config.premium, now - released);
+            Ok((Some(previous), premium))
+        }
+        None => {
+            let premium = match lapsed_read(storage).may_load(name.as_bytes())? {
+                Some(released) => premium_at(&config.premium, now.saturating_sub(released)),
+                None => Uint128::zero(),
+            };
+            Ok((None, premium))
+        }
+    }
+}
+
+/// Premium owed `elapsed` seconds after a name left its grace period.
+pub fn premium_at(premium: &Option<Premium>, elapsed: u64) -> Uint128 {
+    let premium = match premium {
+        Some(premium) if elapsed < premium.window => premium,
+        _ => return Uint128::zero(),
+    };
+    match premium.decay {
+        Decay::Linear => premium
+            .start
+            .multiply_ratio(premium.window - elapsed, premium.window),
+        Decay::Exponential { half_life } => {
+            let halvings = elapsed / half_life;
+            if halvings >= 128 {
+                return Uint128::zero();
+            }
+            let current = Uint128(premium.start.u128() >> halvings);
+            let half = Uint128(current.u128() / 2);
+            Uint128(current.u128() - half.multiply_ratio(elapsed % half_life, half_life).u128())
+        }
+    }
+}
+
 pub fn handle_unsubscribe<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     name: String,
-) -> StdResult<HandleResponse> {
-    let name_service_contract_address = owner_cfg_read(&deps.storage)
-        .load()
-        .unwrap()
-        .name_service_address;
+) -> Result<HandleResponse, ContractError> {
+    let registration = match registrations_read(&deps.storage).may_load(name.as_bytes())? {
+        None => return Err(ContractError::NameNotFound {}),
+        Some(registration) if registration.owner != env.message.sender => {
+            return Err(ContractError::NotOwner {})
+        }
+        Some(registration) => registration,
+    };
+    remove_registration(&mut deps.storage, &name, &registration);
+    let config = owner_cfg_read(&deps.storage).load()?;
     let message = Deregister {
         name_c: Name {
-            value: name,
-            owner: deps.api.canonical_address(&env.message.sender).unwrap(),
+            value: name.clone(),
+            owner: deps.api.canonical_address(&env.message.sender)?,
         },
     };
 
     let exemessage = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: name_service_contract_address,
-        msg: to_binary(&message).unwrap(),
+        contract_addr: config.name_service_address.clone(),
+        msg: to_binary(&message)?,
         send: vec![],
     });
     let mut msgs: Vec<CosmosMsg> = vec![];
     msgs.push(exemessage);
 
-    let paid_amount = payments_read(&deps.storage, env.message.sender.clone()).unwrap();
-    if !paid_amount.is_zero() {
-        let coin = Coin::new(paid_amount.u128() / 10, LUNA);
+    let refund = refund_of(&registration);
+    if !registration.paid.is_zero() {
+        let coin = Coin {
+            denom: LUNA.to_string(),
+            amount: refund,
+        };
 
         msgs.push(
             BankMsg::Send {
@@ -137,60 +1060,972 @@ pub fn handle_unsubscribe<S: Storage, A: Api, Q: Querier>(
             }
             .into(),
         );
-        payments_delete(&mut deps.storage, env.message.sender).expect("No payments to delete");
+        release_payment(&mut deps.storage, &registration)?;
     }
+    let mut unbonding = 0u128;
+    let mut slashed = 0u128;
+    if let Some(stake) = &registration.stake {
+        let unstaked = unstake(deps, &env, &config, &registration.payer, stake)?;
+        msgs.extend(unstaked.message);
+        unbonding = unstaked.amount.u128();
+        slashed = stake.amount.u128() - unbonding;
+    }
+    let mut log = vec![
+        log("action", "unsubscribe"),
+        log("name", name),
+        log("owner", env.message.sender),
+        log("refund", refund),
+        log("denom", LUNA),
+    ];
+    log.extend(unbonding_log(unbonding, &[slashed]));
     let res = HandleResponse {
         messages: msgs,
-        log: vec![],
+        log,
         data: None,
     };
     Ok(res)
 }
 
-pub fn query<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    msg: QueryMsg,
-) -> StdResult<Binary> {
-    match msg {
-        QueryMsg::PaidAmountIs { address } => to_binary(&try_paidamountis(deps, address)),
-        QueryMsg::GetNameServiceAddress {} => to_binary(&get_nameservice_address(deps)),
-        QueryMsg::AddressExists { address } => to_binary(&address_exists(deps, address)),
+/// Subscribes to every name or none of them, each priced as by `Subscribe`. The allowlist
+/// discount covers as many names as the sender's quota has left, in the order given, and
+/// anything sent beyond the total is held for the first name.
+pub fn handle_subscribe_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<String>,
+    periods: Option<u64>,
+    referrer: Option<HumanAddr>,
+) -> Result<HandleResponse, ContractError> {
+    check_batch(&names)?;
+    let sent = sent_luna(&env);
+    let config = initialized_config(&deps.storage)?;
+    check_referrer(&deps.api, &referrer, &env.message.sender)?;
+    let (base_price, expires) = term(&config, periods, env.block.time)?;
+    let allowance = allowlist_read(&deps.storage)
+        .may_load(env.message.sender.as_str().as_bytes())?
+        .unwrap_or(Allowance {
+            remaining: 0,
+            discount: Decimal::zero(),
+        });
+
+    let mut quotes = vec![];
+    let mut total = 0u128;
+    for (i, name) in names.iter().enumerate() {
+        let share = if i < allowance.remaining as usize {
+            allowance.discount
+        } else {
+            Decimal::zero()
+        };
+        let quote = quote(
+            &deps.storage,
+            &config,
+            name,
+            base_price,
+            share,
+            env.block.time,
+        )?;
+        total = total
+            .checked_add(quote.price.u128())
+            .ok_or(ContractError::InvalidPeriods {})?;
+        quotes.push(quote);
+    }
+    let price = Uint128(total);
+    let sent_amount = match sent {
+        Err(ContractError::NoFunds {}) if price.is_zero() => Uint128::zero(),
+        sent => sent?,
+    };
+    if sent_amount < price {
+        return Err(ContractError::InsufficientFunds {
+            price,
+            sent: sent_amount,
+        });
     }
-}
 
-pub fn address_exists<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: HumanAddr,
-) -> StdResult<bool> {
-    if payments_read(&deps.storage, address).is_err() {
-        return Ok(false);
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let mut excess = sent_amount.u128() - total;
+    let mut total_premium = 0u128;
+    let mut total_discount = 0u128;
+    let mut total_referral = 0u128;
+    let mut lapsed = vec![];
+    let mut registered = vec![];
+    let mut results = vec![];
+    for (i, (name, quote)) in names.into_iter().zip(quotes).enumerate() {
+        if let Some(previous) = quote.previous {
+            release_payment(&mut deps.storage, &previous)?;
+            remove_registration(&mut deps.storage, &name, &previous);
+            lapsed.push(Name {
+                value: name.clone(),
+                owner: deps.api.canonical_address(&previous.owner)?,
+            });
+        }
+        if i < allowance.remaining as usize {
+            redeem_discount(&mut deps.storage, &env.message.sender, &None)?;
+        }
+        let held = Uint128(quote.price.u128() - quote.premium.u128() + excess);
+        excess = 0;
+        let referral = credit_referral(&mut deps.storage, &config, &referrer, held)?;
+        total_premium += quote.premium.u128();
+        total_discount += quote.discount.u128();
+        total_referral += referral.u128();
+        lapsed_store(&mut deps.storage).remove(name.as_bytes());
+        let paid = Uint128(held.u128() - referral.u128());
+        pending_store(&mut deps.storage).save(
+            name.as_bytes(),
+            &Registration {
+                owner: env.message.sender.clone(),
+                expires,
+                payer: env.message.sender.clone(),
+                paid,
+                stake: None,
+            },
+        )?;
+        registered.push(Name {
+            value: name.clone(),
+            owner: owner.clone(),
+        });
+        results.push(SubscribeBatchEntry {
+            name,
+            paid,
+            premium: quote.premium,
+            expires,
+        });
     }
-    Ok(true)
-}
 
-pub fn try_paidamountis<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-    address: HumanAddr,
-) -> StdResult<Uint128> {
-    let paid_amount = payments_read(&deps.storage, address)?;
-    Ok(paid_amount)
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !lapsed.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: config.name_service_address.clone(),
+            msg: to_binary(&DeregisterBatch { names: lapsed })?,
+            send: vec![],
+        }));
+    }
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
+        msg: to_binary(&RegisterBatch {
+            names: registered,
+            expires: Some(expires),
+        })?,
+        send: vec![],
+    }));
+    if total_premium != 0 {
+        messages.push(
+            BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: config.treasury,
+                amount: vec![Coin {
+                    denom: LUNA.to_string(),
+                    amount: Uint128(total_premium),
+                }],
+            }
+            .into(),
+        );
+    }
+    let paid: Vec<_> = results.iter().map(|entry| entry.paid.to_string()).collect();
+    let mut log = vec![
+        log("action", "subscribe_batch"),
+        log("name", batch_names(results.iter().map(|entry| &entry.name))),
+        log("owner", &env.message.sender),
+        log("amount", sent_amount),
+        log("denom", LUNA),
+        log("premium", total_premium),
+        log("paid", paid.join(",")),
+        log("expires", expires),
+    ];
+    log.extend(referral_log(&referrer, Uint128(total_referral)));
+    log.extend(discount_log(&None, Uint128(total_discount)));
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: Some(to_binary(&SubscribeBatchResponse { results })?),
+    })
 }
 
-pub fn get_nameservice_address<S: Storage, A: Api, Q: Querier>(
-    deps: &Extern<S, A, Q>,
-) -> StdResult<HumanAddr> {
-    Ok(owner_cfg_read(&deps.storage)
-        .load()
-        .unwrap()
-        .name_service_address)
-}
+/// Unsubscribes from every name or none of them, refunding the total in one transfer.
+pub fn handle_unsubscribe_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<String>,
+) -> Result<HandleResponse, ContractError> {
+    check_batch(&names)?;
+    let mut registrations = vec![];
+    for name in &names {
+        match registrations_read(&deps.storage).may_load(name.as_bytes())? {
+            None => return Err(ContractError::NameNotFound {}),
+            Some(registration) if registration.owner != env.message.sender => {
+                return Err(ContractError::NotOwner {})
+            }
+            Some(registration) => registrations.push(registration),
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::msg::HandleMsg::{Subscribe, Unsubscribe};
-    use cosmwasm_std::coin;
-    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let mut deregistered = vec![];
+    let mut results = vec![];
+    let mut total_refund = 0u128;
+    let mut unstaked = vec![];
+    let mut unbonding = 0u128;
+    let mut slashed = vec![];
+    for (name, registration) in names.into_iter().zip(registrations) {
+        remove_registration(&mut deps.storage, &name, &registration);
+        release_payment(&mut deps.storage, &registration)?;
+        match &registration.stake {
+            Some(stake) => {
+                let released = unstake(deps, &env, &config, &registration.payer, stake)?;
+                unstaked.extend(released.message);
+                unbonding += released.amount.u128();
+                slashed.push(stake.amount.u128() - released.amount.u128());
+            }
+            None => slashed.push(0),
+        }
+        let refund = refund_of(&registration);
+        total_refund += refund.u128();
+        deregistered.push(Name {
+            value: name.clone(),
+            owner: owner.clone(),
+        });
+        results.push(UnsubscribeBatchEntry { name, refund });
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: config.name_service_address,
+        msg: to_binary(&DeregisterBatch {
+            names: deregistered,
+        })?,
+        send: vec![],
+    })];
+    if total_refund != 0 {
+        messages.push(
+            BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: env.message.sender.clone(),
+                amount: vec![Coin {
+                    denom: LUNA.to_string(),
+                    amount: Uint128(total_refund),
+                }],
+            }
+            .into(),
+        );
+    }
+    messages.extend(unstaked);
+    let mut log = vec![
+        log("action", "unsubscribe_batch"),
+        log("name", batch_names(results.iter().map(|entry| &entry.name))),
+        log("owner", env.message.sender),
+        log("refund", total_refund),
+        log("denom", LUNA),
+    ];
+    log.extend(unbonding_log(unbonding, &slashed));
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: Some(to_binary(&UnsubscribeBatchResponse { results })?),
+    })
+}
+
+/// Deregisters up to `limit` names past their grace period, in the order they lapsed. Each
+/// pays the sweeper the configured reward out of what it paid, or all of it if less.
+pub fn handle_sweep<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    limit: Option<u32>,
+) -> Result<HandleResponse, ContractError> {
+    let config = initialized_config(&deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let lapsed = match env.block.time.checked_sub(config.grace_period) {
+        Some(last) => expiries_read(&deps.storage)
+            .range(
+                None,
+                Some(&expiry_key(last.saturating_add(1), "")),
+                Order::Ascending,
+            )
+            .take(limit)
+            .map(|item| item.map(|(_, name)| name))
+            .collect::<StdResult<Vec<_>>>()?,
+        None => vec![],
+    };
+
+    let mut deregistered = vec![];
+    let mut reward = 0u128;
+    for name in &lapsed {
+        let registration = registrations_read(&deps.storage).load(name.as_bytes())?;
+        release_payment(&mut deps.storage, &registration)?;
+        remove_registration(&mut deps.storage, name, &registration);
+        lapsed_store(&mut deps.storage).save(
+            name.as_bytes(),
+            &(registration.expires.saturating_add(config.grace_period)),
+        )?;
+        let share = config
+            .sweep_reward
+            .unwrap_or_default()
+            .min(registration.paid);
+        reward += share.u128();
+        deregistered.push(Name {
+            value: name.clone(),
+            owner: deps.api.canonical_address(&registration.owner)?,
+        });
+    }
+
+    let mut messages: Vec<CosmosMsg> = vec![];
+    if !deregistered.is_empty() {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: config.name_service_address,
+            msg: to_binary(&DeregisterBatch {
+                names: deregistered,
+            })?,
+            send: vec![],
+        }));
+    }
+    if reward != 0 {
+        messages.push(
+            BankMsg::Send {
+                from_address: env.contract.address,
+                to_address: env.message.sender.clone(),
+                amount: vec![Coin {
+                    denom: LUNA.to_string(),
+                    amount: Uint128(reward),
+                }],
+            }
+            .into(),
+        );
+    }
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "sweep"),
+            log("name", batch_names(lapsed.iter())),
+            log("sweeper", env.message.sender),
+            log("reward", reward),
+            log("denom", LUNA),
+        ],
+        data: Some(to_binary(&SweepResponse {
+            names: lapsed,
+            reward: Uint128(reward),
+        })?),
+    })
+}
+
+pub fn handle_claim_referral<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+) -> Result<HandleResponse, ContractError> {
+    let key = env.message.sender.as_str().as_bytes();
+    let mut referral = referrals_read(&deps.storage)
+        .may_load(key)?
+        .unwrap_or_default();
+    let amount = Uint128(referral.earned.u128() - referral.claimed.u128());
+    if amount.is_zero() {
+        return Err(ContractError::NothingToClaim {});
+    }
+    referral.claimed = referral.earned;
+    referrals_store(&mut deps.storage).save(key, &referral)?;
+    Ok(HandleResponse {
+        messages: vec![BankMsg::Send {
+            from_address: env.contract.address,
+            to_address: env.message.sender.clone(),
+            amount: vec![Coin {
+                denom: LUNA.to_string(),
+                amount,
+            }],
+        }
+        .into()],
+        log: vec![
+            log("action", "claim_referral"),
+            log("referrer", env.message.sender),
+            log("amount", amount),
+            log("denom", LUNA),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_set_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    entries: Vec<AllowlistEntry>,
+) -> Result<HandleResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    if env.message.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut addresses = vec![];
+    for entry in entries {
+        if entry.discount > Decimal::one() {
+            return Err(ContractError::InvalidDiscount {});
+        }
+        deps.api.canonical_address(&entry.address)?;
+        let key = entry.address.as_str().as_bytes();
+        match entry.quota {
+            0 => allowlist_store(&mut deps.storage).remove(key),
+            remaining => allowlist_store(&mut deps.storage).save(
+                key,
+                &Allowance {
+                    remaining,
+                    discount: entry.discount,
+                },
+            )?,
+        }
+        addresses.push(entry.address.0);
+    }
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_allowlist"),
+            log("address", addresses.join(",")),
+        ],
+        data: None,
+    })
+}
+
+fn check_referral_share(share: &Option<Decimal>) -> Result<(), ContractError> {
+    match share {
+        Some(share) if *share > Decimal::one() => Err(ContractError::InvalidReferralShare {}),
+        _ => Ok(()),
+    }
+}
+
+fn check_voucher_signer(signer: &Option<Binary>) -> Result<(), ContractError> {
+    match signer {
+        Some(signer) if VerifyingKey::from_sec1_bytes(signer.as_slice()).is_err() => {
+            Err(ContractError::InvalidVoucherSigner {})
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The part of the base price `voucher` waives for the sender, once its signature,
+/// recipient and expiry check out and it is known to be unused.
+fn check_voucher<S: Storage>(
+    storage: &S,
+    env: &Env,
+    config: &Config,
+    voucher: &Voucher,
+) -> Result<Decimal, ContractError> {
+    if voucher
+        .expires
+        .map_or(false, |expires| env.block.time > expires)
+    {
+        return Err(ContractError::VoucherExpired {});
+    }
+    if vouchers_read(storage)
+        .may_load(voucher.id.as_bytes())?
+        .is_some()
+    {
+        return Err(ContractError::VoucherRedeemed {});
+    }
+    let for_sender = voucher
+        .recipient
+        .as_ref()
+        .map_or(true, |recipient| *recipient == env.message.sender);
+    let signer = match &config.voucher_signer {
+        Some(signer) if for_sender && voucher.discount <= Decimal::one() => signer,
+        _ => return Err(ContractError::InvalidVoucher {}),
+    };
+    let key = VerifyingKey::from_sec1_bytes(signer.as_slice())
+        .map_err(|_| ContractError::InvalidVoucherSigner {})?;
+    let message = voucher.message(&env.contract.address)?;
+    Signature::from_slice(voucher.signature.as_slice())
+        .and_then(|signature| key.verify(&message, &signature))
+        .map_err(|_| ContractError::InvalidVoucher {})?;
+    Ok(voucher.discount)
+}
+
+/// Marks `voucher` redeemed by `sender` or, without a voucher, uses up one subscription of
+/// the sender's allowlist quota.
+fn redeem_discount<S: Storage>(
+    storage: &mut S,
+    sender: &HumanAddr,
+    voucher: &Option<Voucher>,
+) -> StdResult<()> {
+    if let Some(voucher) = voucher {
+        return vouchers_store(storage).save(voucher.id.as_bytes(), sender);
+    }
+    let key = sender.as_str().as_bytes();
+    match allowlist_read(storage).may_load(key)? {
+        Some(allowance) if allowance.remaining > 1 => allowlist_store(storage).save(
+            key,
+            &Allowance {
+                remaining: allowance.remaining - 1,
+                ..allowance
+            },
+        ),
+        Some(_) => {
+            allowlist_store(storage).remove(key);
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+fn discount_log(voucher: &Option<Voucher>, discount: Uint128) -> Vec<LogAttribute> {
+    match voucher {
+        Some(voucher) => vec![log("discount", discount), log("voucher", &voucher.id)],
+        None if !discount.is_zero() => vec![log("discount", discount)],
+        None => vec![],
+    }
+}
+
+/// Rejects an empty batch, or one naming a name twice, before any name is looked at.
+fn check_batch(names: &[String]) -> Result<(), ContractError> {
+    if names.is_empty() {
+        return Err(ContractError::EmptyBatch {});
+    }
+    for (i, name) in names.iter().enumerate() {
+        if names[..i].contains(name) {
+            return Err(ContractError::DuplicateName {});
+        }
+    }
+    Ok(())
+}
+
+fn check_referrer<A: Api>(
+    api: &A,
+    referrer: &Option<HumanAddr>,
+    sender: &HumanAddr,
+) -> Result<(), ContractError> {
+    match referrer {
+        Some(referrer) if referrer == sender => Err(ContractError::SelfReferral {}),
+        Some(referrer) => api
+            .canonical_address(referrer)
+            .map(|_| ())
+            .map_err(Into::into),
+        None => Ok(()),
+    }
+}
+
+/// Credits `referrer` with the referral share of `held` and returns that share, which comes
+/// out of what the subscription holds.
+fn credit_referral<S: Storage>(
+    storage: &mut S,
+    config: &Config,
+    referrer: &Option<HumanAddr>,
+    held: Uint128,
+) -> StdResult<Uint128> {
+    let referrer = match referrer {
+        Some(referrer) => referrer,
+        None => return Ok(Uint128::zero()),
+    };
+    let amount = held * config.referral_share.unwrap_or_else(Decimal::zero);
+    referrals_store(storage).update(referrer.as_str().as_bytes(), |referral| {
+        let mut referral = referral.unwrap_or_default();
+        referral.referrals += 1;
+        referral.earned += amount;
+        Ok(referral)
+    })?;
+    Ok(amount)
+}
+
+fn referral_log(referrer: &Option<HumanAddr>, amount: Uint128) -> Vec<LogAttribute> {
+    match referrer {
+        Some(referrer) => vec![log("referrer", referrer), log("referral", amount)],
+        None => vec![],
+    }
+}
+
+/// The stake unbonding, and what slashes took off each name's stake, when any did.
+fn unbonding_log(amount: u128, slashed: &[u128]) -> Vec<LogAttribute> {
+    let mut log = match amount {
+        0 => vec![],
+        amount => vec![cosmwasm_std::log("unbonding", amount)],
+    };
+    if slashed.iter().any(|amount| *amount != 0) {
+        let slashed: Vec<_> = slashed.iter().map(u128::to_string).collect();
+        log.push(cosmwasm_std::log("slashed", slashed.join(",")));
+    }
+    log
+}
+
+/// The amount of a non-empty LUNA payment.
+fn sent_luna(env: &Env) -> Result<Uint128, ContractError> {
+    match env.message.sent_funds.first() {
+        Some(coin) if coin.denom != LUNA => Err(ContractError::InvalidDenom {
+            expected: LUNA.to_string(),
+            denom: coin.denom.clone(),
+        }),
+        Some(coin) if !coin.amount.is_zero() => Ok(coin.amount),
+        _ => Err(ContractError::NoFunds {}),
+    }
+}
+
+/// Subscriptions need the name service to have signed up first.
+fn initialized_config<S: Storage>(storage: &S) -> Result<Config, ContractError> {
+    let config = owner_cfg_read(storage).load()?;
+    if config.name_service_address == HumanAddr::default() {
+        return Err(ContractError::NotInitialized {});
+    }
+    Ok(config)
+}
+
+fn batch_names<'a>(names: impl Iterator<Item = &'a String>) -> String {
+    names.map(String::as_str).collect::<Vec<_>>().join(",")
+}
+
+/// Unsubscribing gives back a tenth of what was paid.
+fn refund_of(registration: &Registration) -> Uint128 {
+    Uint128(registration.paid.u128() / 10)
+}
+
+/// Saves a confirmed registration together with its place in the expiry index.
+fn save_registration<S: Storage>(
+    storage: &mut S,
+    name: &str,
+    registration: &Registration,
+) -> StdResult<()> {
+    registrations_store(storage).save(name.as_bytes(), registration)?;
+    expiries_store(storage).save(&expiry_key(registration.expires, name), &name.to_string())
+}
+
+fn remove_registration<S: Storage>(storage: &mut S, name: &str, registration: &Registration) {
+    registrations_store(storage).remove(name.as_bytes());
+    expiries_store(storage).remove(&expiry_key(registration.expires, name));
+}
+
+/// Builds the expiry index for registrations stored before it existed.
+fn index_expiries<S: Storage>(storage: &mut S) -> StdResult<()> {
+    let registrations = registrations_read(storage)
+        .range(None, None, Order::Ascending)
+        .collect::<StdResult<Vec<_>>>()?;
+    for (name, registration) in registrations {
+        let name = String::from_utf8(name).map_err(StdError::invalid_utf8)?;
+        expiries_store(storage).save(&expiry_key(registration.expires, &name), &name)?;
+    }
+    Ok(())
+}
+
+/// Takes what `registration` paid off its payer's total in `PAYMENTS`.
+fn release_payment<S: Storage>(storage: &mut S, registration: &Registration) -> StdResult<()> {
+    let total = match payments_read(storage, registration.payer.clone()) {
+        Ok(total) => total.u128(),
+        Err(_) => return Ok(()),
+    };
+    match total.saturating_sub(registration.paid.u128()) {
+        0 => payments_delete(storage, registration.payer.clone()),
+        rest => payments_store(storage, registration.payer.clone(), Uint128(rest)),
+    }
+}
+
+pub fn query<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    msg: QueryMsg,
+) -> StdResult<Binary> {
+    match msg {
+        QueryMsg::PaidAmountIs { address } => to_binary(&try_paidamountis(deps, address)?),
+        QueryMsg::GetNameServiceAddress {} => to_binary(&get_nameservice_address(deps)?),
+        QueryMsg::AddressExists { address } => to_binary(&address_exists(deps, address)?),
+        QueryMsg::PriceIs { name, time } => to_binary(&try_priceis(deps, name, time)?),
+        QueryMsg::SubscriptionInfo { name } => to_binary(&try_subscription_info(deps, name)?),
+        QueryMsg::SubscriptionStatus { name, time } => {
+            to_binary(&try_subscription_status(deps, name, time)?)
+        }
+        QueryMsg::ReferralEarnings { referrer } => {
+            to_binary(&try_referral_earnings(deps, referrer)?)
+        }
+        QueryMsg::ListReferrers { start_after, limit } => {
+            to_binary(&try_list_referrers(deps, start_after, limit)?)
+        }
+        QueryMsg::ListPayments { start_after, limit } => {
+            to_binary(&try_list_payments(deps, start_after, limit)?)
+        }
+        QueryMsg::Allowance { address } => to_binary(&try_allowance(deps, address)?),
+        QueryMsg::ListAllowlist { start_after, limit } => {
+            to_binary(&try_list_allowlist(deps, start_after, limit)?)
+        }
+        QueryMsg::VoucherStatus { id } => to_binary(&try_voucher_status(deps, id)?),
+        QueryMsg::Unbonding { address } => to_binary(&try_unbonding(deps, address)?),
+        QueryMsg::VersionHistory {} => to_binary(&try_version_history(deps)?),
+    }
+}
+
+pub fn try_priceis<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: String,
+    time: Option<u64>,
+) -> Result<PriceResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let time = match time {
+        Some(time) => time,
+        None => last_seen_read(&deps.storage)
+            .may_load()?
+            .unwrap_or_default(),
+    };
+    let (_, premium) = current_premium(&deps.storage, &config, &name, time)?;
+    Ok(PriceResponse {
+        base_price: config.base_price,
+        premium,
+        total: config.base_price + premium,
+    })
+}
+
+pub fn try_subscription_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: String,
+) -> Result<SubscriptionInfoResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let (registration, pending) =
+        match registrations_read(&deps.storage).may_load(name.as_bytes())? {
+            Some(registration) => (registration, false),
+            None => match pending_read(&deps.storage).may_load(name.as_bytes())? {
+                Some(registration) => (registration, true),
+                None => return Err(ContractError::NameNotFound {}),
+            },
+        };
+    Ok(SubscriptionInfoResponse {
+        name,
+        refund: if pending {
+            Uint128::zero()
+        } else {
+            refund_of(&registration)
+        },
+        grace_ends: registration.expires.saturating_add(config.grace_period),
+        owner: registration.owner,
+        payer: registration.payer,
+        paid: registration.paid,
+        expires: registration.expires,
+        stake: registration.stake,
+        pending,
+    })
+}
+
+pub fn try_subscription_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: String,
+    time: u64,
+) -> Result<SubscriptionStatusResponse, ContractError> {
+    let config = owner_cfg_read(&deps.storage).load()?;
+    let (registration, pending) =
+        match registrations_read(&deps.storage).may_load(name.as_bytes())? {
+            Some(registration) => (registration, false),
+            None => match pending_read(&deps.storage).may_load(name.as_bytes())? {
+                Some(registration) => (registration, true),
+                None => return Err(ContractError::NameNotFound {}),
+            },
+        };
+    let grace_ends = registration.expires.saturating_add(config.grace_period);
+    let state = if pending {
+        SubscriptionState::Pending
+    } else if time < registration.expires {
+        SubscriptionState::Active
+    } else if time < grace_ends {
+        SubscriptionState::Grace
+    } else {
+        SubscriptionState::Lapsed
+    };
+    let periods_left = match config.registration_period {
+        0 => 0,
+        period => registration.expires.saturating_sub(time) / period,
+    };
+    Ok(SubscriptionStatusResponse {
+        name,
+        owner: registration.owner,
+        state,
+        paid_until: registration.expires,
+        grace_ends,
+        periods_left,
+    })
+}
+
+pub fn address_exists<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<bool> {
+    if payments_read(&deps.storage, address).is_err() {
+        return Ok(false);
+    }
+    Ok(true)
+}
+
+pub fn try_paidamountis<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<PaidAmountResponse> {
+    match payments_read(&deps.storage, address) {
+        Ok(amount) => Ok(PaidAmountResponse {
+            amount,
+            found: true,
+        }),
+        Err(_) => Ok(PaidAmountResponse {
+            amount: Uint128::zero(),
+            found: false,
+        }),
+    }
+}
+
+pub fn try_list_payments<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<PaymentsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let payments = payments_range(&deps.storage, start_after, limit)?
+        .into_iter()
+        .map(|(address, amount)| PaymentEntry { address, amount })
+        .collect();
+    Ok(PaymentsResponse { payments })
+}
+
+pub fn try_referral_earnings<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    referrer: HumanAddr,
+) -> StdResult<ReferralResponse> {
+    let referral = referrals_read(&deps.storage)
+        .may_load(referrer.as_str().as_bytes())?
+        .unwrap_or_default();
+    Ok(referral_response(referrer, referral))
+}
+
+pub fn try_list_referrers<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<ReferrersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|address| {
+        let mut start = address.as_str().as_bytes().to_vec();
+        start.push(0);
+        start
+    });
+    let referrers = referrals_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, referral) = item?;
+            let referrer = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok(referral_response(HumanAddr(referrer), referral))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ReferrersResponse { referrers })
+}
+
+pub fn try_allowance<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<AllowlistEntry> {
+    let allowance = allowlist_read(&deps.storage).may_load(address.as_str().as_bytes())?;
+    Ok(match allowance {
+        Some(allowance) => allowlist_entry(address, allowance),
+        None => AllowlistEntry {
+            address,
+            quota: 0,
+            discount: Decimal::zero(),
+        },
+    })
+}
+
+pub fn try_list_allowlist<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<AllowlistResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|address| {
+        let mut start = address.as_str().as_bytes().to_vec();
+        start.push(0);
+        start
+    });
+    let entries = allowlist_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, allowance) = item?;
+            let address = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok(allowlist_entry(HumanAddr(address), allowance))
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(AllowlistResponse { entries })
+}
+
+fn allowlist_entry(address: HumanAddr, allowance: Allowance) -> AllowlistEntry {
+    AllowlistEntry {
+        address,
+        quota: allowance.remaining,
+        discount: allowance.discount,
+    }
+}
+
+pub fn try_voucher_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    id: String,
+) -> StdResult<VoucherResponse> {
+    let redeemed_by = vouchers_read(&deps.storage).may_load(id.as_bytes())?;
+    Ok(VoucherResponse { id, redeemed_by })
+}
+
+pub fn try_unbonding<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    address: HumanAddr,
+) -> StdResult<UnbondingResponse> {
+    let entries = unbonding_read(&deps.storage)
+        .may_load(address.as_str().as_bytes())?
+        .unwrap_or_default();
+    Ok(UnbondingResponse { address, entries })
+}
+
+pub fn try_version_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<VersionHistoryResponse> {
+    let versions = version_history_read(&deps.storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| Ok(item?.1))
+        .collect::<StdResult<_>>()?;
+    Ok(VersionHistoryResponse { versions })
+}
+
+fn referral_response(referrer: HumanAddr, referral: Referral) -> ReferralResponse {
+    ReferralResponse {
+        referrer,
+        referrals: referral.referrals,
+        earned: referral.earned,
+        claimed: referral.claimed,
+        unclaimed: Uint128(referral.earned.u128() - referral.claimed.u128()),
+    }
+}
+
+pub fn get_nameservice_address<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<HumanAddr> {
+    Ok(owner_cfg_read(&deps.storage).load()?.name_service_address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::HandleMsg::{Subscribe, Unsubscribe};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, MockApi, MockQuerier, MockStorage};
+    use cosmwasm_std::{coin, from_binary, FullDelegation};
+
+    fn default_init() -> InitMsg {
+        InitMsg {
+            nameservice_code_id: 16,
+            treasury: None,
+            base_price: Uint128(100),
+            registration_period: 1000,
+            grace_period: 100,
+            premium: None,
+            sweep_reward: None,
+            referral_share: None,
+            voucher_signer: None,
+            staking: None,
+        }
+    }
+
+    /// Has the mock chain report what `delegator` has bonded to each validator.
+    fn bond(
+        deps: &mut Extern<MockStorage, MockApi, MockQuerier>,
+        delegator: &HumanAddr,
+        bonded: &[(&HumanAddr, u128)],
+    ) {
+        let delegations: Vec<_> = bonded
+            .iter()
+            .map(|(validator, amount)| FullDelegation {
+                delegator: delegator.clone(),
+                validator: (*validator).clone(),
+                amount: Coin::new(*amount, LUNA),
+                can_redelegate: Coin::new(*amount, LUNA),
+                accumulated_rewards: Coin::new(0, LUNA),
+            })
+            .collect();
+        deps.querier.update_staking(LUNA, &[], &delegations);
+    }
 
     #[test]
     fn proper_initialization() {
@@ -199,9 +2034,7 @@ mod tests {
         let base_address = HumanAddr::from("test1");
         let env = mock_env(base_address.clone(), &[sent]);
 
-        let msg1 = InitMsg {
-            nameservice_code_id: 16,
-        };
+        let msg1 = default_init();
         let res1 = init(&mut deps, env.clone(), msg1);
         assert_eq!(&res1.is_err(), &false);
         let res1_message = res1.unwrap().messages.len();
@@ -219,10 +2052,9 @@ mod tests {
         let sent = Coin::new(1000, LUNA);
         let base_address = HumanAddr::from("test1");
         let env = mock_env(base_address.clone(), &[sent]);
+        let now = env.block.time;
 
-        let msg1 = InitMsg {
-            nameservice_code_id: 16,
-        };
+        let msg1 = default_init();
         let _res1 = init(&mut deps, env.clone(), msg1);
 
         let msg2 = Signup {};
@@ -230,10 +2062,26 @@ mod tests {
 
         let msg3 = Subscribe {
             name: "Test1Name".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
         };
-        let res3 = handle(&mut deps, env, msg3);
+        let res3 = handle(&mut deps, env.clone(), msg3);
         assert_eq!(&res3.is_err(), &false);
-        let res3_message = res3.unwrap().messages;
+        let res3 = res3.unwrap();
+        assert_eq!(
+            res3.log,
+            vec![
+                log("action", "subscribe"),
+                log("name", "Test1Name"),
+                log("owner", &base_address),
+                log("amount", 1000),
+                log("denom", LUNA),
+                log("premium", 0),
+                log("expires", env.block.time + 1000),
+            ]
+        );
+        let res3_message = res3.messages;
         assert_eq!(res3_message.len(), 1);
 
         if res3_message.len() == 1 {
@@ -244,19 +2092,116 @@ mod tests {
                         value: "Test1Name".to_string(),
                         owner: deps.api.canonical_address(&base_address).unwrap(),
                     },
+                    expires: Some(env.block.time + 1000),
                 })
                 .unwrap(),
                 send: vec![],
-            })
-            .into()];
+            })];
             assert_eq!(intended_message, res3_message);
         }
 
         let query1 = address_exists(&deps, base_address.clone()).unwrap();
-        assert_eq!(query1, true);
+        assert!(!query1);
+        let info1 = try_subscription_info(&deps, "Test1Name".to_string()).unwrap();
+        assert!(info1.pending);
+        assert_eq!(info1.refund, Uint128::zero());
+
+        let msg4 = HandleMsg::NameRegistered {
+            name: "Test1Name".to_string(),
+            owner: base_address.clone(),
+        };
+        let res4 = handle(&mut deps, mock_env("test2", &[]), msg4.clone());
+        assert_eq!(res4, Err(ContractError::Unauthorized {}.into()));
+        let res4 = handle(&mut deps, env.clone(), msg4.clone());
+        assert_eq!(&res4.is_err(), &false);
+        let res4 = handle(&mut deps, env, msg4);
+        assert_eq!(res4, Err(ContractError::NoPendingRegistration {}.into()));
+
+        let query1 = address_exists(&deps, base_address.clone()).unwrap();
+        assert!(query1);
+        let msg5 = QueryMsg::SubscriptionInfo {
+            name: "Test1Name".to_string(),
+        };
+        let info2: SubscriptionInfoResponse = from_binary(&query(&deps, msg5).unwrap()).unwrap();
+        assert_eq!(
+            info2,
+            SubscriptionInfoResponse {
+                name: "Test1Name".to_string(),
+                owner: base_address.clone(),
+                payer: base_address.clone(),
+                paid: Uint128(1000),
+                expires: now + 1000,
+                grace_ends: now + 1100,
+                pending: false,
+                refund: Uint128(100),
+                stake: None,
+            }
+        );
+        let info3 = try_subscription_info(&deps, "Test2Name".to_string());
+        assert_eq!(info3, Err(ContractError::NameNotFound {}));
 
         let query2 = try_paidamountis(&deps, base_address).unwrap();
-        assert_eq!(query2, Uint128(1000));
+        assert_eq!(
+            query2,
+            PaidAmountResponse {
+                amount: Uint128(1000),
+                found: true,
+            }
+        );
+        let query3 = try_paidamountis(&deps, HumanAddr::from("test2")).unwrap();
+        assert_eq!(
+            query3,
+            PaidAmountResponse {
+                amount: Uint128::zero(),
+                found: false,
+            }
+        );
+    }
+
+    #[test]
+    fn subscription_checks_sent_funds() {
+        let mut deps = mock_dependencies(20, &[]);
+        let base_address = HumanAddr::from("test1");
+        let env = mock_env(base_address.clone(), &[]);
+
+        let msg1 = default_init();
+        let _res1 = init(&mut deps, env.clone(), msg1);
+
+        let msg2 = Subscribe {
+            name: "Test1Name".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        let env2 = mock_env(base_address.clone(), &[Coin::new(1000, LUNA)]);
+        let res2 = handle_subscribe(&mut deps, env2, "Test1Name".to_string(), None, None, None);
+        assert_eq!(res2, Err(ContractError::NotInitialized {}));
+
+        let _res3 = handle(&mut deps, env.clone(), Signup {});
+
+        let res4 = handle_subscribe(&mut deps, env, "Test1Name".to_string(), None, None, None);
+        assert_eq!(res4, Err(ContractError::NoFunds {}));
+
+        let env5 = mock_env(base_address.clone(), &[Coin::new(1000, "uusd")]);
+        let res5 = handle_subscribe(&mut deps, env5, "Test1Name".to_string(), None, None, None);
+        assert_eq!(
+            res5,
+            Err(ContractError::InvalidDenom {
+                expected: LUNA.to_string(),
+                denom: "uusd".to_string(),
+            })
+        );
+
+        let env6 = mock_env(base_address, &[Coin::new(99, LUNA)]);
+        let res6 = handle(&mut deps, env6, msg2);
+        assert_eq!(
+            res6,
+            Err(ContractError::InsufficientFunds {
+                price: Uint128(100),
+                sent: Uint128(99),
+            }
+            .into())
+        );
     }
 
     #[test]
@@ -266,9 +2211,7 @@ mod tests {
         let base_address = HumanAddr::from("test1");
         let env = mock_env(base_address.clone(), &[sent]);
 
-        let msg1 = InitMsg {
-            nameservice_code_id: 16,
-        };
+        let msg1 = default_init();
         let _res1 = init(&mut deps, env.clone(), msg1);
 
         let msg2 = Signup {};
@@ -276,15 +2219,34 @@ mod tests {
 
         let msg3 = Subscribe {
             name: "Test1Name".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
         };
         let _res3 = handle(&mut deps, env.clone(), msg3);
+        let confirmation = HandleMsg::NameRegistered {
+            name: "Test1Name".to_string(),
+            owner: base_address.clone(),
+        };
+        let _res3 = handle(&mut deps, env.clone(), confirmation);
 
         let msg4 = Unsubscribe {
             name: "Test1Name".to_string(),
         };
         let res4 = handle(&mut deps, env.clone(), msg4);
         assert_eq!(&res4.is_err(), &false);
-        let res4_message = res4.unwrap().messages;
+        let res4 = res4.unwrap();
+        assert_eq!(
+            res4.log,
+            vec![
+                log("action", "unsubscribe"),
+                log("name", "Test1Name"),
+                log("owner", &base_address),
+                log("refund", 100),
+                log("denom", LUNA),
+            ]
+        );
+        let res4_message = res4.messages;
         assert_eq!(res4_message.len(), 2); //a bank message and a deregister one
 
         if res4_message.len() == 2 {
@@ -315,6 +2277,1256 @@ mod tests {
         }
 
         let query1 = address_exists(&deps, base_address).unwrap();
-        assert_eq!(query1, false);
+        assert!(!query1);
+    }
+
+    #[test]
+    fn batches_price_each_name() {
+        let mut deps = mock_dependencies(20, &[]);
+        let base_address = HumanAddr::from("test1");
+        let env = mock_env(base_address.clone(), &[Coin::new(1001, LUNA)]);
+        let msg1 = default_init();
+        let _res1 = init(&mut deps, env.clone(), msg1).unwrap();
+        let _res2 = handle(&mut deps, env.clone(), Signup {}).unwrap();
+
+        let names = vec!["first".to_string(), "second".to_string()];
+        let duplicated = HandleMsg::SubscribeBatch {
+            names: vec!["first".to_string(), "first".to_string()],
+            periods: None,
+            referrer: None,
+        };
+        let res = handle(&mut deps, env.clone(), duplicated);
+        assert_eq!(res, Err(ContractError::DuplicateName {}.into()));
+        let empty = HandleMsg::SubscribeBatch {
+            names: vec![],
+            periods: None,
+            referrer: None,
+        };
+        let res = handle(&mut deps, env.clone(), empty);
+        assert_eq!(res, Err(ContractError::EmptyBatch {}.into()));
+        let short_env = mock_env(base_address.clone(), &[Coin::new(150, LUNA)]);
+        let res = handle(
+            &mut deps,
+            short_env,
+            HandleMsg::SubscribeBatch {
+                names: names.clone(),
+                periods: None,
+                referrer: None,
+            },
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::InsufficientFunds {
+                price: Uint128(200),
+                sent: Uint128(150),
+            }
+            .into())
+        );
+
+        let msg = HandleMsg::SubscribeBatch {
+            names: names.clone(),
+            periods: None,
+            referrer: None,
+        };
+        let res = handle(&mut deps, env.clone(), msg).unwrap();
+        let expires = env.block.time + 1000;
+        let owner = deps.api.canonical_address(&base_address).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: base_address.clone(),
+                msg: to_binary(&RegisterBatch {
+                    names: names
+                        .iter()
+                        .map(|name| Name {
+                            value: name.clone(),
+                            owner: owner.clone(),
+                        })
+                        .collect(),
+                    expires: Some(expires),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        let data: SubscribeBatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        let paid: Vec<Uint128> = data.results.iter().map(|entry| entry.paid).collect();
+        // Each name holds its price, and the first also what was sent beyond the total.
+        assert_eq!(paid, vec![Uint128(901), Uint128(100)]);
+        assert_eq!(res.log[1], log("name", "first,second"));
+        assert_eq!(res.log[6], log("paid", "901,100"));
+
+        for name in &names {
+            let confirmation = HandleMsg::NameRegistered {
+                name: name.clone(),
+                owner: base_address.clone(),
+            };
+            let _res = handle(&mut deps, env.clone(), confirmation).unwrap();
+        }
+        assert_eq!(
+            try_paidamountis(&deps, base_address.clone())
+                .unwrap()
+                .amount,
+            Uint128(1001)
+        );
+
+        let stranger = mock_env("test2", &[]);
+        let msg = HandleMsg::UnsubscribeBatch {
+            names: names.clone(),
+        };
+        let res = handle(&mut deps, stranger, msg);
+        assert_eq!(res, Err(ContractError::NotOwner {}.into()));
+        let msg = HandleMsg::UnsubscribeBatch {
+            names: vec!["first".to_string(), "missing".to_string()],
+        };
+        let res = handle(&mut deps, env.clone(), msg);
+        assert_eq!(res, Err(ContractError::NameNotFound {}.into()));
+        let msg = HandleMsg::UnsubscribeBatch {
+            names: vec!["first".to_string(), "first".to_string()],
+        };
+        let res = handle(&mut deps, env.clone(), msg);
+        assert_eq!(res, Err(ContractError::DuplicateName {}.into()));
+        assert!(registrations_read(&deps.storage).load(b"first").is_ok());
+
+        let msg = HandleMsg::UnsubscribeBatch {
+            names: names.clone(),
+        };
+        let res = handle(&mut deps, env.clone(), msg).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(
+            res.messages[1],
+            BankMsg::Send {
+                from_address: env.contract.address.clone(),
+                to_address: base_address.clone(),
+                amount: vec![coin(100, LUNA)],
+            }
+            .into()
+        );
+        let data: UnsubscribeBatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.results[0].refund, Uint128(90));
+        assert!(!address_exists(&deps, base_address.clone()).unwrap());
+
+        // The allowlist discount covers as many names as the quota has left.
+        let allow = HandleMsg::SetAllowlist {
+            entries: vec![AllowlistEntry {
+                address: base_address.clone(),
+                quota: 1,
+                discount: Decimal::percent(50),
+            }],
+        };
+        let _res = handle(&mut deps, env.clone(), allow).unwrap();
+        let msg = HandleMsg::SubscribeBatch {
+            names: vec!["third".to_string(), "fourth".to_string()],
+            periods: None,
+            referrer: None,
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(base_address.clone(), &[Coin::new(149, LUNA)]),
+            msg.clone(),
+        );
+        assert_eq!(
+            res,
+            Err(ContractError::InsufficientFunds {
+                price: Uint128(150),
+                sent: Uint128(149),
+            }
+            .into())
+        );
+        let res = handle(
+            &mut deps,
+            mock_env(base_address.clone(), &[Coin::new(150, LUNA)]),
+            msg,
+        )
+        .unwrap();
+        let data: SubscribeBatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        let paid: Vec<Uint128> = data.results.iter().map(|entry| entry.paid).collect();
+        assert_eq!(paid, vec![Uint128(50), Uint128(100)]);
+        assert_eq!(res.log.last(), Some(&log("discount", 50)));
+        assert_eq!(try_allowance(&deps, base_address).unwrap().quota, 0);
+    }
+
+    #[test]
+    fn premium_decays_to_base_price() {
+        let linear = Some(Premium {
+            start: Uint128(1000),
+            window: 100,
+            decay: Decay::Linear,
+        });
+        assert_eq!(premium_at(&linear, 0), Uint128(1000));
+        assert_eq!(premium_at(&linear, 25), Uint128(750));
+        assert_eq!(premium_at(&linear, 100), Uint128::zero());
+
+        let exponential = Some(Premium {
+            start: Uint128(1000),
+            window: 100,
+            decay: Decay::Exponential { half_life: 10 },
+        });
+        assert_eq!(premium_at(&exponential, 0), Uint128(1000));
+        assert_eq!(premium_at(&exponential, 5), Uint128(750));
+        assert_eq!(premium_at(&exponential, 20), Uint128(250));
+        assert_eq!(premium_at(&exponential, 100), Uint128::zero());
+        assert_eq!(premium_at(&None, 0), Uint128::zero());
+    }
+
+    #[test]
+    fn expired_name_reregistration_pays_premium() {
+        let mut deps = mock_dependencies(20, &[]);
+        let first_owner = HumanAddr::from("test1");
+        let second_owner = HumanAddr::from("test2");
+        let treasury = HumanAddr::from("treasury");
+        let mut env = mock_env(first_owner.clone(), &[Coin::new(100, LUNA)]);
+
+        let msg1 = InitMsg {
+            treasury: Some(treasury.clone()),
+            premium: Some(Premium {
+                start: Uint128(1000),
+                window: 100,
+                decay: Decay::Linear,
+            }),
+            ..default_init()
+        };
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let _res2 = handle(&mut deps, env.clone(), Signup {});
+        let name = "Test1Name".to_string();
+        let registered_at = env.block.time;
+        let res3 = handle(
+            &mut deps,
+            env.clone(),
+            Subscribe {
+                name: name.clone(),
+                periods: None,
+                referrer: None,
+                voucher: None,
+            },
+        );
+        assert_eq!(&res3.is_err(), &false);
+        let confirmation = HandleMsg::NameRegistered {
+            name: name.clone(),
+            owner: first_owner.clone(),
+        };
+        let _res3 = handle(&mut deps, env.clone(), confirmation);
+
+        env.message.sender = second_owner.clone();
+        env.block.time = registered_at + 1050;
+        let res4 = handle(
+            &mut deps,
+            env.clone(),
+            Subscribe {
+                name: name.clone(),
+                periods: None,
+                referrer: None,
+                voucher: None,
+            },
+        );
+        assert_eq!(&res4.is_err(), &true);
+
+        let released = registered_at + 1100;
+        let price = try_priceis(&deps, name.clone(), Some(released + 50)).unwrap();
+        assert_eq!(
+            price,
+            PriceResponse {
+                base_price: Uint128(100),
+                premium: Uint128(500),
+                total: Uint128(600),
+            }
+        );
+
+        // Without a time the price is taken at the latest transaction.
+        env.block.time = released + 50;
+        let sweep = HandleMsg::Sweep { limit: Some(0) };
+        handle(&mut deps, env.clone(), sweep).unwrap();
+        assert_eq!(try_priceis(&deps, name.clone(), None).unwrap(), price);
+
+        let res5 = handle(
+            &mut deps,
+            env.clone(),
+            Subscribe {
+                name: name.clone(),
+                periods: None,
+                referrer: None,
+                voucher: None,
+            },
+        );
+        assert_eq!(&res5.is_err(), &true);
+
+        env.message.sent_funds = vec![Coin::new(600, LUNA)];
+        let res6 = handle(
+            &mut deps,
+            env.clone(),
+            Subscribe {
+                name: name.clone(),
+                periods: None,
+                referrer: None,
+                voucher: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(res6.messages.len(), 3);
+        assert_eq!(
+            res6.messages[2],
+            BankMsg::Send {
+                from_address: env.contract.address.clone(),
+                to_address: treasury,
+                amount: vec![coin(500, LUNA)],
+            }
+            .into()
+        );
+        assert!(!address_exists(&deps, first_owner.clone()).unwrap());
+        let confirmation = HandleMsg::NameRegistered {
+            name,
+            owner: second_owner.clone(),
+        };
+        let _res7 = handle(&mut deps, mock_env(first_owner, &[]), confirmation);
+        assert_eq!(
+            try_paidamountis(&deps, second_owner).unwrap().amount,
+            Uint128(100)
+        );
+    }
+
+    #[test]
+    fn owner_manages_reserved_names() {
+        let mut deps = mock_dependencies(20, &[]);
+        let base_address = HumanAddr::from("test1");
+        let env = mock_env(base_address.clone(), &[]);
+
+        let msg1 = default_init();
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let _res2 = handle(&mut deps, env.clone(), Signup {});
+
+        let msg3 = HandleMsg::SetNameStatus {
+            names: vec!["terra".to_string()],
+            status: NameStatus::Reserved,
+        };
+        let res3 = handle(&mut deps, mock_env("intruder", &[]), msg3.clone());
+        assert_eq!(&res3.is_err(), &true);
+        let res3 = handle(&mut deps, env.clone(), msg3).unwrap();
+        assert_eq!(res3.messages.len(), 1);
+
+        let holder = HumanAddr::from("terraform");
+        let msg4 = HandleMsg::RegisterReserved {
+            name: "terra".to_string(),
+            owner: holder.clone(),
+        };
+        let res4 = handle(&mut deps, mock_env("intruder", &[]), msg4.clone());
+        assert_eq!(&res4.is_err(), &true);
+        let res4 = handle(&mut deps, env.clone(), msg4).unwrap();
+        assert_eq!(
+            res4.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: base_address,
+                msg: to_binary(&RegisterReserved {
+                    name_c: Name {
+                        value: "terra".to_string(),
+                        owner: deps.api.canonical_address(&holder).unwrap(),
+                    },
+                    expires: Some(env.block.time + 1000),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        let confirmation = HandleMsg::NameRegistered {
+            name: "terra".to_string(),
+            owner: holder.clone(),
+        };
+        let _res5 = handle(&mut deps, env, confirmation);
+        let registration = registrations_read(&deps.storage).load(b"terra").unwrap();
+        assert_eq!(registration.owner, holder);
+        assert!(!address_exists(&deps, holder).unwrap());
+    }
+
+    #[test]
+    fn transfers_follow_the_name_service() {
+        let mut deps = mock_dependencies(20, &[]);
+        let base_address = HumanAddr::from("test1");
+        let env = mock_env(base_address.clone(), &[Coin::new(1000, LUNA)]);
+
+        let msg1 = default_init();
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let _res2 = handle(&mut deps, mock_env("nameservice", &[]), Signup {});
+        let msg3 = Subscribe {
+            name: "Test1Name".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        let _res3 = handle(&mut deps, env.clone(), msg3);
+        let confirmation = HandleMsg::NameRegistered {
+            name: "Test1Name".to_string(),
+            owner: base_address,
+        };
+        let _res3 = handle(&mut deps, mock_env("nameservice", &[]), confirmation);
+
+        let msg4 = HandleMsg::NameTransferred {
+            name: "Test1Name".to_string(),
+            owner: HumanAddr::from("test2"),
+        };
+        let res4 = handle(&mut deps, env, msg4.clone());
+        assert_eq!(&res4.is_err(), &true);
+        let res4 = handle(&mut deps, mock_env("nameservice", &[]), msg4);
+        assert_eq!(&res4.is_err(), &false);
+        let registration = registrations_read(&deps.storage)
+            .load(b"Test1Name")
+            .unwrap();
+        assert_eq!(registration.owner, HumanAddr::from("test2"));
+    }
+
+    #[test]
+    fn unsubscription_requires_the_owner() {
+        let mut deps = mock_dependencies(20, &[]);
+        let base_address = HumanAddr::from("test1");
+        let env = mock_env(base_address.clone(), &[Coin::new(1000, LUNA)]);
+
+        let msg1 = default_init();
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let _res2 = handle(&mut deps, env.clone(), Signup {});
+        let msg3 = Subscribe {
+            name: "Test1Name".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        let _res3 = handle(&mut deps, env.clone(), msg3);
+        let confirmation = HandleMsg::NameRegistered {
+            name: "Test1Name".to_string(),
+            owner: base_address.clone(),
+        };
+        let _res3 = handle(&mut deps, env, confirmation);
+
+        let intruder = mock_env("test2", &[Coin::new(1000, LUNA)]);
+        let msg4 = Unsubscribe {
+            name: "Test1Name".to_string(),
+        };
+        let res4 = handle(&mut deps, intruder.clone(), msg4);
+        assert_eq!(res4, Err(ContractError::NotOwner {}.into()));
+        let msg5 = Unsubscribe {
+            name: "Unknown".to_string(),
+        };
+        let res5 = handle(&mut deps, intruder, msg5);
+        assert_eq!(res5, Err(ContractError::NameNotFound {}.into()));
+        assert!(address_exists(&deps, base_address).unwrap());
+    }
+
+    #[test]
+    fn payments_are_listed_in_pages() {
+        let mut deps = mock_dependencies(20, &[]);
+        for (address, amount) in &[("carol", 300), ("alice", 100), ("bob", 200)] {
+            payments_store(
+                &mut deps.storage,
+                HumanAddr::from(*address),
+                Uint128(*amount),
+            )
+            .unwrap();
+        }
+
+        let msg1 = QueryMsg::ListPayments {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res1: PaymentsResponse = from_binary(&query(&deps, msg1).unwrap()).unwrap();
+        assert_eq!(
+            res1.payments,
+            vec![
+                PaymentEntry {
+                    address: HumanAddr::from("alice"),
+                    amount: Uint128(100),
+                },
+                PaymentEntry {
+                    address: HumanAddr::from("bob"),
+                    amount: Uint128(200),
+                },
+            ]
+        );
+
+        let msg2 = QueryMsg::ListPayments {
+            start_after: Some(HumanAddr::from("bob")),
+            limit: None,
+        };
+        let res2: PaymentsResponse = from_binary(&query(&deps, msg2).unwrap()).unwrap();
+        assert_eq!(res2.payments.len(), 1);
+        assert_eq!(res2.payments[0].address, HumanAddr::from("carol"));
+    }
+
+    #[test]
+    fn prepaid_terms_lapse_and_are_swept() {
+        let mut deps = mock_dependencies(20, &[]);
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let name_service = mock_env("names", &[]);
+        let mut env = mock_env(alice.clone(), &[Coin::new(299, LUNA)]);
+        let start = env.block.time;
+
+        let msg = InitMsg {
+            premium: Some(Premium {
+                start: Uint128(1000),
+                window: 100,
+                decay: Decay::Linear,
+            }),
+            sweep_reward: Some(Uint128(30)),
+            ..default_init()
+        };
+        init(&mut deps, env.clone(), msg).unwrap();
+        handle(&mut deps, name_service.clone(), Signup {}).unwrap();
+
+        let subscribe = |periods| Subscribe {
+            name: "alice".to_string(),
+            periods,
+            referrer: None,
+            voucher: None,
+        };
+        let res = handle(&mut deps, env.clone(), subscribe(Some(0)));
+        assert_eq!(res, Err(ContractError::InvalidPeriods {}.into()));
+        let res = handle(&mut deps, env.clone(), subscribe(Some(3)));
+        let short = ContractError::InsufficientFunds {
+            price: Uint128(300),
+            sent: Uint128(299),
+        };
+        assert_eq!(res, Err(short.into()));
+        env.message.sent_funds = vec![Coin::new(300, LUNA)];
+        handle(&mut deps, env.clone(), subscribe(Some(3))).unwrap();
+        let msg = Subscribe {
+            name: "bob".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        handle(
+            &mut deps,
+            mock_env(bob.clone(), &[Coin::new(100, LUNA)]),
+            msg,
+        )
+        .unwrap();
+        for (name, owner) in &[("alice", &alice), ("bob", &bob)] {
+            let confirmation = HandleMsg::NameRegistered {
+                name: name.to_string(),
+                owner: (*owner).clone(),
+            };
+            handle(&mut deps, name_service.clone(), confirmation).unwrap();
+        }
+
+        let status = try_subscription_status(&deps, "alice".to_string(), start + 1500).unwrap();
+        assert_eq!(
+            status,
+            SubscriptionStatusResponse {
+                name: "alice".to_string(),
+                owner: alice.clone(),
+                state: SubscriptionState::Active,
+                paid_until: start + 3000,
+                grace_ends: start + 3100,
+                periods_left: 1,
+            }
+        );
+        let status = try_subscription_status(&deps, "bob".to_string(), start + 1050).unwrap();
+        assert_eq!(status.state, SubscriptionState::Grace);
+        let status = try_subscription_status(&deps, "bob".to_string(), start + 1100).unwrap();
+        assert_eq!(status.state, SubscriptionState::Lapsed);
+
+        let mut sweeper = mock_env("sweeper", &[]);
+        sweeper.block.time = start + 1099;
+        let res = handle(&mut deps, sweeper.clone(), HandleMsg::Sweep { limit: None }).unwrap();
+        assert!(res.messages.is_empty());
+
+        sweeper.block.time = start + 1100;
+        let res = handle(&mut deps, sweeper.clone(), HandleMsg::Sweep { limit: None }).unwrap();
+        let swept: SweepResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(swept.names, vec!["bob".to_string()]);
+        assert_eq!(swept.reward, Uint128(30));
+        let deregister = DeregisterBatch {
+            names: vec![Name {
+                value: "bob".to_string(),
+                owner: deps.api.canonical_address(&bob).unwrap(),
+            }],
+        };
+        assert_eq!(
+            res.messages,
+            vec![
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: HumanAddr::from("names"),
+                    msg: to_binary(&deregister).unwrap(),
+                    send: vec![],
+                }),
+                BankMsg::Send {
+                    from_address: sweeper.contract.address.clone(),
+                    to_address: HumanAddr::from("sweeper"),
+                    amount: vec![Coin::new(30, LUNA)],
+                }
+                .into(),
+            ]
+        );
+        let res = try_subscription_info(&deps, "bob".to_string());
+        assert_eq!(res, Err(ContractError::NameNotFound {}));
+        assert!(!address_exists(&deps, bob).unwrap());
+        let price = try_priceis(&deps, "bob".to_string(), Some(start + 1150)).unwrap();
+        assert_eq!(price.premium, Uint128(500));
+
+        sweeper.block.time = start + 3100;
+        let res = handle(&mut deps, sweeper, HandleMsg::Sweep { limit: Some(1) }).unwrap();
+        let swept: SweepResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(swept.names, vec!["alice".to_string()]);
+    }
+
+    #[test]
+    fn names_are_renewed_until_grace_ends() {
+        let mut deps = mock_dependencies(20, &[]);
+        let bob = HumanAddr::from("bob");
+        let name_service = mock_env("names", &[]);
+        let mut env = mock_env(bob.clone(), &[Coin::new(100, LUNA)]);
+        let start = env.block.time;
+        let msg = default_init();
+        init(&mut deps, env.clone(), msg).unwrap();
+        handle(&mut deps, name_service.clone(), Signup {}).unwrap();
+        let subscribe = Subscribe {
+            name: "bob".to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        let renew = HandleMsg::Renew {
+            name: "bob".to_string(),
+            periods: Some(2),
+        };
+        handle(&mut deps, env.clone(), subscribe).unwrap();
+        let res = handle(&mut deps, env.clone(), renew.clone());
+        assert_eq!(res, Err(ContractError::NameNotFound {}.into()));
+        let confirmation = HandleMsg::NameRegistered {
+            name: "bob".to_string(),
+            owner: bob.clone(),
+        };
+        handle(&mut deps, name_service, confirmation).unwrap();
+
+        env.block.time = start + 1050;
+        let mut stranger = mock_env("alice", &[Coin::new(200, LUNA)]);
+        stranger.block.time = env.block.time;
+        let res = handle(&mut deps, stranger, renew.clone());
+        assert_eq!(res, Err(ContractError::NotOwner {}.into()));
+        env.message.sent_funds = vec![Coin::new(199, LUNA)];
+        let res = handle(&mut deps, env.clone(), renew.clone());
+        let short = ContractError::InsufficientFunds {
+            price: Uint128(200),
+            sent: Uint128(199),
+        };
+        assert_eq!(res, Err(short.into()));
+
+        // In grace the term is extended from now, as the previous one is over.
+        env.message.sent_funds = vec![Coin::new(200, LUNA)];
+        let res = handle(&mut deps, env.clone(), renew.clone()).unwrap();
+        let expires = start + 1050 + 2000;
+        assert_eq!(
+            res.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: HumanAddr::from("names"),
+                msg: to_binary(&NSRenew {
+                    name: "bob".to_string(),
+                    expires: Some(expires),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        assert_eq!(res.log[5], log("expires", expires));
+        let info = try_subscription_info(&deps, "bob".to_string()).unwrap();
+        assert_eq!(info.expires, expires);
+        assert_eq!(info.paid, Uint128(300));
+        assert_eq!(
+            try_paidamountis(&deps, bob.clone()).unwrap().amount,
+            Uint128(300)
+        );
+
+        let mut sweeper = mock_env("sweeper", &[]);
+        sweeper.block.time = start + 1100;
+        let res = handle(&mut deps, sweeper.clone(), HandleMsg::Sweep { limit: None }).unwrap();
+        let swept: SweepResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert!(swept.names.is_empty());
+        let status = try_subscription_status(&deps, "bob".to_string(), start + 1100).unwrap();
+        assert_eq!(status.state, SubscriptionState::Active);
+
+        // Before it lapses, the term is extended from its end.
+        env.block.time = start + 2000;
+        env.message.sent_funds = vec![Coin::new(100, LUNA)];
+        let res = handle(
+            &mut deps,
+            env.clone(),
+            HandleMsg::Renew {
+                name: "bob".to_string(),
+                periods: None,
+            },
+        )
+        .unwrap();
+        let expires = expires + 1000;
+        assert_eq!(res.log[5], log("expires", expires));
+
+        env.block.time = expires + 100;
+        let res = handle(&mut deps, env, renew);
+        assert_eq!(res, Err(ContractError::NameLapsed {}.into()));
+        sweeper.block.time = expires + 100;
+        let res = handle(&mut deps, sweeper, HandleMsg::Sweep { limit: None }).unwrap();
+        let swept: SweepResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(swept.names, vec!["bob".to_string()]);
+    }
+
+    #[test]
+    fn batches_renew_each_name() {
+        let mut deps = mock_dependencies(20, &[]);
+        let bob = HumanAddr::from("bob");
+        let name_service = mock_env("names", &[]);
+        let mut env = mock_env(bob.clone(), &[Coin::new(100, LUNA)]);
+        let start = env.block.time;
+        init(&mut deps, env.clone(), default_init()).unwrap();
+        handle(&mut deps, name_service.clone(), Signup {}).unwrap();
+        for name in &["a", "b"] {
+            let subscribe = Subscribe {
+                name: name.to_string(),
+                periods: None,
+                referrer: None,
+                voucher: None,
+            };
+            handle(&mut deps, env.clone(), subscribe).unwrap();
+            let confirmation = HandleMsg::NameRegistered {
+                name: name.to_string(),
+                owner: bob.clone(),
+            };
+            handle(&mut deps, name_service.clone(), confirmation).unwrap();
+        }
+
+        env.block.time = start + 500;
+        env.message.sent_funds = vec![Coin::new(250, LUNA)];
+        let batch = |names: &[&str]| HandleMsg::RenewBatch {
+            names: names.iter().map(|name| name.to_string()).collect(),
+            periods: None,
+        };
+        let res = handle(&mut deps, env.clone(), batch(&[]));
+        assert_eq!(res, Err(ContractError::EmptyBatch {}.into()));
+        let res = handle(&mut deps, env.clone(), batch(&["a", "a"]));
+        assert_eq!(res, Err(ContractError::DuplicateName {}.into()));
+        let res = handle(&mut deps, env.clone(), batch(&["a", "c"]));
+        assert_eq!(res, Err(ContractError::NameNotFound {}.into()));
+        env.message.sent_funds = vec![Coin::new(199, LUNA)];
+        let res = handle(&mut deps, env.clone(), batch(&["a", "b"]));
+        let short = ContractError::InsufficientFunds {
+            price: Uint128(200),
+            sent: Uint128(199),
+        };
+        assert_eq!(res, Err(short.into()));
+        assert_eq!(
+            try_subscription_info(&deps, "a".to_string())
+                .unwrap()
+                .expires,
+            start + 1000
+        );
+
+        env.message.sent_funds = vec![Coin::new(250, LUNA)];
+        let res = handle(&mut deps, env.clone(), batch(&["a", "b"])).unwrap();
+        assert_eq!(res.messages.len(), 2);
+        assert_eq!(res.log[5], log("paid", "150,100"));
+        let data: RenewBatchResponse = from_binary(&res.data.unwrap()).unwrap();
+        let expires = start + 2000;
+        assert_eq!(
+            data.results,
+            vec![
+                RenewBatchEntry {
+                    name: "a".to_string(),
+                    paid: Uint128(150),
+                    expires,
+                },
+                RenewBatchEntry {
+                    name: "b".to_string(),
+                    paid: Uint128(100),
+                    expires,
+                },
+            ]
+        );
+        assert_eq!(
+            try_subscription_info(&deps, "a".to_string()).unwrap().paid,
+            Uint128(250)
+        );
+        assert_eq!(try_paidamountis(&deps, bob).unwrap().amount, Uint128(450));
+
+        let mut sweeper = mock_env("sweeper", &[]);
+        sweeper.block.time = start + 1100;
+        let res = handle(&mut deps, sweeper, HandleMsg::Sweep { limit: None }).unwrap();
+        let swept: SweepResponse = from_binary(&res.data.unwrap()).unwrap();
+        assert!(swept.names.is_empty());
+    }
+
+    #[test]
+    fn referrers_earn_and_claim_their_share() {
+        let mut deps = mock_dependencies(20, &[]);
+        let alice = HumanAddr::from("alice");
+        let carol = HumanAddr::from("carol");
+        let env = mock_env(alice.clone(), &[Coin::new(200, LUNA)]);
+
+        let mut msg = InitMsg {
+            referral_share: Some(Decimal::percent(101)),
+            ..default_init()
+        };
+        let res = init(&mut deps, env.clone(), msg.clone());
+        assert_eq!(res, Err(ContractError::InvalidReferralShare {}.into()));
+        msg.referral_share = Some(Decimal::percent(10));
+        init(&mut deps, env.clone(), msg).unwrap();
+        handle(&mut deps, mock_env("names", &[]), Signup {}).unwrap();
+
+        let subscribe = |referrer: &HumanAddr| Subscribe {
+            name: "alice".to_string(),
+            periods: None,
+            referrer: Some(referrer.clone()),
+            voucher: None,
+        };
+        let res = handle(&mut deps, env.clone(), subscribe(&alice));
+        assert_eq!(res, Err(ContractError::SelfReferral {}.into()));
+        let res = handle(&mut deps, env.clone(), subscribe(&carol)).unwrap();
+        assert!(res.log.contains(&log("referral", "20")));
+        let confirmation = HandleMsg::NameRegistered {
+            name: "alice".to_string(),
+            owner: alice.clone(),
+        };
+        handle(&mut deps, mock_env("names", &[]), confirmation).unwrap();
+        let paid = try_paidamountis(&deps, alice).unwrap();
+        assert_eq!(paid.amount, Uint128(180));
+
+        let earnings = try_referral_earnings(&deps, carol.clone()).unwrap();
+        assert_eq!(
+            earnings,
+            ReferralResponse {
+                referrer: carol.clone(),
+                referrals: 1,
+                earned: Uint128(20),
+                claimed: Uint128::zero(),
+                unclaimed: Uint128(20),
+            }
+        );
+
+        let claimer = mock_env(carol.clone(), &[]);
+        let res = handle(&mut deps, claimer.clone(), HandleMsg::ClaimReferral {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![BankMsg::Send {
+                from_address: claimer.contract.address.clone(),
+                to_address: carol.clone(),
+                amount: vec![Coin::new(20, LUNA)],
+            }
+            .into()]
+        );
+        let res = handle(&mut deps, claimer, HandleMsg::ClaimReferral {});
+        assert_eq!(res, Err(ContractError::NothingToClaim {}.into()));
+
+        let msg = QueryMsg::ListReferrers {
+            start_after: None,
+            limit: None,
+        };
+        let res: ReferrersResponse = from_binary(&query(&deps, msg).unwrap()).unwrap();
+        assert_eq!(res.referrers.len(), 1);
+        assert_eq!(res.referrers[0].claimed, Uint128(20));
+        assert!(res.referrers[0].unclaimed.is_zero());
+    }
+
+    #[test]
+    fn allowlists_and_vouchers_discount_subscriptions() {
+        use k256::ecdsa::signature::Signer;
+        use k256::ecdsa::SigningKey;
+
+        let mut deps = mock_dependencies(20, &[]);
+        let signer = SigningKey::from_slice(&[1; 32]).unwrap();
+        let public = signer.verifying_key().to_encoded_point(true);
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let admin = mock_env("admin", &[]);
+
+        let mut msg = InitMsg {
+            voucher_signer: Some(Binary(vec![4; 33])),
+            ..default_init()
+        };
+        let res = init(&mut deps, admin.clone(), msg.clone());
+        assert_eq!(res, Err(ContractError::InvalidVoucherSigner {}.into()));
+        msg.voucher_signer = Some(Binary::from(public.as_bytes()));
+        init(&mut deps, admin.clone(), msg).unwrap();
+        handle(&mut deps, mock_env("names", &[]), Signup {}).unwrap();
+
+        let allow = |quota, discount| HandleMsg::SetAllowlist {
+            entries: vec![AllowlistEntry {
+                address: alice.clone(),
+                quota,
+                discount,
+            }],
+        };
+        let res = handle(&mut deps, mock_env(&alice, &[]), allow(2, Decimal::one()));
+        assert_eq!(res, Err(StdError::unauthorized()));
+        let res = handle(&mut deps, admin.clone(), allow(2, Decimal::percent(101)));
+        assert_eq!(res, Err(ContractError::InvalidDiscount {}.into()));
+        handle(&mut deps, admin.clone(), allow(2, Decimal::percent(50))).unwrap();
+
+        // Half price while the quota lasts, then the full price again.
+        let subscribe = |name: &str, voucher| Subscribe {
+            name: name.to_string(),
+            periods: None,
+            referrer: None,
+            voucher,
+        };
+        let env = mock_env(&alice, &[Coin::new(50, LUNA)]);
+        let res = handle(&mut deps, env.clone(), subscribe("a1", None)).unwrap();
+        assert!(res.log.contains(&log("discount", "50")));
+        assert_eq!(try_allowance(&deps, alice.clone()).unwrap().quota, 1);
+        handle(&mut deps, env.clone(), subscribe("a2", None)).unwrap();
+        let res = try_list_allowlist(&deps, None, None).unwrap();
+        assert!(res.entries.is_empty());
+        let res = handle(&mut deps, env, subscribe("a3", None));
+        let short = ContractError::InsufficientFunds {
+            price: Uint128(100),
+            sent: Uint128(50),
+        };
+        assert_eq!(res, Err(short.into()));
+
+        let mut voucher = Voucher {
+            id: "launch-1".to_string(),
+            discount: Decimal::one(),
+            recipient: Some(bob.clone()),
+            expires: Some(admin.block.time + 10),
+            signature: Binary::default(),
+        };
+        let message = voucher.message(&admin.contract.address).unwrap();
+        let signature: Signature = signer.sign(&message);
+        voucher.signature = Binary(signature.to_bytes().to_vec());
+
+        let res = handle(
+            &mut deps,
+            mock_env(&alice, &[]),
+            subscribe("b1", Some(voucher.clone())),
+        );
+        assert_eq!(res, Err(ContractError::InvalidVoucher {}.into()));
+        let mut tampered = voucher.clone();
+        tampered.id = "launch-2".to_string();
+        let res = handle(
+            &mut deps,
+            mock_env(&bob, &[]),
+            subscribe("b1", Some(tampered)),
+        );
+        assert_eq!(res, Err(ContractError::InvalidVoucher {}.into()));
+        let mut late = mock_env(&bob, &[]);
+        late.block.time += 11;
+        let res = handle(&mut deps, late, subscribe("b1", Some(voucher.clone())));
+        assert_eq!(res, Err(ContractError::VoucherExpired {}.into()));
+
+        // A free subscription needs no funds.
+        let res = handle(
+            &mut deps,
+            mock_env(&bob, &[]),
+            subscribe("b1", Some(voucher.clone())),
+        )
+        .unwrap();
+        assert!(res.log.contains(&log("voucher", "launch-1")));
+        let status = try_voucher_status(&deps, "launch-1".to_string()).unwrap();
+        assert_eq!(status.redeemed_by, Some(bob.clone()));
+        let res = handle(
+            &mut deps,
+            mock_env(&bob, &[]),
+            subscribe("b2", Some(voucher)),
+        );
+        assert_eq!(res, Err(ContractError::VoucherRedeemed {}.into()));
+    }
+
+    #[test]
+    fn staked_names_unbond_and_are_claimed() {
+        let mut deps = mock_dependencies(20, &[]);
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let validators = vec![HumanAddr::from("val1"), HumanAddr::from("val2")];
+        let admin = mock_env("admin", &[]);
+
+        let mut msg = InitMsg {
+            staking: Some(Staking {
+                validators: vec![],
+                stake: Uint128(500),
+                unbonding_period: 50,
+            }),
+            ..default_init()
+        };
+        let res = init(&mut deps, admin.clone(), msg.clone());
+        assert_eq!(res, Err(ContractError::InvalidStaking {}.into()));
+        msg.staking = Some(Staking {
+            validators: validators.clone(),
+            stake: Uint128(500),
+            unbonding_period: 50,
+        });
+        init(&mut deps, admin.clone(), msg).unwrap();
+        handle(&mut deps, mock_env("names", &[]), Signup {}).unwrap();
+
+        let stake = |name: &str| HandleMsg::Stake {
+            name: name.to_string(),
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(&alice, &[Coin::new(400, LUNA)]),
+            stake("alice"),
+        );
+        let short = ContractError::InsufficientFunds {
+            price: Uint128(500),
+            sent: Uint128(400),
+        };
+        assert_eq!(res, Err(short.into()));
+
+        // Each stake goes to the validator holding the least, and rewards to the treasury.
+        for (owner, name, amount) in [(&alice, "alice", 600), (&bob, "bob", 500)] {
+            let env = mock_env(owner, &[Coin::new(amount, LUNA)]);
+            handle(&mut deps, env, stake(name)).unwrap();
+            let confirmation = HandleMsg::NameRegistered {
+                name: name.to_string(),
+                owner: owner.clone(),
+            };
+            handle(&mut deps, mock_env("names", &[]), confirmation).unwrap();
+        }
+        let info = try_subscription_info(&deps, "bob".to_string()).unwrap();
+        let held = Stake {
+            validator: validators[1].clone(),
+            amount: Uint128(500),
+        };
+        assert_eq!(info.stake, Some(held));
+        assert_eq!(info.paid, Uint128::zero());
+        let res = handle(
+            &mut deps,
+            mock_env(&bob, &[]),
+            HandleMsg::WithdrawRewards {},
+        )
+        .unwrap();
+        let withdraw = |validator: &HumanAddr| -> CosmosMsg {
+            StakingMsg::Withdraw {
+                validator: validator.clone(),
+                recipient: Some(admin.message.sender.clone()),
+            }
+            .into()
+        };
+        assert_eq!(
+            res.messages,
+            vec![withdraw(&validators[0]), withdraw(&validators[1])]
+        );
+
+        // The stake comes back in full, once the unbonding period has passed.
+        let contract = admin.contract.address.clone();
+        bond(
+            &mut deps,
+            &contract,
+            &[(&validators[0], 600), (&validators[1], 500)],
+        );
+        let res = handle(
+            &mut deps,
+            mock_env(&alice, &[]),
+            Unsubscribe {
+                name: "alice".to_string(),
+            },
+        )
+        .unwrap();
+        let undelegate: CosmosMsg = StakingMsg::Undelegate {
+            validator: validators[0].clone(),
+            amount: Coin::new(600, LUNA),
+        }
+        .into();
+        assert!(res.messages.contains(&undelegate));
+        assert!(res.log.contains(&log("unbonding", 600)));
+        let unbonding = try_unbonding(&deps, alice.clone()).unwrap();
+        assert_eq!(
+            unbonding.entries,
+            vec![Unbonding {
+                amount: Uint128(600),
+                release: admin.block.time + 50,
+            }]
+        );
+        let res = handle(&mut deps, mock_env(&alice, &[]), HandleMsg::ClaimStake {});
+        assert_eq!(res, Err(ContractError::NoUnbondedStake {}.into()));
+        let mut later = mock_env(&alice, &[]);
+        later.block.time += 50;
+        let res = handle(&mut deps, later.clone(), HandleMsg::ClaimStake {});
+        assert_eq!(res, Err(ContractError::StakeNotReleased {}.into()));
+        deps.querier
+            .update_balance(&admin.contract.address, vec![Coin::new(600, LUNA)]);
+        let res = handle(&mut deps, later, HandleMsg::ClaimStake {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![BankMsg::Send {
+                from_address: admin.contract.address.clone(),
+                to_address: alice.clone(),
+                amount: vec![Coin::new(600, LUNA)],
+            }
+            .into()]
+        );
+        assert!(try_unbonding(&deps, alice.clone())
+            .unwrap()
+            .entries
+            .is_empty());
+    }
+
+    #[test]
+    fn transferred_stakes_return_to_the_staker() {
+        let mut deps = mock_dependencies(20, &[]);
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let admin = mock_env("admin", &[]);
+        let msg = InitMsg {
+            staking: Some(Staking {
+                validators: vec![HumanAddr::from("val1")],
+                stake: Uint128(500),
+                unbonding_period: 50,
+            }),
+            ..default_init()
+        };
+        init(&mut deps, admin.clone(), msg).unwrap();
+        handle(&mut deps, mock_env("names", &[]), Signup {}).unwrap();
+        let stake = HandleMsg::Stake {
+            name: "held".to_string(),
+        };
+        handle(&mut deps, mock_env(&alice, &[Coin::new(500, LUNA)]), stake).unwrap();
+        let confirmation = HandleMsg::NameRegistered {
+            name: "held".to_string(),
+            owner: alice.clone(),
+        };
+        handle(&mut deps, mock_env("names", &[]), confirmation).unwrap();
+        let transfer = HandleMsg::NameTransferred {
+            name: "held".to_string(),
+            owner: bob.clone(),
+        };
+        handle(&mut deps, mock_env("names", &[]), transfer).unwrap();
+
+        bond(
+            &mut deps,
+            &admin.contract.address,
+            &[(&HumanAddr::from("val1"), 500)],
+        );
+        let unsubscribe = Unsubscribe {
+            name: "held".to_string(),
+        };
+        let res = handle(&mut deps, mock_env(&alice, &[]), unsubscribe.clone());
+        assert_eq!(res, Err(ContractError::NotOwner {}.into()));
+        handle(&mut deps, mock_env(&bob, &[]), unsubscribe).unwrap();
+        assert!(try_unbonding(&deps, bob.clone())
+            .unwrap()
+            .entries
+            .is_empty());
+        let unbonding = try_unbonding(&deps, alice.clone()).unwrap();
+        assert_eq!(unbonding.entries[0].amount, Uint128(500));
+
+        deps.querier
+            .update_balance(&admin.contract.address, vec![Coin::new(500, LUNA)]);
+        let mut later = mock_env(&bob, &[]);
+        later.block.time += 50;
+        let res = handle(&mut deps, later.clone(), HandleMsg::ClaimStake {});
+        assert_eq!(res, Err(ContractError::NoUnbondedStake {}.into()));
+        later.message.sender = alice.clone();
+        let res = handle(&mut deps, later, HandleMsg::ClaimStake {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![BankMsg::Send {
+                from_address: admin.contract.address,
+                to_address: alice,
+                amount: vec![Coin::new(500, LUNA)],
+            }
+            .into()]
+        );
+    }
+
+    #[test]
+    fn slashed_stakes_unbond_their_share() {
+        let mut deps = mock_dependencies(20, &[]);
+        let owners = [HumanAddr::from("alice"), HumanAddr::from("bob")];
+        let validator = HumanAddr::from("val1");
+        let admin = mock_env("admin", &[]);
+        let contract = admin.contract.address.clone();
+        let msg = InitMsg {
+            staking: Some(Staking {
+                validators: vec![validator.clone()],
+                stake: Uint128(500),
+                unbonding_period: 50,
+            }),
+            referral_share: Some(Decimal::percent(10)),
+            ..default_init()
+        };
+        init(&mut deps, admin.clone(), msg).unwrap();
+        handle(&mut deps, mock_env("names", &[]), Signup {}).unwrap();
+        for (owner, amount) in owners.iter().zip(&[600, 500]) {
+            let env = mock_env(owner, &[Coin::new(*amount, LUNA)]);
+            let name = owner.to_string();
+            handle(&mut deps, env, HandleMsg::Stake { name: name.clone() }).unwrap();
+            let confirmation = HandleMsg::NameRegistered {
+                name,
+                owner: owner.clone(),
+            };
+            handle(&mut deps, mock_env("names", &[]), confirmation).unwrap();
+        }
+
+        // A fifth of the 1100 delegated was slashed, which each stake leaving shares.
+        bond(&mut deps, &contract, &[(&validator, 880)]);
+        let unsubscribe = |owner: &HumanAddr| Unsubscribe {
+            name: owner.to_string(),
+        };
+        let res = handle(
+            &mut deps,
+            mock_env(&owners[0], &[]),
+            unsubscribe(&owners[0]),
+        )
+        .unwrap();
+        let undelegate: CosmosMsg = StakingMsg::Undelegate {
+            validator: validator.clone(),
+            amount: Coin::new(480, LUNA),
+        }
+        .into();
+        assert!(res.messages.contains(&undelegate));
+        assert!(res.log.contains(&log("unbonding", 480)));
+        assert!(res.log.contains(&log("slashed", "120")));
+        bond(&mut deps, &contract, &[(&validator, 400)]);
+        let msg = HandleMsg::UnsubscribeBatch {
+            names: vec![owners[1].to_string()],
+        };
+        let res = handle(&mut deps, mock_env(&owners[1], &[]), msg).unwrap();
+        assert!(res.log.contains(&log("unbonding", 400)));
+        assert!(res.log.contains(&log("slashed", "100")));
+        let unbonding = try_unbonding(&deps, owners[1].clone()).unwrap();
+        assert_eq!(unbonding.entries[0].amount, Uint128(400));
+
+        // carol's payment keeps her refund of 9 and alice's referral of 10 in the balance.
+        let subscribe = Subscribe {
+            name: "carol".to_string(),
+            periods: None,
+            referrer: Some(owners[0].clone()),
+            voucher: None,
+        };
+        handle(
+            &mut deps,
+            mock_env("carol", &[Coin::new(100, LUNA)]),
+            subscribe,
+        )
+        .unwrap();
+        let confirmation = HandleMsg::NameRegistered {
+            name: "carol".to_string(),
+            owner: HumanAddr::from("carol"),
+        };
+        handle(&mut deps, mock_env("names", &[]), confirmation).unwrap();
+
+        // Claims wait until the contract holds them besides what it owes, then pay in full.
+        deps.querier
+            .update_balance(&contract, vec![Coin::new(480 + 18, LUNA)]);
+        let mut later = mock_env(&owners[0], &[]);
+        later.block.time += 50;
+        let res = handle(&mut deps, later.clone(), HandleMsg::ClaimStake {});
+        assert_eq!(res, Err(ContractError::StakeNotReleased {}.into()));
+        assert_eq!(
+            try_unbonding(&deps, owners[0].clone())
+                .unwrap()
+                .entries
+                .len(),
+            1
+        );
+        deps.querier
+            .update_balance(&contract, vec![Coin::new(480 + 19, LUNA)]);
+        let res = handle(&mut deps, later, HandleMsg::ClaimStake {}).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![BankMsg::Send {
+                from_address: contract,
+                to_address: owners[0].clone(),
+                amount: vec![Coin::new(480, LUNA)],
+            }
+            .into()]
+        );
+        assert!(try_unbonding(&deps, owners[0].clone())
+            .unwrap()
+            .entries
+            .is_empty());
     }
 }
@@ -0,0 +1,113 @@
+use cosmwasm_std::{StdError, Uint128};
+use snafu::Snafu;
+
+#[derive(Snafu, Debug, PartialEq)]
+#[snafu(visibility = "pub")]
+pub enum ContractError {
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+
+    #[snafu(display("Unauthorized"))]
+    Unauthorized {},
+
+    #[snafu(display("Name service has not signed up yet."))]
+    NotInitialized {},
+
+    #[snafu(display("No sent value found."))]
+    NoFunds {},
+
+    #[snafu(display("Only {} is accepted, got {}.", expected, denom))]
+    InvalidDenom { expected: String, denom: String },
+
+    #[snafu(display("Sent value does not cover the price of {}.", price))]
+    InsufficientFunds { price: Uint128, sent: Uint128 },
+
+    #[snafu(display("Batch contains no names."))]
+    EmptyBatch {},
+
+    #[snafu(display("Batch contains a name more than once."))]
+    DuplicateName {},
+
+    #[snafu(display("Name is already taken."))]
+    NameTaken {},
+
+    #[snafu(display("Name not found."))]
+    NameNotFound {},
+
+    #[snafu(display("Not the owner of the name."))]
+    NotOwner {},
+
+    #[snafu(display("No pending registration matches."))]
+    NoPendingRegistration {},
+
+    #[snafu(display("Periods must be at least one and the term must fit in time."))]
+    InvalidPeriods {},
+
+    #[snafu(display("Referral share must be at most one."))]
+    InvalidReferralShare {},
+
+    #[snafu(display("Cannot refer yourself."))]
+    SelfReferral {},
+
+    #[snafu(display("No referral earnings to claim."))]
+    NothingToClaim {},
+
+    #[snafu(display("Discount must be at most one."))]
+    InvalidDiscount {},
+
+    #[snafu(display("Voucher signer is not a secp256k1 public key."))]
+    InvalidVoucherSigner {},
+
+    #[snafu(display("Voucher is not signed by the voucher signer for this sender."))]
+    InvalidVoucher {},
+
+    #[snafu(display("Voucher has expired."))]
+    VoucherExpired {},
+
+    #[snafu(display("Voucher has already been redeemed."))]
+    VoucherRedeemed {},
+
+    #[snafu(display("Staking needs validators, a positive stake and an unbonding period."))]
+    InvalidStaking {},
+
+    #[snafu(display("Names cannot be held by staking here."))]
+    StakingDisabled {},
+
+    #[snafu(display("Name has lapsed and must be subscribed to again."))]
+    NameLapsed {},
+
+    #[snafu(display("Staked names do not expire."))]
+    NameStaked {},
+
+    #[snafu(display("No unbonded stake to claim."))]
+    NoUnbondedStake {},
+
+    #[snafu(display("The chain has not returned the unbonded stake yet."))]
+    StakeNotReleased {},
+
+    #[snafu(display("Premium window and half life must be positive."))]
+    InvalidPremium {},
+
+    #[snafu(display("Pricing is required to migrate from 0.1."))]
+    MissingPricing {},
+
+    #[snafu(display("Cannot migrate from {} to {}.", from, to))]
+    CannotMigrate { from: String, to: String },
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+/// The 0.10 entry points only return `StdError`, so other kinds travel as their message.
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std { source } => source,
+            ContractError::Unauthorized {} => StdError::unauthorized(),
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
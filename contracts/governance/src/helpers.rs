@@ -0,0 +1,271 @@
+//! Typed access to a deployed governance contract for other contracts, the counterpart of
+//! `name_service::helpers`.
+
+use crate::msg::{
+    HandleMsg, PaidAmountResponse, PriceResponse, QueryMsg, ReferralResponse,
+    SubscriptionStatusResponse, UnbondingResponse,
+};
+use cosmwasm_std::{
+    to_binary, Coin, CosmosMsg, HumanAddr, Querier, QueryRequest, StdResult, Uint128, WasmMsg,
+    WasmQuery,
+};
+use name_service::helpers::NameServiceContract;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Address of a governance instance.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GovernanceContract(pub HumanAddr);
+
+impl GovernanceContract {
+    pub fn addr(&self) -> HumanAddr {
+        self.0.clone()
+    }
+
+    /// Wraps `msg` in a `WasmMsg::Execute` to this contract.
+    pub fn call(&self, msg: &HandleMsg, send: Vec<Coin>) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: self.addr(),
+            msg: to_binary(msg)?,
+            send,
+        }))
+    }
+
+    /// Subscribes the sending contract to `name` for `periods` registration periods, one if
+    /// `None`; `funds` must cover `price` plus the base price of each extra period.
+    pub fn subscribe_msg(
+        &self,
+        name: &str,
+        periods: Option<u64>,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = HandleMsg::Subscribe {
+            name: name.to_string(),
+            periods,
+            referrer: None,
+            voucher: None,
+        };
+        self.call(&msg, funds)
+    }
+
+    pub fn unsubscribe_msg(&self, name: &str) -> StdResult<CosmosMsg> {
+        let msg = HandleMsg::Unsubscribe {
+            name: name.to_string(),
+        };
+        self.call(&msg, vec![])
+    }
+
+    /// Extends the term of `name`, held by the sending contract, by `periods` registration
+    /// periods, one if `None`; `funds` must cover the base price of each.
+    pub fn renew_msg(
+        &self,
+        name: &str,
+        periods: Option<u64>,
+        funds: Vec<Coin>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = HandleMsg::Renew {
+            name: name.to_string(),
+            periods,
+        };
+        self.call(&msg, funds)
+    }
+
+    /// Holds `name` for the sending contract by delegating `funds`, which must cover the
+    /// configured stake plus any premium.
+    pub fn stake_msg(&self, name: &str, funds: Vec<Coin>) -> StdResult<CosmosMsg> {
+        let msg = HandleMsg::Stake {
+            name: name.to_string(),
+        };
+        self.call(&msg, funds)
+    }
+
+    /// Pays the sending contract its stakes that have finished unbonding.
+    pub fn claim_stake_msg(&self) -> StdResult<CosmosMsg> {
+        self.call(&HandleMsg::ClaimStake {}, vec![])
+    }
+
+    /// Pays the sending contract what it has earned referring subscriptions and not yet
+    /// claimed.
+    pub fn claim_referral_msg(&self) -> StdResult<CosmosMsg> {
+        self.call(&HandleMsg::ClaimReferral {}, vec![])
+    }
+
+    pub fn query<Q: Querier, T: DeserializeOwned>(
+        &self,
+        querier: &Q,
+        msg: &QueryMsg,
+    ) -> StdResult<T> {
+        querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: self.addr(),
+            msg: to_binary(msg)?,
+        }))
+    }
+
+    /// Total `address` has paid for the names it holds, zero if none.
+    pub fn paid_amount<Q: Querier>(&self, querier: &Q, address: &HumanAddr) -> StdResult<Uint128> {
+        let res: PaidAmountResponse = self.query(
+            querier,
+            &QueryMsg::PaidAmountIs {
+                address: address.clone(),
+            },
+        )?;
+        Ok(res.amount)
+    }
+
+    /// What subscribing to `name` costs at `time`, usually `env.block.time`.
+    pub fn price<Q: Querier>(
+        &self,
+        querier: &Q,
+        name: &str,
+        time: u64,
+    ) -> StdResult<PriceResponse> {
+        self.query(
+            querier,
+            &QueryMsg::PriceIs {
+                name: name.to_string(),
+                time: Some(time),
+            },
+        )
+    }
+
+    /// Whether the subscription of `name` is active, in grace or lapsed at `time`.
+    pub fn subscription_status<Q: Querier>(
+        &self,
+        querier: &Q,
+        name: &str,
+        time: u64,
+    ) -> StdResult<SubscriptionStatusResponse> {
+        self.query(
+            querier,
+            &QueryMsg::SubscriptionStatus {
+                name: name.to_string(),
+                time,
+            },
+        )
+    }
+
+    /// What `referrer` has earned from referrals, and how much of it is still unclaimed.
+    pub fn referral_earnings<Q: Querier>(
+        &self,
+        querier: &Q,
+        referrer: &HumanAddr,
+    ) -> StdResult<ReferralResponse> {
+        self.query(
+            querier,
+            &QueryMsg::ReferralEarnings {
+                referrer: referrer.clone(),
+            },
+        )
+    }
+
+    /// Stakes `address` has undelegated and not yet claimed.
+    pub fn unbonding<Q: Querier>(
+        &self,
+        querier: &Q,
+        address: &HumanAddr,
+    ) -> StdResult<UnbondingResponse> {
+        self.query(
+            querier,
+            &QueryMsg::Unbonding {
+                address: address.clone(),
+            },
+        )
+    }
+
+    /// The name service this governance instance registers names with.
+    pub fn name_service<Q: Querier>(&self, querier: &Q) -> StdResult<NameServiceContract> {
+        let address: HumanAddr = self.query(querier, &QueryMsg::GetNameServiceAddress {})?;
+        Ok(NameServiceContract(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::msg::{InitMsg, SubscriptionState};
+    use crate::testing::MockGovernance;
+    use cosmwasm_std::{coins, from_binary};
+
+    fn init_msg() -> InitMsg {
+        InitMsg {
+            nameservice_code_id: 1,
+            treasury: None,
+            base_price: Uint128(100),
+            registration_period: 1000,
+            grace_period: 100,
+            premium: None,
+            sweep_reward: None,
+            referral_share: None,
+            voucher_signer: None,
+            staking: None,
+        }
+    }
+
+    #[test]
+    fn answers_for_both_contracts() {
+        let mut querier = MockGovernance::new("governance", "names", init_msg());
+        let owner = HumanAddr::from("owner");
+        querier.subscribe("alice", &owner, 150).unwrap();
+        let governance = GovernanceContract(HumanAddr::from("governance"));
+
+        assert_eq!(
+            governance.paid_amount(&querier, &owner).unwrap(),
+            Uint128(150)
+        );
+        assert_eq!(
+            governance
+                .paid_amount(&querier, &HumanAddr::from("other"))
+                .unwrap(),
+            Uint128::zero()
+        );
+        assert_eq!(
+            governance.price(&querier, "bob", 0).unwrap().total,
+            Uint128(100)
+        );
+
+        let status = governance
+            .subscription_status(&querier, "alice", 500)
+            .unwrap();
+        assert_eq!(status.state, SubscriptionState::Active);
+
+        let earnings = governance.referral_earnings(&querier, &owner).unwrap();
+        assert!(earnings.earned.is_zero());
+
+        let names = governance.name_service(&querier).unwrap();
+        assert_eq!(names, NameServiceContract(HumanAddr::from("names")));
+        assert_eq!(names.owner_of(&querier, "alice").unwrap(), Some(owner));
+        assert!(querier
+            .subscribe("bob", &HumanAddr::from("owner"), 50)
+            .is_err());
+    }
+
+    #[test]
+    fn builds_messages() {
+        let governance = GovernanceContract(HumanAddr::from("governance"));
+        match governance
+            .subscribe_msg("alice", Some(2), coins(200, "uluna"))
+            .unwrap()
+        {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                send,
+            }) => {
+                assert_eq!(contract_addr, HumanAddr::from("governance"));
+                assert_eq!(send, coins(200, "uluna"));
+                let msg: HandleMsg = from_binary(&msg).unwrap();
+                assert_eq!(
+                    msg,
+                    HandleMsg::Subscribe {
+                        name: "alice".to_string(),
+                        periods: Some(2),
+                        referrer: None,
+                        voucher: None,
+                    }
+                );
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Storage layout of the first release, kept so `migrate` can read it.
+
+use crate::error::ContractError;
+use crate::msg::MigrateMsg;
+use crate::state::{
+    owner_cfg_store, payments_store, registrations_store, Config, Registration, OWNER_CFG,
+};
+use cosmwasm_std::{
+    from_binary, from_slice, to_binary, to_vec, Api, Binary, Empty, Env, Extern, HumanAddr, Order,
+    Querier, QueryRequest, ReadonlyStorage, StdError, StdResult, Storage, Uint128, WasmQuery,
+};
+use cosmwasm_storage::{
+    singleton_read, PrefixedStorage, ReadonlyPrefixedStorage, ReadonlySingleton,
+};
+use name_service::msg::{NameResponse, NamesResponse, QueryMsg as NSQueryMsg};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Payments keyed by the JSON encoding of the payer address.
+pub static PAYMENTS: &[u8] = b"payments";
+
+/// Names asked for per `NamesByOwner` query, the most the name service hands out.
+const PAGE_LIMIT: u32 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyConfig {
+    pub owner: HumanAddr,
+    pub name_service_address: HumanAddr,
+}
+
+pub fn owner_cfg_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, LegacyConfig> {
+    singleton_read(storage, OWNER_CFG)
+}
+
+/// Adds the pricing from `msg` to the config and re-keys payments by raw address. The first
+/// release kept no per-name records, so every name the name service reports for a payer
+/// gets a fresh term, and the payment is split evenly over them, the first taking the
+/// remainder.
+pub fn migrate_v0<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    msg: MigrateMsg,
+) -> Result<(), ContractError> {
+    let legacy = owner_cfg_read(&deps.storage).load()?;
+    let missing = || ContractError::MissingPricing {};
+    let config = Config {
+        treasury: msg.treasury.unwrap_or_else(|| legacy.owner.clone()),
+        owner: legacy.owner,
+        name_service_address: legacy.name_service_address,
+        base_price: msg.base_price.ok_or_else(missing)?,
+        registration_period: msg.registration_period.ok_or_else(missing)?,
+        grace_period: msg.grace_period.ok_or_else(missing)?,
+        premium: msg.premium,
+        sweep_reward: msg.sweep_reward,
+        referral_share: msg.referral_share,
+        voucher_signer: msg.voucher_signer,
+        staking: msg.staking,
+    };
+    owner_cfg_store(&mut deps.storage).save(&config)?;
+
+    let payments: Vec<(Vec<u8>, Vec<u8>)> = ReadonlyPrefixedStorage::new(PAYMENTS, &deps.storage)
+        .range(None, None, Order::Ascending)
+        .collect();
+    for (key, value) in payments {
+        let payer: HumanAddr = from_slice(&key)?;
+        let paid: Uint128 = from_slice(&value)?;
+        PrefixedStorage::new(PAYMENTS, &mut deps.storage).remove(&key);
+        payments_store(&mut deps.storage, payer.clone(), paid)?;
+
+        let names = held_names(&deps.querier, &config.name_service_address, &payer)?;
+        let count = names.len() as u128;
+        for (i, name) in names.iter().enumerate() {
+            let share = paid.u128() / count + if i == 0 { paid.u128() % count } else { 0 };
+            registrations_store(&mut deps.storage).save(
+                name.as_bytes(),
+                &Registration {
+                    owner: payer.clone(),
+                    expires: env.block.time + config.registration_period,
+                    payer: payer.clone(),
+                    paid: Uint128(share),
+                    stake: None,
+                },
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Lists the names held by `owner`. Later releases page through them with `NamesByOwner`;
+/// the first release rejects that query and only answers `ValueIs`, with a bare string
+/// naming one of them.
+fn held_names<Q: Querier>(
+    querier: &Q,
+    name_service: &HumanAddr,
+    owner: &HumanAddr,
+) -> StdResult<Vec<String>> {
+    let mut names: Vec<String> = vec![];
+    loop {
+        let page = NSQueryMsg::NamesByOwner {
+            owner: owner.clone(),
+            start_after: names.last().cloned(),
+            limit: Some(PAGE_LIMIT),
+        };
+        let response = match query_name_service(querier, name_service, &page)? {
+            Ok(response) => response,
+            Err(_) if names.is_empty() => break,
+            Err(err) => return Err(err),
+        };
+        let page: NamesResponse = from_binary(&response)?;
+        let done = page.names.len() < PAGE_LIMIT as usize;
+        names.extend(page.names.into_iter().map(|entry| entry.name));
+        if done {
+            return Ok(names);
+        }
+    }
+    let value_is = NSQueryMsg::ValueIs {
+        owner: owner.clone(),
+    };
+    let response = query_name_service(querier, name_service, &value_is)??;
+    let name = match from_binary::<NameResponse>(&response) {
+        Ok(res) => res.name.unwrap_or_default(),
+        Err(_) => from_binary(&response)?,
+    };
+    if !name.is_empty() {
+        names.push(name);
+    }
+    Ok(names)
+}
+
+/// Sends `msg` to the name service, keeping the contract's own error apart from a failure
+/// to reach it.
+fn query_name_service<Q: Querier>(
+    querier: &Q,
+    name_service: &HumanAddr,
+    msg: &NSQueryMsg,
+) -> StdResult<StdResult<Binary>> {
+    let request = to_vec(&QueryRequest::<Empty>::Wasm(WasmQuery::Smart {
+        contract_addr: name_service.clone(),
+        msg: to_binary(msg)?,
+    }))?;
+    match querier.raw_query(&request) {
+        Ok(response) => Ok(response),
+        Err(err) => Err(StdError::generic_err(format!("Querier error: {}", err))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{migrate, try_paidamountis, try_version_history};
+    use crate::state::{
+        contract_version_read, current_version, expiries_read, expiry_key, owner_cfg_read,
+        registrations_read,
+    };
+    use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+    use cosmwasm_std::QuerierResult;
+    use cosmwasm_storage::singleton;
+    use name_service::msg::NameEntry;
+
+    /// Answers the name service queries of `migrate_v0`, where test1 holds two names. The
+    /// first release rejects `NamesByOwner` and answers `ValueIs` with a bare string.
+    struct NameServiceQuerier {
+        first_release: bool,
+    }
+
+    const HELD: &[&str] = &["Test1Name", "Test1Other"];
+
+    impl Querier for NameServiceQuerier {
+        fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+            let request: QueryRequest<cosmwasm_std::Empty> = from_slice(bin_request).unwrap();
+            let msg = match request {
+                QueryRequest::Wasm(WasmQuery::Smart { msg, .. }) => from_slice(msg.as_slice()),
+                _ => panic!("unexpected query"),
+            };
+            match msg.unwrap() {
+                NSQueryMsg::ValueIs { owner } if self.first_release => {
+                    let name = if owner.as_str() == "test1" {
+                        HELD[0]
+                    } else {
+                        ""
+                    };
+                    Ok(to_binary(&name))
+                }
+                NSQueryMsg::NamesByOwner {
+                    owner,
+                    start_after,
+                    limit,
+                } if !self.first_release => {
+                    let held = if owner.as_str() == "test1" { HELD } else { &[] };
+                    let names = held
+                        .iter()
+                        .filter(|name| match &start_after {
+                            Some(after) => **name > after.as_str(),
+                            None => true,
+                        })
+                        .take(limit.unwrap() as usize)
+                        .map(|name| NameEntry {
+                            name: name.to_string(),
+                            owner: owner.clone(),
+                        })
+                        .collect();
+                    Ok(to_binary(&NamesResponse { names }))
+                }
+                _ => Ok(Err(StdError::generic_err("unknown variant"))),
+            }
+        }
+    }
+
+    fn legacy_deps(
+        first_release: bool,
+        payments: &[(&str, u128)],
+    ) -> Extern<MockStorage, MockApi, NameServiceQuerier> {
+        let mut deps = Extern {
+            storage: MockStorage::default(),
+            api: MockApi::new(20),
+            querier: NameServiceQuerier { first_release },
+        };
+        singleton(&mut deps.storage, OWNER_CFG)
+            .save(&LegacyConfig {
+                owner: HumanAddr::from("owner"),
+                name_service_address: HumanAddr::from("nameservice"),
+            })
+            .unwrap();
+        for (payer, paid) in payments {
+            PrefixedStorage::new(PAYMENTS, &mut deps.storage).set(
+                &to_vec(&HumanAddr::from(*payer)).unwrap(),
+                &to_vec(&Uint128(*paid)).unwrap(),
+            );
+        }
+        deps
+    }
+
+    fn pricing() -> MigrateMsg {
+        MigrateMsg {
+            treasury: None,
+            base_price: Some(Uint128(100)),
+            registration_period: Some(1000),
+            grace_period: Some(100),
+            premium: None,
+            sweep_reward: None,
+            referral_share: None,
+            voucher_signer: None,
+            staking: None,
+        }
+    }
+
+    #[test]
+    fn migrates_payments_and_config() {
+        let mut deps = legacy_deps(true, &[("test1", 1000), ("test2", 500)]);
+        let env = mock_env("owner", &[]);
+
+        let mut msg = MigrateMsg {
+            base_price: None,
+            ..pricing()
+        };
+        let res = migrate(&mut deps, env.clone(), msg.clone());
+        assert_eq!(&res.is_err(), &true);
+
+        msg.base_price = Some(Uint128(100));
+        let res = migrate(&mut deps, env.clone(), msg);
+        assert_eq!(&res.is_err(), &false);
+
+        let config = owner_cfg_read(&deps.storage).load().unwrap();
+        assert_eq!(config.name_service_address, HumanAddr::from("nameservice"));
+        assert_eq!(config.treasury, HumanAddr::from("owner"));
+        assert_eq!(config.base_price, Uint128(100));
+        assert_eq!(
+            try_paidamountis(&deps, HumanAddr::from("test2"))
+                .unwrap()
+                .amount,
+            Uint128(500)
+        );
+        let registration = registrations_read(&deps.storage)
+            .load(b"Test1Name")
+            .unwrap();
+        assert_eq!(
+            registration,
+            Registration {
+                owner: HumanAddr::from("test1"),
+                expires: env.block.time + 1000,
+                payer: HumanAddr::from("test1"),
+                paid: Uint128(1000),
+                stake: None,
+            }
+        );
+        let indexed = expiries_read(&deps.storage)
+            .load(&expiry_key(env.block.time + 1000, "Test1Name"))
+            .unwrap();
+        assert_eq!(indexed, "Test1Name");
+        let legacy_key = to_vec(&HumanAddr::from("test1")).unwrap();
+        assert_eq!(
+            ReadonlyPrefixedStorage::new(PAYMENTS, &deps.storage).get(&legacy_key),
+            None
+        );
+        assert_eq!(
+            contract_version_read(&deps.storage).load().unwrap(),
+            current_version()
+        );
+        let history = try_version_history(&deps).unwrap().versions;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].version, current_version().version);
+        assert_eq!(history[0].height, env.block.height);
+    }
+
+    #[test]
+    fn migrates_every_name_of_a_payer() {
+        let mut deps = legacy_deps(false, &[("test1", 1001), ("test2", 500)]);
+        let env = mock_env("owner", &[]);
+        migrate(&mut deps, env.clone(), pricing()).unwrap();
+
+        let expires = env.block.time + 1000;
+        for (name, paid) in HELD.iter().zip(&[501, 500]) {
+            let registration = registrations_read(&deps.storage)
+                .load(name.as_bytes())
+                .unwrap();
+            assert_eq!(registration.owner, HumanAddr::from("test1"));
+            assert_eq!(registration.paid, Uint128(*paid));
+            assert_eq!(registration.expires, expires);
+            let indexed = expiries_read(&deps.storage)
+                .load(&expiry_key(expires, name))
+                .unwrap();
+            assert_eq!(&indexed, name);
+        }
+        assert_eq!(
+            try_paidamountis(&deps, HumanAddr::from("test1"))
+                .unwrap()
+                .amount,
+            Uint128(1001)
+        );
+        let registered = registrations_read(&deps.storage)
+            .range(None, None, Order::Ascending)
+            .count();
+        assert_eq!(registered, 2);
+    }
+}
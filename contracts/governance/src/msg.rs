@@ -1,4 +1,6 @@
-use cosmwasm_std::HumanAddr;
+use crate::state::{Premium, Stake, Staking, Unbonding, VersionEntry};
+use cosmwasm_std::{to_vec, Binary, Decimal, HumanAddr, StdResult, Uint128};
+use name_service::state::NameStatus;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -6,20 +8,385 @@ use serde::{Deserialize, Serialize};
 #[serde(rename_all = "snake_case")]
 pub struct InitMsg {
     pub nameservice_code_id: u64,
+    /// Receives registration premiums; defaults to the instantiator.
+    pub treasury: Option<HumanAddr>,
+    pub base_price: Uint128,
+    pub registration_period: u64,
+    pub grace_period: u64,
+    pub premium: Option<Premium>,
+    /// Paid to whoever sweeps a lapsed name, out of what the name paid.
+    pub sweep_reward: Option<Uint128>,
+    /// Part of each payment, after premiums, credited to the subscriber's referrer. At most
+    /// one; no referrals are paid without it.
+    pub referral_share: Option<Decimal>,
+    /// SEC1 encoded secp256k1 public key that signs vouchers; none are accepted without it.
+    pub voucher_signer: Option<Binary>,
+    /// Lets names be held by staking; only paid subscriptions are accepted without it.
+    pub staking: Option<Staking>,
+}
+
+/// Pricing is required when migrating from the first release, which had none,
+/// and replaces the stored pricing otherwise.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {
+    pub treasury: Option<HumanAddr>,
+    pub base_price: Option<Uint128>,
+    pub registration_period: Option<u64>,
+    pub grace_period: Option<u64>,
+    pub premium: Option<Premium>,
+    pub sweep_reward: Option<Uint128>,
+    pub referral_share: Option<Decimal>,
+    pub voucher_signer: Option<Binary>,
+    pub staking: Option<Staking>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
-    Subscribe { name: String },
-    Unsubscribe { name: String },
+    /// Pays for `periods` registration periods up front, one if omitted. `referrer` earns
+    /// the referral share of the payment. The base price is discounted by `voucher` or,
+    /// without one, by the sender's allowlist entry while its quota lasts.
+    Subscribe {
+        name: String,
+        periods: Option<u64>,
+        referrer: Option<HumanAddr>,
+        voucher: Option<Voucher>,
+    },
+    Unsubscribe {
+        name: String,
+    },
+    /// Owner only: extends the term of `name` by `periods` registration periods, one if
+    /// omitted, at the base price each. Open until the grace period ends; the extension
+    /// runs from the current expiry, or from now once that has passed.
+    Renew {
+        name: String,
+        periods: Option<u64>,
+    },
+    /// Subscribes to all `names` or none of them. One payment covers the batch, which must
+    /// be at least the sum of each name's price as for `Subscribe`; the allowlist discount
+    /// applies to as many names as the quota has left. Data is a `SubscribeBatchResponse`.
+    SubscribeBatch {
+        names: Vec<String>,
+        periods: Option<u64>,
+        referrer: Option<HumanAddr>,
+    },
+    /// Renews all `names` or none of them, as `Renew` would each. One payment covers the
+    /// batch. Data is a `RenewBatchResponse`.
+    RenewBatch {
+        names: Vec<String>,
+        periods: Option<u64>,
+    },
+    /// Unsubscribes from all `names` or none of them, refunding the total in one transfer.
+    /// Data is an `UnsubscribeBatchResponse`.
+    UnsubscribeBatch {
+        names: Vec<String>,
+    },
     Signup {},
+    /// Anyone may deregister up to `limit` names whose grace period is over, oldest first,
+    /// and collect the sweep reward for each.
+    Sweep {
+        limit: Option<u32>,
+    },
+    /// Sends the sender everything it has earned from referrals and not yet claimed.
+    ClaimReferral {},
+    /// Holds `name` for as long as the sent funds stay delegated, instead of paying for
+    /// periods. At least the configured stake must be sent on top of any premium.
+    /// Unsubscribing undelegates the stake, and `ClaimStake` returns it to the sender of
+    /// `Stake` once unbonded, even if the name was transferred since.
+    Stake {
+        name: String,
+    },
+    /// Sends the sender its undelegated stakes whose unbonding period is over, in full. A
+    /// stake undelegated from a slashed validator is its share of what the validator still
+    /// holds; slashes of stakes already unbonding are not visible to the contract.
+    ClaimStake {},
+    /// Anyone may withdraw the staking rewards of every validator holding stake to the
+    /// treasury.
+    WithdrawRewards {},
+    /// Owner only: sets how many discounted subscriptions each address has left. A zero
+    /// quota removes the address from the allowlist.
+    SetAllowlist {
+        entries: Vec<AllowlistEntry>,
+    },
+    /// Owner only: reserves, blocks or reopens names in the name service.
+    SetNameStatus {
+        names: Vec<String>,
+        status: NameStatus,
+    },
+    /// Owner only: assigns a reserved name to `owner` without payment.
+    RegisterReserved {
+        name: String,
+        owner: HumanAddr,
+    },
+    /// Name service only: confirms a registration, finalizing its payment.
+    NameRegistered {
+        name: String,
+        owner: HumanAddr,
+    },
+    /// Name service only: a name moved to `owner` through its NFT interface.
+    NameTransferred {
+        name: String,
+        owner: HumanAddr,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    PaidAmountIs { address: HumanAddr },
-    AddressExists { address: HumanAddr },
+    /// Returns `PaidAmountResponse`; addresses that never paid get zero with `found: false`.
+    PaidAmountIs {
+        address: HumanAddr,
+    },
+    AddressExists {
+        address: HumanAddr,
+    },
     GetNameServiceAddress {},
+    /// Price at `time`, in seconds. Queries carry no block info, so it defaults to the block
+    /// time of the contract's latest transaction.
+    PriceIs {
+        name: String,
+        time: Option<u64>,
+    },
+    /// Term and payment of a subscribed name, as a `SubscriptionInfoResponse`.
+    SubscriptionInfo {
+        name: String,
+    },
+    /// Where the subscription of `name` stands at `time`, as a `SubscriptionStatusResponse`.
+    SubscriptionStatus {
+        name: String,
+        time: u64,
+    },
+    /// What `referrer` has earned from referrals, as a `ReferralResponse`; zero for
+    /// addresses that never referred anyone.
+    ReferralEarnings {
+        referrer: HumanAddr,
+    },
+    /// Referrers and their earnings in ascending address order, starting after `start_after`.
+    ListReferrers {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    /// Payers and their totals in ascending address order, starting after `start_after`.
+    ListPayments {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    /// The allowlist entry of `address` as an `AllowlistEntry`, with a zero quota if it has
+    /// none.
+    Allowance {
+        address: HumanAddr,
+    },
+    /// Allowlisted addresses in ascending order, starting after `start_after`.
+    ListAllowlist {
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    /// Who redeemed the voucher `id`, as a `VoucherResponse`.
+    VoucherStatus {
+        id: String,
+    },
+    /// Stakes `address` has undelegated and not yet claimed, as an `UnbondingResponse`.
+    Unbonding {
+        address: HumanAddr,
+    },
+    /// Versions the contract has run since it started keeping them, oldest first, as a
+    /// `VersionHistoryResponse`.
+    VersionHistory {},
+}
+
+/// A discount signed off-chain by the configured voucher signer, redeemable once.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Voucher {
+    pub id: String,
+    /// Part of the base price waived; one makes the subscription free.
+    pub discount: Decimal,
+    /// Only this address may redeem the voucher; anyone may without it.
+    pub recipient: Option<HumanAddr>,
+    /// Block time in seconds after which the voucher is refused.
+    pub expires: Option<u64>,
+    /// Compact 64 byte signature over the SHA-256 of [`Voucher::message`].
+    pub signature: Binary,
+}
+
+/// What a voucher signer signs, binding the voucher to one governance instance.
+#[derive(Serialize)]
+struct VoucherMessage<'a> {
+    governance: &'a HumanAddr,
+    id: &'a str,
+    discount: Decimal,
+    recipient: &'a Option<HumanAddr>,
+    expires: Option<u64>,
+}
+
+impl Voucher {
+    /// The bytes to sign for redeeming this voucher at `governance`.
+    pub fn message(&self, governance: &HumanAddr) -> StdResult<Vec<u8>> {
+        to_vec(&VoucherMessage {
+            governance,
+            id: &self.id,
+            discount: self.discount,
+            recipient: &self.recipient,
+            expires: self.expires,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowlistEntry {
+    pub address: HumanAddr,
+    /// Discounted subscriptions left.
+    pub quota: u32,
+    /// Part of the base price waived; one makes the subscriptions free.
+    pub discount: Decimal,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AllowlistResponse {
+    pub entries: Vec<AllowlistEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnbondingResponse {
+    pub address: HumanAddr,
+    /// Oldest first.
+    pub entries: Vec<Unbonding>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VoucherResponse {
+    pub id: String,
+    /// Unset while the voucher is unused.
+    pub redeemed_by: Option<HumanAddr>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceResponse {
+    pub base_price: Uint128,
+    pub premium: Uint128,
+    pub total: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionInfoResponse {
+    pub name: String,
+    pub owner: HumanAddr,
+    pub payer: HumanAddr,
+    pub paid: Uint128,
+    pub expires: u64,
+    /// After this time anyone may subscribe to the name again.
+    pub grace_ends: u64,
+    /// Still waiting for the name service to confirm the registration.
+    pub pending: bool,
+    /// What `Unsubscribe` would send back to the owner now.
+    pub refund: Uint128,
+    /// Set on names held by staking, whose `expires` is never reached.
+    pub stake: Option<Stake>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SubscriptionState {
+    /// Waiting for the name service to confirm the registration.
+    Pending,
+    /// Paid for until `paid_until`.
+    Active,
+    /// Past `paid_until`; only the owner keeps the name until `grace_ends`.
+    Grace,
+    /// Past `grace_ends`; anyone may sweep the name or subscribe to it.
+    Lapsed,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscriptionStatusResponse {
+    pub name: String,
+    pub owner: HumanAddr,
+    pub state: SubscriptionState,
+    pub paid_until: u64,
+    pub grace_ends: u64,
+    /// Whole registration periods left before `paid_until`.
+    pub periods_left: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaidAmountResponse {
+    pub amount: Uint128,
+    pub found: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentEntry {
+    pub address: HumanAddr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PaymentsResponse {
+    pub payments: Vec<PaymentEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferralResponse {
+    pub referrer: HumanAddr,
+    /// Subscriptions that named this referrer.
+    pub referrals: u64,
+    pub earned: Uint128,
+    pub claimed: Uint128,
+    /// What `ClaimReferral` would send now.
+    pub unclaimed: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ReferrersResponse {
+    pub referrers: Vec<ReferralResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RenewBatchEntry {
+    pub name: String,
+    /// The share of the payment added to what the name holds.
+    pub paid: Uint128,
+    pub expires: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct RenewBatchResponse {
+    pub results: Vec<RenewBatchEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscribeBatchEntry {
+    pub name: String,
+    /// The share of the payment held for this name, excluding its premium and referral.
+    pub paid: Uint128,
+    pub premium: Uint128,
+    pub expires: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SubscribeBatchResponse {
+    pub results: Vec<SubscribeBatchEntry>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SweepResponse {
+    pub names: Vec<String>,
+    pub reward: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnsubscribeBatchEntry {
+    pub name: String,
+    pub refund: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct UnsubscribeBatchResponse {
+    pub results: Vec<UnsubscribeBatchEntry>,
+}
+
+/// Response to `VersionHistory`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VersionHistoryResponse {
+    pub versions: Vec<VersionEntry>,
 }
@@ -1,15 +1,31 @@
 use cosmwasm_std::{
-    from_slice, to_vec, HumanAddr, ReadonlyStorage, StdError, StdResult, Storage, Uint128,
+    from_slice, to_vec, Binary, Decimal, Env, HumanAddr, Order, ReadonlyStorage, StdError,
+    StdResult, Storage, Uint128,
 };
 use cosmwasm_storage::{
-    singleton, singleton_read, PrefixedStorage, ReadonlyPrefixedStorage, ReadonlySingleton,
-    Singleton,
+    bucket, bucket_read, singleton, singleton_read, Bucket, PrefixedStorage, ReadonlyBucket,
+    ReadonlyPrefixedStorage, ReadonlySingleton, Singleton,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub static PAYMENTS: &[u8] = b"payments";
+pub static PAYMENTS: &[u8] = b"paid_amounts";
 pub static OWNER_CFG: &[u8] = b"owner_cfg";
+pub static CONTRACT_INFO: &[u8] = b"contract_info";
+pub static VERSION_HISTORY: &[u8] = b"version_history";
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub static REGISTRATIONS: &[u8] = b"registrations";
+pub static PENDING: &[u8] = b"pending";
+pub static EXPIRIES: &[u8] = b"expiries";
+pub static LAPSED: &[u8] = b"lapsed";
+pub static REFERRALS: &[u8] = b"referrals";
+pub static ALLOWLIST: &[u8] = b"allowlist";
+pub static VOUCHERS: &[u8] = b"vouchers";
+pub static DELEGATIONS: &[u8] = b"delegations";
+pub static UNBONDING: &[u8] = b"unbonding";
+pub static LAST_SEEN: &[u8] = b"last_seen";
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Money {
@@ -30,39 +46,283 @@ pub struct Name {
 pub struct Config {
     pub owner: HumanAddr, /*instantiator e governance*/
     pub name_service_address: HumanAddr,
+    pub treasury: HumanAddr,
+    pub base_price: Uint128,
+    pub registration_period: u64,
+    pub grace_period: u64,
+    pub premium: Option<Premium>,
+    pub sweep_reward: Option<Uint128>,
+    pub referral_share: Option<Decimal>,
+    pub voucher_signer: Option<Binary>,
+    pub staking: Option<Staking>,
+}
+
+/// Lets names be held by delegating `stake` instead of paying for periods.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Staking {
+    /// Each stake goes to whichever of these holds the least stake so far.
+    pub validators: Vec<HumanAddr>,
+    /// Least amount delegated to hold a name.
+    pub stake: Uint128,
+    /// Seconds before undelegated stakes can be claimed; at least the chain's unbonding
+    /// period.
+    pub unbonding_period: u64,
+}
+
+/// Extra price charged for a name that has just left its grace period. It starts at `start`
+/// and decays to zero over `window` seconds, so the name ends up at the base price.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Premium {
+    pub start: Uint128,
+    pub window: u64,
+    pub decay: Decay,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Decay {
+    Linear,
+    /// Halves every `half_life` seconds, interpolating linearly between halvings.
+    Exponential {
+        half_life: u64,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Registration {
+    pub owner: HumanAddr,
+    pub expires: u64,
+    /// Address whose entry in `PAYMENTS` holds `paid` for this name. For staked names it is
+    /// the staker, who gets the stake back even if the name has changed hands.
+    pub payer: HumanAddr,
+    pub paid: Uint128,
+    /// Set on names held by staking, which do not expire.
+    #[serde(default)]
+    pub stake: Option<Stake>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Stake {
+    pub validator: HumanAddr,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Unbonding {
+    pub amount: Uint128,
+    /// Block time from which the amount can be claimed.
+    pub release: u64,
 }
 
 pub fn owner_cfg_store<S: Storage>(
     storage: &mut S, /*,nsaddress:HumanAddr*/
-) -> Singleton<S, Config> {
+) -> Singleton<'_, S, Config> {
     singleton(storage, OWNER_CFG)
 }
-pub fn owner_cfg_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, Config> {
+pub fn owner_cfg_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, Config> {
     singleton_read(storage, OWNER_CFG)
 }
 
+pub fn registrations_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Registration> {
+    bucket(REGISTRATIONS, storage)
+}
+pub fn registrations_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Registration> {
+    bucket_read(REGISTRATIONS, storage)
+}
+
+/// Registrations sent to the name service and waiting for its `NameRegistered` confirmation.
+pub fn pending_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Registration> {
+    bucket(PENDING, storage)
+}
+pub fn pending_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Registration> {
+    bucket_read(PENDING, storage)
+}
+
+/// Registered names ordered by the end of their term, so sweeping reads the oldest first.
+/// Keys are [`expiry_key`]s and values the names.
+pub fn expiries_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, String> {
+    bucket(EXPIRIES, storage)
+}
+pub fn expiries_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, String> {
+    bucket_read(EXPIRIES, storage)
+}
+
+/// The big-endian `expires` followed by the name, which sorts by expiry.
+pub fn expiry_key(expires: u64, name: &str) -> Vec<u8> {
+    let mut key = expires.to_be_bytes().to_vec();
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// When each swept name left its grace period, so its next subscriber still pays the
+/// premium.
+pub fn lapsed_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, u64> {
+    bucket(LAPSED, storage)
+}
+pub fn lapsed_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, u64> {
+    bucket_read(LAPSED, storage)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct Referral {
+    pub referrals: u64,
+    pub earned: Uint128,
+    pub claimed: Uint128,
+}
+
+/// Referral earnings keyed by the raw bytes of the referrer address, like `PAYMENTS`.
+pub fn referrals_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Referral> {
+    bucket(REFERRALS, storage)
+}
+pub fn referrals_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Referral> {
+    bucket_read(REFERRALS, storage)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Allowance {
+    pub remaining: u32,
+    pub discount: Decimal,
+}
+
+/// Allowlist entries keyed by the raw bytes of the address, like `PAYMENTS`.
+pub fn allowlist_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Allowance> {
+    bucket(ALLOWLIST, storage)
+}
+pub fn allowlist_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Allowance> {
+    bucket_read(ALLOWLIST, storage)
+}
+
+/// Who redeemed each voucher, keyed by its id, so no voucher is redeemed twice.
+pub fn vouchers_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, HumanAddr> {
+    bucket(VOUCHERS, storage)
+}
+pub fn vouchers_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, HumanAddr> {
+    bucket_read(VOUCHERS, storage)
+}
+
+/// Total stake delegated to each validator, keyed by its address.
+pub fn delegations_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Uint128> {
+    bucket(DELEGATIONS, storage)
+}
+pub fn delegations_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Uint128> {
+    bucket_read(DELEGATIONS, storage)
+}
+
+/// Undelegated stakes waiting to be claimed, keyed by the raw bytes of the staker address.
+pub fn unbonding_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, Vec<Unbonding>> {
+    bucket(UNBONDING, storage)
+}
+pub fn unbonding_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, Vec<Unbonding>> {
+    bucket_read(UNBONDING, storage)
+}
+
+/// Block time of the latest transaction, which queries use when no time is given since they
+/// carry no block info themselves.
+pub fn last_seen_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, u64> {
+    singleton(storage, LAST_SEEN)
+}
+pub fn last_seen_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, u64> {
+    singleton_read(storage, LAST_SEEN)
+}
+
+/// Which code wrote the storage, so `migrate` knows the layout it is upgrading from.
+/// The first release predates it and has no entry.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContractVersion {
+    pub contract: String,
+    pub version: String,
+}
+
+pub fn contract_version_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, ContractVersion> {
+    singleton(storage, CONTRACT_INFO)
+}
+pub fn contract_version_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, ContractVersion> {
+    singleton_read(storage, CONTRACT_INFO)
+}
+
+pub fn current_version() -> ContractVersion {
+    ContractVersion {
+        contract: CONTRACT_NAME.to_string(),
+        version: CONTRACT_VERSION.to_string(),
+    }
+}
+
+/// A version the contract ran from the block at `height` on, recorded by `init` and every
+/// `migrate`. The chain does not tell a contract its code id, so the version stands in for it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VersionEntry {
+    pub contract: String,
+    pub version: String,
+    pub height: u64,
+    pub time: u64,
+}
+
+/// Past and current versions, keyed by the big-endian height they took over at.
+pub fn version_history_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, VersionEntry> {
+    bucket(VERSION_HISTORY, storage)
+}
+pub fn version_history_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, VersionEntry> {
+    bucket_read(VERSION_HISTORY, storage)
+}
+
+/// Stores the current version and appends it to the history at the block of `env`.
+pub fn save_version<S: Storage>(storage: &mut S, env: &Env) -> StdResult<ContractVersion> {
+    let version = current_version();
+    contract_version_store(storage).save(&version)?;
+    version_history_store(storage).save(
+        &env.block.height.to_be_bytes(),
+        &VersionEntry {
+            contract: version.contract.clone(),
+            version: version.version.clone(),
+            height: env.block.height,
+            time: env.block.time,
+        },
+    )?;
+    Ok(version)
+}
+
+/// Payments are keyed by the raw bytes of the payer address.
 pub fn payments_store<S: Storage>(
     storage: &mut S,
     key_raw: HumanAddr,
     value_raw: Uint128,
 ) -> StdResult<()> {
-    let key = to_vec(&key_raw)?;
     let value = to_vec(&value_raw)?;
-    PrefixedStorage::new(PAYMENTS, storage).set(&key, &value);
+    PrefixedStorage::new(PAYMENTS, storage).set(key_raw.as_str().as_bytes(), &value);
     Ok(())
 }
 
 pub fn payments_read<S: Storage>(storage: &S, key_raw: HumanAddr) -> StdResult<Uint128> {
-    let key = to_vec(&key_raw)?;
-    let res = ReadonlyPrefixedStorage::new(PAYMENTS, storage).get(&key);
+    let res = ReadonlyPrefixedStorage::new(PAYMENTS, storage).get(key_raw.as_str().as_bytes());
     match res {
         Some(data) => from_slice(&data),
         None => Err(StdError::generic_err("No payment is found")),
     }
 }
 
+/// Up to `limit` payments with keys sorting after `start_after`, in ascending order.
+pub fn payments_range<S: Storage>(
+    storage: &S,
+    start_after: Option<HumanAddr>,
+    limit: usize,
+) -> StdResult<Vec<(HumanAddr, Uint128)>> {
+    let start = start_after.map(|address| {
+        let mut start = address.as_str().as_bytes().to_vec();
+        start.push(0);
+        start
+    });
+    ReadonlyPrefixedStorage::new(PAYMENTS, storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|(key, value)| {
+            let address = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            Ok((HumanAddr(address), from_slice(&value)?))
+        })
+        .collect()
+}
+
 pub fn payments_delete<S: Storage>(storage: &mut S, key_raw: HumanAddr) -> StdResult<()> {
-    let key = to_vec(&key_raw)?;
-    PrefixedStorage::new(PAYMENTS, storage).remove(&key);
+    PrefixedStorage::new(PAYMENTS, storage).remove(key_raw.as_str().as_bytes());
     Ok(())
 }
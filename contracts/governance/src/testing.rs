@@ -0,0 +1,90 @@
+//! A querier for unit tests of contracts that read governance. It runs a real governance
+//! instance in memory on top of a `MockNameService`, so names subscribed here can be looked
+//! up through either contract.
+
+use crate::contract::{handle, init, query};
+use crate::msg::{HandleMsg, InitMsg, QueryMsg};
+use cosmwasm_std::testing::{mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    coins, from_binary, from_slice, Empty, Extern, HumanAddr, Querier, QuerierResult, QueryRequest,
+    StdResult, WasmQuery,
+};
+use name_service::testing::MockNameService;
+
+/// Instantiates governance, and so owns it.
+const ADMIN: &str = "admin";
+
+pub struct MockGovernance<Q: Querier = MockQuerier> {
+    address: HumanAddr,
+    deps: Extern<MockStorage, MockApi, MockQuerier>,
+    name_service: MockNameService<Q>,
+}
+
+impl MockGovernance {
+    pub fn new<U: Into<HumanAddr>>(address: U, name_service: U, msg: InitMsg) -> Self {
+        MockGovernance::with_name_service(address, MockNameService::new(name_service), msg)
+    }
+}
+
+impl<Q: Querier> MockGovernance<Q> {
+    /// Signs governance up with `name_service`, which answers the queries meant for it.
+    pub fn with_name_service<U: Into<HumanAddr>>(
+        address: U,
+        name_service: MockNameService<Q>,
+        msg: InitMsg,
+    ) -> Self {
+        let mut deps = Extern {
+            storage: MockStorage::default(),
+            api: MockApi::new(20),
+            querier: MockQuerier::new(&[]),
+        };
+        init(&mut deps, mock_env(ADMIN, &[]), msg).expect("governance init");
+        let env = mock_env(name_service.address().clone(), &[]);
+        handle(&mut deps, env, HandleMsg::Signup {}).expect("governance signup");
+        MockGovernance {
+            address: address.into(),
+            deps,
+            name_service,
+        }
+    }
+
+    pub fn name_service_mut(&mut self) -> &mut MockNameService<Q> {
+        &mut self.name_service
+    }
+
+    /// Subscribes `owner` to `name` paying `amount` uluna, and registers it in the name
+    /// service as the chain would.
+    pub fn subscribe(&mut self, name: &str, owner: &HumanAddr, amount: u128) -> StdResult<()> {
+        let msg = HandleMsg::Subscribe {
+            name: name.to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        };
+        handle(
+            &mut self.deps,
+            mock_env(owner, &coins(amount, "uluna")),
+            msg,
+        )?;
+        self.name_service.register(name, owner)?;
+        let msg = HandleMsg::NameRegistered {
+            name: name.to_string(),
+            owner: owner.clone(),
+        };
+        let env = mock_env(self.name_service.address().clone(), &[]);
+        handle(&mut self.deps, env, msg).map(|_| ())
+    }
+}
+
+impl<Q: Querier> Querier for MockGovernance<Q> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_slice::<QueryRequest<Empty>>(bin_request) {
+            Ok(QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }))
+                if contract_addr == self.address =>
+            {
+                Ok(from_binary::<QueryMsg>(&msg).and_then(|msg| query(&self.deps, msg)))
+            }
+            _ => self.name_service.raw_query(bin_request),
+        }
+    }
+}
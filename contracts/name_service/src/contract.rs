@@ -1,8 +1,19 @@
-use crate::msg::{HandleMsg, NSInitMsg, QueryMsg};
-use crate::state::{names_read, names_store, nsowner_read, nsowner_store, NSOwner, Name, NamesS};
+use crate::error::ContractError;
+use crate::legacy;
+use crate::msg::{
+    HandleMsg, MigrateMsg, NSHookMsg, NSInitMsg, NameEntry, NameInfoResponse, NameResponse,
+    NamesResponse, OwnerResponse, QueryMsg, VersionHistoryResponse, RESOLVER_KEY,
+};
+use crate::nft::{self, exclusive, DEFAULT_LIMIT, MAX_LIMIT};
+use crate::state::{
+    clear_primary, contract_version_read, count_subdomain, name_status_read, name_status_store,
+    names_read, names_store, nsowner_read, nsowner_store, num_names_store, owned_names_read,
+    owned_names_store, primary_read, primary_store, resolve_store, save_version, subdomains_read,
+    version_history_read, NSOwner, Name, NameRecord, NameStatus, Record, CONTRACT_NAME,
+};
 use cosmwasm_std::{
-    to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr, InitResponse,
-    Querier, StdError, StdResult, Storage, WasmMsg,
+    log, to_binary, Api, Binary, CosmosMsg, Env, Extern, HandleResponse, HumanAddr, InitResponse,
+    MigrateResponse, MigrateResult, Order, Querier, StdError, StdResult, Storage, WasmMsg,
 };
 
 pub fn init<S: Storage, A: Api, Q: Querier>(
@@ -10,43 +21,121 @@ pub fn init<S: Storage, A: Api, Q: Querier>(
     env: Env,
     msg: NSInitMsg,
 ) -> StdResult<InitResponse> {
-    let names_storage = NamesS {
-        names_vector: vec![],
-    };
-    names_store(&mut deps.storage).save(&names_storage).unwrap();
+    save_version(&mut deps.storage, &env)?;
+    num_names_store(&mut deps.storage).save(&0)?;
     let nsowner = NSOwner {
         nameservice_owner: env.message.sender,
+        listener: msg.listener,
+    };
+    nsowner_store(&mut deps.storage).save(&nsowner)?;
+    let messages = match msg.hook {
+        Some(hook) => vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: hook.contract_addr,
+            msg: hook.msg,
+            send: vec![],
+        })],
+        None => vec![],
     };
-    nsowner_store(&mut deps.storage).save(&nsowner).unwrap();
-    let message = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: msg.hook.clone().unwrap().contract_addr,
-        msg: msg.hook.unwrap().msg,
-        send: vec![],
-    });
     let response = InitResponse {
-        messages: vec![message],
+        messages,
         log: vec![],
     };
     Ok(response)
 }
 
+pub fn migrate<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    _msg: MigrateMsg,
+) -> MigrateResult {
+    match contract_version_read(&deps.storage).may_load()? {
+        None => legacy::migrate_v0(&mut deps.storage)?,
+        Some(stored) if stored.contract != CONTRACT_NAME => {
+            return Err(ContractError::CannotMigrate {
+                from: stored.contract,
+                to: CONTRACT_NAME.to_string(),
+            }
+            .into())
+        }
+        Some(_) => index_resolved(&mut deps.storage)?,
+    }
+    let version = save_version(&mut deps.storage, &env)?;
+    Ok(MigrateResponse {
+        messages: vec![],
+        log: vec![log("action", "migrate"), log("version", version.version)],
+        data: None,
+    })
+}
+
+/// Copies `address` records into the raw resolution index, which releases before it
+/// did not keep.
+fn index_resolved<S: Storage>(storage: &mut S) -> StdResult<()> {
+    let mut resolved = vec![];
+    for item in names_read(storage).range(None, None, Order::Ascending) {
+        let (name, record) = item?;
+        if let Some(entry) = record.records.into_iter().find(|r| r.key == RESOLVER_KEY) {
+            resolved.push((
+                String::from_utf8(name).map_err(StdError::invalid_utf8)?,
+                entry,
+            ));
+        }
+    }
+    for (name, entry) in resolved {
+        resolve_store(storage, &name, Some(&entry.value));
+    }
+    Ok(())
+}
+
 pub fn handle<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     msg: HandleMsg,
 ) -> StdResult<HandleResponse> {
-    match msg {
+    let res = match msg {
         HandleMsg::Register {
             name_c: name_component,
-        } => try_register(deps, env, name_component),
+            expires,
+        } => try_register(deps, env, name_component, expires),
         HandleMsg::Deregister {
             name_c: name_component,
         } => try_deregister(deps, env, name_component),
+        HandleMsg::RegisterBatch { names, expires } => {
+            try_register_batch(deps, env, names, expires)
+        }
+        HandleMsg::DeregisterBatch { names } => try_deregister_batch(deps, env, names),
+        HandleMsg::RegisterReserved {
+            name_c: name_component,
+            expires,
+        } => try_register_reserved(deps, env, name_component, expires),
+        HandleMsg::Renew { name, expires } => try_renew(deps, env, name, expires),
+        HandleMsg::SetNameStatus { names, status } => try_set_name_status(deps, env, names, status),
         HandleMsg::TestPurposes {} => test_purposes(),
-    }
+        HandleMsg::SetRecord { name, key, value } => try_set_record(deps, env, name, key, value),
+        HandleMsg::SetPrimary { name } => try_set_primary(deps, env, name),
+        HandleMsg::TransferNft {
+            recipient,
+            token_id,
+        } => nft::handle_transfer_nft(deps, env, recipient, token_id),
+        HandleMsg::SendNft {
+            contract,
+            token_id,
+            msg,
+        } => nft::handle_send_nft(deps, env, contract, token_id, msg),
+        HandleMsg::Approve {
+            spender,
+            token_id,
+            expires,
+        } => nft::handle_approve(deps, env, spender, token_id, expires),
+        HandleMsg::Revoke { spender, token_id } => nft::handle_revoke(deps, env, spender, token_id),
+        HandleMsg::ApproveAll { operator, expires } => {
+            nft::handle_approve_all(deps, env, operator, expires)
+        }
+        HandleMsg::RevokeAll { operator } => nft::handle_revoke_all(deps, env, operator),
+    };
+    Ok(res?)
 }
 
-pub fn test_purposes() -> StdResult<HandleResponse> {
+pub fn test_purposes() -> Result<HandleResponse, ContractError> {
     Ok(Default::default())
 }
 
@@ -54,55 +143,352 @@ pub fn try_register<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
     name_c: Name,
-) -> StdResult<HandleResponse> {
-    if env.message.sender
-        != nsowner_read(&deps.storage)
-            .load()
-            .unwrap()
-            .nameservice_owner
-    {
-        return Err(StdError::generic_err("Access not granted."));
-    }
-    let mut found = false;
-    let names_iter = names_read(&deps.storage).load()?.names_vector;
-    for val in names_iter {
-        if val.value == name_c.value {
-            found = true;
-            break;
+    expires: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_open(deps, &name_c.value)?;
+    store_name(deps, &env, name_c, expires, "register")
+}
+
+/// Registers every name or none of them. The response data is a `NamesResponse` listing
+/// the registered names.
+pub fn try_register_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<Name>,
+    expires: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_batch(&names)?;
+    for name_c in &names {
+        check_open(deps, &name_c.value)?;
+        if names_read(&deps.storage)
+            .may_load(name_c.value.as_bytes())?
+            .is_some()
+        {
+            return Err(ContractError::NameTaken {});
         }
     }
-    if !found {
-        names_store(&mut deps.storage).update(|mut names_s| {
-            names_s.names_vector.push(name_c);
-            Ok(names_s)
-        })?;
+    let mut messages = vec![];
+    let mut results = vec![];
+    for name_c in names {
+        results.push(NameEntry {
+            name: name_c.value.clone(),
+            owner: deps.api.human_address(&name_c.owner)?,
+        });
+        messages.extend(store_name(deps, &env, name_c, expires, "register")?.messages);
     }
-    Ok(HandleResponse::default())
+    let mut log = vec![
+        log("action", "register_batch"),
+        log("name", batch_names(&results)),
+        log("owner", batch_owners(&results)),
+    ];
+    if let Some(expires) = expires {
+        log.push(cosmwasm_std::log("expires", expires));
+    }
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: Some(to_binary(&NamesResponse { names: results })?),
+    })
 }
 
-pub fn try_deregister<S: Storage, A: Api, Q: Querier>(
+/// Rejects an empty batch, or one naming a name twice, before any name is looked at.
+fn check_batch(names: &[Name]) -> Result<(), ContractError> {
+    if names.is_empty() {
+        return Err(ContractError::EmptyBatch {});
+    }
+    for (i, name_c) in names.iter().enumerate() {
+        if names[..i].iter().any(|other| other.value == name_c.value) {
+            return Err(ContractError::DuplicateName {});
+        }
+    }
+    Ok(())
+}
+
+fn check_open<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: &str,
+) -> Result<(), ContractError> {
+    match try_name_status(deps, name.to_string())? {
+        NameStatus::Open => Ok(()),
+        NameStatus::Reserved => Err(ContractError::NameReserved {}),
+        NameStatus::Blocked => Err(ContractError::NameBlocked {}),
+    }
+}
+
+fn batch_names(entries: &[NameEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn batch_owners(entries: &[NameEntry]) -> String {
+    entries
+        .iter()
+        .map(|entry| entry.owner.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub fn try_register_reserved<S: Storage, A: Api, Q: Querier>(
     deps: &mut Extern<S, A, Q>,
     env: Env,
-    name_component: Name,
-) -> StdResult<HandleResponse> {
-    if env.message.sender
-        != nsowner_read(&deps.storage)
-            .load()
-            .unwrap()
-            .nameservice_owner
+    name_c: Name,
+    expires: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if try_name_status(deps, name_c.value.clone())? != NameStatus::Reserved {
+        return Err(ContractError::NameNotReserved {});
+    }
+    store_name(deps, &env, name_c, expires, "register_reserved")
+}
+
+pub fn try_renew<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    expires: Option<u64>,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    let mut record = match names_read(&deps.storage).may_load(name.as_bytes())? {
+        Some(record) => record,
+        None => return Err(ContractError::NameNotFound {}),
+    };
+    record.expires = expires;
+    names_store(&mut deps.storage).save(name.as_bytes(), &record)?;
+    let mut log = vec![log("action", "renew"), log("name", &name)];
+    if let Some(expires) = expires {
+        log.push(cosmwasm_std::log("expires", expires));
+    }
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+fn store_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    name_c: Name,
+    expires: Option<u64>,
+    action: &str,
+) -> Result<HandleResponse, ContractError> {
+    if names_read(&deps.storage)
+        .may_load(name_c.value.as_bytes())?
+        .is_some()
     {
-        return Err(StdError::generic_err("Access not granted"));
-    }
-    let names_iter = names_read(&deps.storage).load()?.names_vector;
-    for (index, val) in names_iter.into_iter().enumerate() {
-        if val == name_component {
-            names_store(&mut deps.storage).update(|mut names_s| {
-                names_s.names_vector.remove(index);
-                Ok(names_s)
-            })?;
+        return Err(ContractError::NameTaken {});
+    }
+    owned_names_store(&mut deps.storage, &name_c.owner).save(name_c.value.as_bytes(), &true)?;
+    names_store(&mut deps.storage).save(
+        name_c.value.as_bytes(),
+        &NameRecord {
+            owner: name_c.owner.clone(),
+            approvals: vec![],
+            records: vec![],
+            expires,
+            registered_at: Some(env.block.time),
+        },
+    )?;
+    num_names_store(&mut deps.storage).update(|count| Ok(count + 1))?;
+    count_subdomain(&mut deps.storage, &name_c.value, true)?;
+
+    let owner = deps.api.human_address(&name_c.owner)?;
+    let mut log = vec![
+        log("action", action),
+        log("name", &name_c.value),
+        log("owner", &owner),
+    ];
+    if let Some(expires) = expires {
+        log.push(cosmwasm_std::log("expires", expires));
+    }
+    let mut messages = vec![];
+    if let Some(listener) = nsowner_read(&deps.storage).load()?.listener {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: listener,
+            msg: to_binary(&NSHookMsg::NameRegistered {
+                name: name_c.value,
+                owner,
+            })?,
+            send: vec![],
+        }));
+    }
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: None,
+    })
+}
+
+pub fn try_set_record<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+    key: String,
+    value: Option<String>,
+) -> Result<HandleResponse, ContractError> {
+    let mut record = nft::load_record(&deps.storage, &name)?;
+    nft::check_can_send(deps, &env, &record)?;
+    record.records.retain(|entry| entry.key != key);
+    let mut log = vec![
+        log("action", "set_record"),
+        log("name", &name),
+        log("key", &key),
+    ];
+    if let Some(value) = &value {
+        log.push(cosmwasm_std::log("value", value));
+    }
+    if key == RESOLVER_KEY {
+        resolve_store(&mut deps.storage, &name, value.as_deref());
+    }
+    if let Some(value) = value {
+        record.records.push(Record { key, value });
+    }
+    names_store(&mut deps.storage).save(name.as_bytes(), &record)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log,
+        data: None,
+    })
+}
+
+pub fn try_set_primary<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name: String,
+) -> Result<HandleResponse, ContractError> {
+    let record = match names_read(&deps.storage).may_load(name.as_bytes())? {
+        Some(record) => record,
+        None => return Err(ContractError::NameNotFound {}),
+    };
+    if deps.api.canonical_address(&env.message.sender)? != record.owner {
+        return Err(ContractError::NotOwner {});
+    }
+    primary_store(&mut deps.storage).save(record.owner.as_slice(), &name)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_primary"),
+            log("name", name),
+            log("owner", env.message.sender),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_set_name_status<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<String>,
+    status: NameStatus,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    for name in &names {
+        if status == NameStatus::Open {
+            name_status_store(&mut deps.storage).remove(name.as_bytes());
+        } else {
+            name_status_store(&mut deps.storage).save(name.as_bytes(), &status)?;
         }
     }
-    Ok(HandleResponse::default())
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "set_name_status"),
+            log("name", names.join(",")),
+            log("status", status.as_str()),
+        ],
+        data: None,
+    })
+}
+
+pub fn try_deregister<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    name_component: Name,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    remove_name(deps, &name_component)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "deregister"),
+            log("name", name_component.value),
+            log("owner", deps.api.human_address(&name_component.owner)?),
+        ],
+        data: None,
+    })
+}
+
+/// Deregisters every name or none of them, with the same response data as `RegisterBatch`.
+pub fn try_deregister_batch<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    names: Vec<Name>,
+) -> Result<HandleResponse, ContractError> {
+    if env.message.sender != nsowner_read(&deps.storage).load()?.nameservice_owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_batch(&names)?;
+    for name_c in &names {
+        check_owned(deps, name_c)?;
+    }
+    let mut results = vec![];
+    for name_c in names {
+        remove_name(deps, &name_c)?;
+        results.push(NameEntry {
+            name: name_c.value,
+            owner: deps.api.human_address(&name_c.owner)?,
+        });
+    }
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "deregister_batch"),
+            log("name", batch_names(&results)),
+        ],
+        data: Some(to_binary(&NamesResponse { names: results })?),
+    })
+}
+
+fn check_owned<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name_c: &Name,
+) -> Result<(), ContractError> {
+    match names_read(&deps.storage).may_load(name_c.value.as_bytes())? {
+        None => Err(ContractError::NameNotFound {}),
+        Some(record) if record.owner != name_c.owner => Err(ContractError::NotOwner {}),
+        Some(_) => Ok(()),
+    }
+}
+
+fn remove_name<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    name_c: &Name,
+) -> Result<(), ContractError> {
+    check_owned(deps, name_c)?;
+    names_store(&mut deps.storage).remove(name_c.value.as_bytes());
+    resolve_store(&mut deps.storage, &name_c.value, None);
+    owned_names_store(&mut deps.storage, &name_c.owner).remove(name_c.value.as_bytes());
+    clear_primary(&mut deps.storage, &name_c.owner, &name_c.value)?;
+    num_names_store(&mut deps.storage).update(|count| Ok(count - 1))?;
+    count_subdomain(&mut deps.storage, &name_c.value, false)?;
+    Ok(())
 }
 
 pub fn query<S: Storage, A: Api, Q: Querier>(
@@ -110,65 +496,207 @@ pub fn query<S: Storage, A: Api, Q: Querier>(
     msg: QueryMsg,
 ) -> StdResult<Binary> {
     match msg {
-        QueryMsg::NameExists { value } => to_binary(&try_nameexists(deps, value)),
+        QueryMsg::NameExists { value } => to_binary(&try_nameexists(deps, value)?),
         QueryMsg::OwnerIs { value } => to_binary(&try_owneris(deps, value)?),
         QueryMsg::ValueIs { owner } => to_binary(&try_valueis(deps, owner)?),
+        QueryMsg::NameStatusIs { value } => to_binary(&try_name_status(deps, value)?),
+        QueryMsg::RecordsOf { value } => to_binary(&try_recordsof(deps, value)?),
+        QueryMsg::NameInfo { name } => to_binary(&try_name_info(deps, name)?),
+        QueryMsg::ListNames { start_after, limit } => {
+            to_binary(&try_list_names(deps, start_after, limit)?)
+        }
+        QueryMsg::NamesByOwner {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&try_names_by_owner(deps, owner, start_after, limit)?),
+        QueryMsg::VersionHistory {} => to_binary(&try_version_history(deps)?),
+        QueryMsg::OwnerOf { token_id } => to_binary(&nft::query_owner_of(deps, token_id)?),
+        QueryMsg::ApprovedForAll {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&nft::query_approved_for_all(
+            deps,
+            owner,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::NumTokens {} => to_binary(&nft::query_num_tokens(deps)?),
+        QueryMsg::ContractInfo {} => to_binary(&nft::query_contract_info()),
+        QueryMsg::NftInfo { token_id } => to_binary(&nft::query_nft_info(deps, token_id)?),
+        QueryMsg::AllNftInfo { token_id } => to_binary(&nft::query_all_nft_info(deps, token_id)?),
+        QueryMsg::Tokens {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&nft::query_tokens(deps, owner, start_after, limit)?),
+        QueryMsg::AllTokens { start_after, limit } => {
+            to_binary(&nft::query_all_tokens(deps, start_after, limit)?)
+        }
     }
 }
 
+pub fn try_recordsof<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    value: String,
+) -> Result<Vec<Record>, ContractError> {
+    Ok(nft::load_record(&deps.storage, &value)?.records)
+}
+
+pub fn try_name_status<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    value: String,
+) -> StdResult<NameStatus> {
+    Ok(name_status_read(&deps.storage)
+        .may_load(value.as_bytes())?
+        .unwrap_or(NameStatus::Open))
+}
+
 pub fn try_nameexists<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     value: String,
 ) -> StdResult<bool> {
-    let mut found = false;
-    let names_iter = names_read(&deps.storage).load().unwrap().names_vector;
-    for val in names_iter {
-        if val.value == value {
-            found = true;
-            break;
-        }
-    }
-    Ok(found)
+    Ok(names_read(&deps.storage)
+        .may_load(value.as_bytes())?
+        .is_some())
 }
 
 pub fn try_owneris<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     value: String,
-) -> StdResult<HumanAddr> {
-    let mut def_h_a = HumanAddr::default();
-    let names_iter = names_read(&deps.storage).load()?.names_vector;
-    for val in names_iter {
-        if val.value == value {
-            def_h_a = deps.api.human_address(&val.owner).unwrap();
-            break;
-        }
+) -> StdResult<OwnerResponse> {
+    match names_read(&deps.storage).may_load(value.as_bytes())? {
+        Some(record) => Ok(OwnerResponse {
+            owner: Some(deps.api.human_address(&record.owner)?),
+            expires: record.expires,
+            records: record.records,
+        }),
+        None => Ok(OwnerResponse {
+            owner: None,
+            expires: None,
+            records: vec![],
+        }),
     }
-    Ok(def_h_a)
 }
 
 pub fn try_valueis<S: Storage, A: Api, Q: Querier>(
     deps: &Extern<S, A, Q>,
     owner: HumanAddr,
-) -> StdResult<String> {
-    let mut def_v = String::default();
-    let names_iter = names_read(&deps.storage).load()?.names_vector;
-    for val in names_iter {
-        if val.owner == deps.api.canonical_address(&owner)? {
-            def_v = val.value;
-            break;
-        }
+) -> StdResult<NameResponse> {
+    let owner = deps.api.canonical_address(&owner)?;
+    if let Some(name) = primary_read(&deps.storage).may_load(owner.as_slice())? {
+        return Ok(NameResponse { name: Some(name) });
     }
-    Ok(def_v)
+    let name = match owned_names_read(&deps.storage, &owner)
+        .range(None, None, Order::Ascending)
+        .next()
+    {
+        Some(item) => Some(String::from_utf8(item?.0).map_err(StdError::invalid_utf8)?),
+        None => None,
+    };
+    Ok(NameResponse { name })
+}
+
+pub fn try_name_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    name: String,
+) -> Result<NameInfoResponse, ContractError> {
+    let record = match names_read(&deps.storage).may_load(name.as_bytes())? {
+        Some(record) => record,
+        None => return Err(ContractError::NameNotFound {}),
+    };
+    let primary = primary_read(&deps.storage)
+        .may_load(record.owner.as_slice())?
+        .as_ref()
+        == Some(&name);
+    let owner = deps.api.human_address(&record.owner)?;
+    let resolves_to = record
+        .records
+        .iter()
+        .find(|entry| entry.key == RESOLVER_KEY)
+        .map(|entry| entry.value.clone());
+    Ok(NameInfoResponse {
+        subdomains: subdomains_read(&deps.storage)
+            .may_load(name.as_bytes())?
+            .unwrap_or(0),
+        name,
+        owner,
+        resolves_to,
+        records: record.records,
+        registered_at: record.registered_at,
+        expires: record.expires,
+        primary,
+    })
+}
+
+pub fn try_list_names<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<NamesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|name| exclusive(name.as_bytes()));
+    let names = names_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (name, record) = item?;
+            Ok(NameEntry {
+                name: String::from_utf8(name).map_err(StdError::invalid_utf8)?,
+                owner: deps.api.human_address(&record.owner)?,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(NamesResponse { names })
+}
+
+pub fn try_names_by_owner<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<NamesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|name| exclusive(name.as_bytes()));
+    let owner_raw = deps.api.canonical_address(&owner)?;
+    let names = owned_names_read(&deps.storage, &owner_raw)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            Ok(NameEntry {
+                name: String::from_utf8(item?.0).map_err(StdError::invalid_utf8)?,
+                owner: owner.clone(),
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(NamesResponse { names })
+}
+
+pub fn try_version_history<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<VersionHistoryResponse> {
+    let versions = version_history_read(&deps.storage)
+        .range(None, None, Order::Ascending)
+        .map(|item| Ok(item?.1))
+        .collect::<StdResult<_>>()?;
+    Ok(VersionHistoryResponse { versions })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::msg::HandleMsg::{Deregister, Register, TestPurposes};
+    use crate::msg::HandleMsg::{
+        Deregister, Register, RegisterReserved, SetNameStatus, TestPurposes,
+    };
     use crate::msg::InitHook;
-    use crate::msg::QueryMsg::{NameExists, OwnerIs, ValueIs};
-    use cosmwasm_std::from_binary;
+    use crate::msg::QueryMsg::{
+        ListNames, NameExists, NameInfo, NameStatusIs, NamesByOwner, OwnerIs, ValueIs,
+        VersionHistory,
+    };
+    use crate::state::{resolve_key, CONTRACT_VERSION};
     use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{from_binary, ReadonlyStorage};
 
     #[test]
     fn proper_initialization() {
@@ -181,6 +709,7 @@ mod tests {
                 contract_addr: env.clone().contract.address,
                 msg: to_binary(&TestPurposes {}).unwrap(),
             }),
+            listener: Some(env.clone().contract.address),
         };
         let res1 = init(&mut deps, env.clone(), msg1);
         assert_eq!(&res1.is_err(), &false);
@@ -188,6 +717,58 @@ mod tests {
         assert_eq!(res1_message, 1);
     }
 
+    #[test]
+    fn initialization_without_hook() {
+        let operator_address = HumanAddr::from("test1");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+
+        let msg1 = NSInitMsg {
+            hook: None,
+            listener: None,
+        };
+        let res1 = init(&mut deps, env.clone(), msg1);
+        assert_eq!(&res1.is_err(), &false);
+        assert_eq!(res1.unwrap().messages.len(), 0);
+
+        let msg2 = Register {
+            name_c: Name {
+                value: "Test1Name".to_string(),
+                owner: deps.api.canonical_address(&operator_address).unwrap(),
+            },
+            expires: None,
+        };
+        let res2 = handle(&mut deps, env, msg2);
+        assert_eq!(&res2.is_err(), &false);
+        assert_eq!(res2.unwrap().messages.len(), 0);
+    }
+
+    #[test]
+    fn hook_contract_is_not_a_listener() {
+        let operator_address = HumanAddr::from("test1");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+        let msg1 = NSInitMsg {
+            hook: Some(InitHook {
+                contract_addr: HumanAddr::from("factory"),
+                msg: to_binary(&TestPurposes {}).unwrap(),
+            }),
+            listener: None,
+        };
+        let res1 = init(&mut deps, env.clone(), msg1).unwrap();
+        assert_eq!(res1.messages.len(), 1);
+
+        let msg2 = Register {
+            name_c: Name {
+                value: "Test1Name".to_string(),
+                owner: deps.api.canonical_address(&operator_address).unwrap(),
+            },
+            expires: None,
+        };
+        let res2 = handle(&mut deps, env, msg2).unwrap();
+        assert_eq!(res2.messages.len(), 0);
+    }
+
     #[test]
     fn proper_registration() {
         let operator_address = HumanAddr::from("test1");
@@ -199,6 +780,7 @@ mod tests {
                 contract_addr: env.clone().contract.address,
                 msg: to_binary(&TestPurposes {}).unwrap(),
             }),
+            listener: Some(env.clone().contract.address),
         };
         let _res1 = init(&mut deps, env.clone(), msg1);
 
@@ -210,32 +792,67 @@ mod tests {
                     .canonical_address(&operator_address.clone())
                     .unwrap(),
             },
+            expires: Some(env.block.time + 1000),
         };
-        let res2 = handle(&mut deps, env.clone(), msg2);
+        let res2 = handle(&mut deps, env.clone(), msg2.clone());
         assert_eq!(&res2.is_err(), &false);
-        let res2_message = res2.unwrap().messages.len();
-        assert_eq!(res2_message, 0);
+        let res2 = res2.unwrap();
+        assert_eq!(
+            res2.log,
+            vec![
+                log("action", "register"),
+                log("name", &test_name),
+                log("owner", &operator_address),
+                log("expires", env.block.time + 1000),
+            ]
+        );
+        let res2_message = res2.messages;
+        assert_eq!(
+            res2_message,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: env.contract.address.clone(),
+                msg: to_binary(&NSHookMsg::NameRegistered {
+                    name: test_name.clone(),
+                    owner: operator_address.clone(),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        let res2_duplicate = handle(&mut deps, env.clone(), msg2);
+        assert_eq!(res2_duplicate, Err(ContractError::NameTaken {}.into()));
 
         let msg3 = NameExists {
             value: test_name.clone(),
         };
         let res3 = query(&deps, msg3).unwrap();
-        let res3_value: StdResult<bool> = from_binary(&res3).unwrap();
-        assert_eq!(true, res3_value.unwrap());
+        let res3_value: bool = from_binary(&res3).unwrap();
+        assert!(res3_value);
 
         let msg4 = OwnerIs {
             value: test_name.clone(),
         };
         let res4 = query(&deps, msg4).unwrap();
-        let res4_value: StdResult<HumanAddr> = from_binary(&res4);
-        assert_eq!(res4_value.unwrap(), operator_address.clone());
+        let res4_value: OwnerResponse = from_binary(&res4).unwrap();
+        assert_eq!(
+            res4_value,
+            OwnerResponse {
+                owner: Some(operator_address.clone()),
+                expires: Some(env.block.time + 1000),
+                records: vec![],
+            }
+        );
+        let res4_missing = try_owneris(&deps, "Unknown".to_string()).unwrap();
+        assert_eq!(res4_missing.owner, None);
 
         let msg5 = ValueIs {
             owner: operator_address,
         };
         let res5 = query(&deps, msg5).unwrap();
-        let res5_value: StdResult<String> = from_binary(&res5);
-        assert_eq!(res5_value.unwrap(), test_name);
+        let res5_value: NameResponse = from_binary(&res5).unwrap();
+        assert_eq!(res5_value.name, Some(test_name));
+        let res5_missing = try_valueis(&deps, HumanAddr::from("test2")).unwrap();
+        assert_eq!(res5_missing.name, None);
     }
 
     #[test]
@@ -249,6 +866,7 @@ mod tests {
                 contract_addr: env.clone().contract.address,
                 msg: to_binary(&TestPurposes {}).unwrap(),
             }),
+            listener: Some(env.clone().contract.address),
         };
         let _res1 = init(&mut deps, env.clone(), msg1);
         let msg2 = Register {
@@ -259,6 +877,7 @@ mod tests {
                     .canonical_address(&operator_address.clone())
                     .unwrap(),
             },
+            expires: None,
         };
         let _res2 = handle(&mut deps, env.clone(), msg2);
 
@@ -273,14 +892,556 @@ mod tests {
         };
         let res3 = handle(&mut deps, env.clone(), msg3);
         assert_eq!(&res3.is_err(), &false);
-        let res3_message = res3.unwrap().messages.len();
-        assert_eq!(res3_message, 0);
+        let res3 = res3.unwrap();
+        assert_eq!(res3.messages.len(), 0);
+        assert_eq!(
+            res3.log,
+            vec![
+                log("action", "deregister"),
+                log("name", &test_name),
+                log("owner", &operator_address),
+            ]
+        );
 
         let msg3 = NameExists {
             value: test_name.clone(),
         };
         let res3 = query(&deps, msg3).unwrap();
-        let res3_value: StdResult<bool> = from_binary(&res3).unwrap();
-        assert_eq!(false, res3_value.unwrap());
+        let res3_value: bool = from_binary(&res3).unwrap();
+        assert!(!res3_value);
+    }
+
+    #[test]
+    fn reserved_and_blocked_names() {
+        let operator_address = HumanAddr::from("test1");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+        let msg1 = NSInitMsg {
+            hook: Some(InitHook {
+                contract_addr: env.clone().contract.address,
+                msg: to_binary(&TestPurposes {}).unwrap(),
+            }),
+            listener: Some(env.clone().contract.address),
+        };
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let owner = deps.api.canonical_address(&operator_address).unwrap();
+
+        let msg2 = SetNameStatus {
+            names: vec!["admin".to_string()],
+            status: NameStatus::Reserved,
+        };
+        let res2 = handle(&mut deps, mock_env("intruder", &[]), msg2.clone());
+        assert_eq!(res2, Err(ContractError::Unauthorized {}.into()));
+        let _res2 = handle(&mut deps, env.clone(), msg2);
+        let msg3 = SetNameStatus {
+            names: vec!["badword".to_string()],
+            status: NameStatus::Blocked,
+        };
+        let _res3 = handle(&mut deps, env.clone(), msg3);
+
+        let res4 = query(
+            &deps,
+            NameStatusIs {
+                value: "admin".to_string(),
+            },
+        )
+        .unwrap();
+        let res4_value: NameStatus = from_binary(&res4).unwrap();
+        assert_eq!(res4_value, NameStatus::Reserved);
+
+        for (value, error) in [
+            ("admin", ContractError::NameReserved {}),
+            ("badword", ContractError::NameBlocked {}),
+        ] {
+            let name_c = Name {
+                value: value.to_string(),
+                owner: owner.clone(),
+            };
+            let res = try_register(&mut deps, env.clone(), name_c, None);
+            assert_eq!(res, Err(error));
+        }
+        let msg5 = RegisterReserved {
+            name_c: Name {
+                value: "badword".to_string(),
+                owner: owner.clone(),
+            },
+            expires: None,
+        };
+        let res5 = handle(&mut deps, env.clone(), msg5);
+        assert_eq!(res5, Err(ContractError::NameNotReserved {}.into()));
+
+        let msg6 = RegisterReserved {
+            name_c: Name {
+                value: "admin".to_string(),
+                owner,
+            },
+            expires: None,
+        };
+        let res6 = handle(&mut deps, env.clone(), msg6);
+        assert_eq!(&res6.is_err(), &false);
+        assert!(try_nameexists(&deps, "admin".to_string()).unwrap());
+    }
+
+    #[test]
+    fn deregistration_requires_the_owner() {
+        let operator_address = HumanAddr::from("test1");
+        let test_name: String = "Test1Name".to_string();
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+        let msg1 = NSInitMsg {
+            hook: Some(InitHook {
+                contract_addr: env.clone().contract.address,
+                msg: to_binary(&TestPurposes {}).unwrap(),
+            }),
+            listener: Some(env.clone().contract.address),
+        };
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        let owner = deps.api.canonical_address(&operator_address).unwrap();
+        let msg2 = Register {
+            name_c: Name {
+                value: test_name.clone(),
+                owner: owner.clone(),
+            },
+            expires: None,
+        };
+        let _res2 = handle(&mut deps, env.clone(), msg2);
+
+        let msg3 = Deregister {
+            name_c: Name {
+                value: "Unknown".to_string(),
+                owner: owner.clone(),
+            },
+        };
+        let res3 = handle(&mut deps, env.clone(), msg3);
+        assert_eq!(res3, Err(ContractError::NameNotFound {}.into()));
+
+        let msg4 = Deregister {
+            name_c: Name {
+                value: test_name.clone(),
+                owner: deps
+                    .api
+                    .canonical_address(&HumanAddr::from("test2"))
+                    .unwrap(),
+            },
+        };
+        let res4 = handle(&mut deps, env, msg4);
+        assert_eq!(res4, Err(ContractError::NotOwner {}.into()));
+        assert!(try_nameexists(&deps, test_name).unwrap());
+    }
+
+    #[test]
+    fn names_are_listed_in_pages() {
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(alice.clone(), &[]);
+        let _res1 = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+
+        for (value, owner) in &[("alpha", &alice), ("beta", &bob), ("gamma", &alice)] {
+            let msg = Register {
+                name_c: Name {
+                    value: value.to_string(),
+                    owner: deps.api.canonical_address(owner).unwrap(),
+                },
+                expires: None,
+            };
+            let _res = handle(&mut deps, env.clone(), msg);
+        }
+
+        let msg2 = ListNames {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res2: NamesResponse = from_binary(&query(&deps, msg2).unwrap()).unwrap();
+        assert_eq!(
+            res2.names,
+            vec![
+                NameEntry {
+                    name: "alpha".to_string(),
+                    owner: alice.clone(),
+                },
+                NameEntry {
+                    name: "beta".to_string(),
+                    owner: bob,
+                },
+            ]
+        );
+
+        let msg3 = ListNames {
+            start_after: Some("beta".to_string()),
+            limit: None,
+        };
+        let res3: NamesResponse = from_binary(&query(&deps, msg3).unwrap()).unwrap();
+        assert_eq!(res3.names.len(), 1);
+        assert_eq!(res3.names[0].name, "gamma");
+
+        let msg4 = NamesByOwner {
+            owner: alice.clone(),
+            start_after: Some("alpha".to_string()),
+            limit: Some(100),
+        };
+        let res4: NamesResponse = from_binary(&query(&deps, msg4).unwrap()).unwrap();
+        assert_eq!(
+            res4.names,
+            vec![NameEntry {
+                name: "gamma".to_string(),
+                owner: alice,
+            }]
+        );
+    }
+
+    #[test]
+    fn name_info_aggregates_the_name() {
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(alice.clone(), &[]);
+        let _res1 = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+
+        for (value, owner) in &[("terra", &alice), ("sub.terra", &bob)] {
+            let msg = Register {
+                name_c: Name {
+                    value: value.to_string(),
+                    owner: deps.api.canonical_address(owner).unwrap(),
+                },
+                expires: Some(env.block.time + 1000),
+            };
+            let _res = handle(&mut deps, env.clone(), msg);
+        }
+        let msg2 = HandleMsg::SetRecord {
+            name: "terra".to_string(),
+            key: RESOLVER_KEY.to_string(),
+            value: Some("terra1abc".to_string()),
+        };
+        let _res2 = handle(&mut deps, env.clone(), msg2);
+
+        let msg3 = NameInfo {
+            name: "terra".to_string(),
+        };
+        let res3: NameInfoResponse = from_binary(&query(&deps, msg3).unwrap()).unwrap();
+        assert_eq!(
+            res3,
+            NameInfoResponse {
+                name: "terra".to_string(),
+                owner: alice,
+                resolves_to: Some("terra1abc".to_string()),
+                records: vec![Record {
+                    key: RESOLVER_KEY.to_string(),
+                    value: "terra1abc".to_string(),
+                }],
+                registered_at: Some(env.block.time),
+                expires: Some(env.block.time + 1000),
+                subdomains: 1,
+                primary: false,
+            }
+        );
+
+        let msg4 = Deregister {
+            name_c: Name {
+                value: "sub.terra".to_string(),
+                owner: deps.api.canonical_address(&bob).unwrap(),
+            },
+        };
+        let _res4 = handle(&mut deps, env, msg4);
+        let res5 = try_name_info(&deps, "terra".to_string()).unwrap();
+        assert_eq!(res5.subdomains, 0);
+        let res6 = try_name_info(&deps, "sub.terra".to_string());
+        assert_eq!(res6, Err(ContractError::NameNotFound {}));
+    }
+
+    #[test]
+    fn renewals_move_the_expiry() {
+        let alice = HumanAddr::from("alice");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("registrar", &[]);
+        let _res1 = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+        let msg = Register {
+            name_c: Name {
+                value: "terra".to_string(),
+                owner: deps.api.canonical_address(&alice).unwrap(),
+            },
+            expires: Some(env.block.time + 1000),
+        };
+        let _res = handle(&mut deps, env.clone(), msg);
+
+        let renew = HandleMsg::Renew {
+            name: "terra".to_string(),
+            expires: Some(env.block.time + 2000),
+        };
+        let res2 = handle(&mut deps, mock_env(alice, &[]), renew.clone());
+        assert_eq!(res2, Err(ContractError::Unauthorized {}.into()));
+        let unknown = HandleMsg::Renew {
+            name: "luna".to_string(),
+            expires: None,
+        };
+        let res3 = handle(&mut deps, env.clone(), unknown);
+        assert_eq!(res3, Err(ContractError::NameNotFound {}.into()));
+
+        let res4 = handle(&mut deps, env.clone(), renew).unwrap();
+        assert_eq!(
+            res4.log,
+            vec![
+                log("action", "renew"),
+                log("name", "terra"),
+                log("expires", env.block.time + 2000),
+            ]
+        );
+        let res5 = try_name_info(&deps, "terra".to_string()).unwrap();
+        assert_eq!(res5.expires, Some(env.block.time + 2000));
+    }
+
+    #[test]
+    fn primary_name_is_chosen_by_the_owner() {
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(alice.clone(), &[]);
+        let _res1 = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+        for value in &["alpha", "beta"] {
+            let msg = Register {
+                name_c: Name {
+                    value: value.to_string(),
+                    owner: deps.api.canonical_address(&alice).unwrap(),
+                },
+                expires: None,
+            };
+            handle(&mut deps, env.clone(), msg).unwrap();
+        }
+        let primary = |deps: &Extern<_, _, _>| try_valueis(deps, alice.clone()).unwrap().name;
+        let is_primary = |deps: &Extern<_, _, _>, name: &str| {
+            try_name_info(deps, name.to_string()).unwrap().primary
+        };
+        assert_eq!(primary(&deps), Some("alpha".to_string()));
+        assert!(!is_primary(&deps, "alpha"));
+
+        let set_primary = |name: &str| HandleMsg::SetPrimary {
+            name: name.to_string(),
+        };
+        let res2 = handle(&mut deps, mock_env(bob.clone(), &[]), set_primary("beta"));
+        assert_eq!(res2, Err(ContractError::NotOwner {}.into()));
+        let res3 = handle(&mut deps, env.clone(), set_primary("gamma"));
+        assert_eq!(res3, Err(ContractError::NameNotFound {}.into()));
+        let res4 = handle(&mut deps, env.clone(), set_primary("beta")).unwrap();
+        assert_eq!(
+            res4.log,
+            vec![
+                log("action", "set_primary"),
+                log("name", "beta"),
+                log("owner", &alice),
+            ]
+        );
+        assert_eq!(primary(&deps), Some("beta".to_string()));
+        assert!(is_primary(&deps, "beta"));
+        assert!(!is_primary(&deps, "alpha"));
+
+        let msg5 = HandleMsg::TransferNft {
+            recipient: bob,
+            token_id: "beta".to_string(),
+        };
+        handle(&mut deps, env, msg5).unwrap();
+        assert_eq!(primary(&deps), Some("alpha".to_string()));
+        assert!(!is_primary(&deps, "beta"));
+    }
+
+    #[test]
+    fn resolution_is_mirrored_at_the_raw_key() {
+        let alice = HumanAddr::from("alice");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(alice.clone(), &[]);
+        let _res = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+        let name_c = Name {
+            value: "terra".to_string(),
+            owner: deps.api.canonical_address(&alice).unwrap(),
+        };
+        let msg = Register {
+            name_c: name_c.clone(),
+            expires: None,
+        };
+        handle(&mut deps, env.clone(), msg).unwrap();
+        let set_record = |key: &str, value: Option<&str>| HandleMsg::SetRecord {
+            name: "terra".to_string(),
+            key: key.to_string(),
+            value: value.map(str::to_string),
+        };
+        let key = resolve_key("terra");
+
+        handle(&mut deps, env.clone(), set_record("avatar", Some("x"))).unwrap();
+        assert_eq!(deps.storage.get(&key), None);
+        handle(
+            &mut deps,
+            env.clone(),
+            set_record(RESOLVER_KEY, Some("terra1abc")),
+        )
+        .unwrap();
+        assert_eq!(deps.storage.get(&key), Some(b"terra1abc".to_vec()));
+        handle(&mut deps, env.clone(), set_record(RESOLVER_KEY, None)).unwrap();
+        assert_eq!(deps.storage.get(&key), None);
+
+        // Names resolved before the index existed are picked up by migrate.
+        handle(
+            &mut deps,
+            env.clone(),
+            set_record(RESOLVER_KEY, Some("terra1def")),
+        )
+        .unwrap();
+        deps.storage.remove(&key);
+        migrate(&mut deps, env.clone(), MigrateMsg {}).unwrap();
+        assert_eq!(deps.storage.get(&key), Some(b"terra1def".to_vec()));
+
+        handle(&mut deps, env, Deregister { name_c }).unwrap();
+        assert_eq!(deps.storage.get(&key), None);
+    }
+
+    #[test]
+    fn migrations_are_kept_in_the_version_history() {
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("test1", &[]);
+        init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        )
+        .unwrap();
+        let mut later = env.clone();
+        later.block.height += 10;
+        later.block.time += 60;
+        migrate(&mut deps, later.clone(), MigrateMsg {}).unwrap();
+
+        let res = query(&deps, VersionHistory {}).unwrap();
+        let history: VersionHistoryResponse = from_binary(&res).unwrap();
+        let versions: Vec<_> = history
+            .versions
+            .iter()
+            .map(|entry| (entry.version.as_str(), entry.height, entry.time))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![
+                (CONTRACT_VERSION, env.block.height, env.block.time),
+                (CONTRACT_VERSION, later.block.height, later.block.time),
+            ]
+        );
+    }
+
+    #[test]
+    fn batches_are_all_or_nothing() {
+        let operator_address = HumanAddr::from("test1");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+        let _res1 = init(
+            &mut deps,
+            env.clone(),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        );
+        let owner = deps.api.canonical_address(&operator_address).unwrap();
+        let name = |value: &str| Name {
+            value: value.to_string(),
+            owner: owner.clone(),
+        };
+
+        let msg2 = SetNameStatus {
+            names: vec!["badword".to_string()],
+            status: NameStatus::Blocked,
+        };
+        let _res2 = handle(&mut deps, env.clone(), msg2);
+
+        let msg3 = HandleMsg::RegisterBatch {
+            names: vec![name("alpha"), name("badword")],
+            expires: None,
+        };
+        let res3 = handle(&mut deps, env.clone(), msg3);
+        assert_eq!(res3, Err(ContractError::NameBlocked {}.into()));
+        let msg4 = HandleMsg::RegisterBatch {
+            names: vec![name("alpha"), name("alpha")],
+            expires: None,
+        };
+        let res4 = handle(&mut deps, env.clone(), msg4);
+        assert_eq!(res4, Err(ContractError::DuplicateName {}.into()));
+        assert!(!try_nameexists(&deps, "alpha".to_string()).unwrap());
+
+        let msg5 = HandleMsg::RegisterBatch {
+            names: vec![name("alpha"), name("beta")],
+            expires: Some(env.block.time + 1000),
+        };
+        let res5 = handle(&mut deps, env.clone(), msg5).unwrap();
+        let entry = |value: &str| NameEntry {
+            name: value.to_string(),
+            owner: operator_address.clone(),
+        };
+        let res5_data: NamesResponse = from_binary(&res5.data.unwrap()).unwrap();
+        assert_eq!(res5_data.names, vec![entry("alpha"), entry("beta")]);
+        assert_eq!(
+            res5.log,
+            vec![
+                log("action", "register_batch"),
+                log("name", "alpha,beta"),
+                log(
+                    "owner",
+                    format!("{},{}", operator_address, operator_address)
+                ),
+                log("expires", env.block.time + 1000),
+            ]
+        );
+
+        let msg6 = HandleMsg::DeregisterBatch {
+            names: vec![name("alpha"), name("gamma")],
+        };
+        let res6 = handle(&mut deps, env.clone(), msg6);
+        assert_eq!(res6, Err(ContractError::NameNotFound {}.into()));
+        let msg6_duplicate = HandleMsg::DeregisterBatch {
+            names: vec![name("alpha"), name("alpha")],
+        };
+        let res6_duplicate = handle(&mut deps, env.clone(), msg6_duplicate);
+        assert_eq!(res6_duplicate, Err(ContractError::DuplicateName {}.into()));
+        assert!(try_nameexists(&deps, "alpha".to_string()).unwrap());
+
+        let msg7 = HandleMsg::DeregisterBatch {
+            names: vec![name("alpha"), name("beta")],
+        };
+        let res7 = handle(&mut deps, env, msg7).unwrap();
+        let res7_data: NamesResponse = from_binary(&res7.data.unwrap()).unwrap();
+        assert_eq!(res7_data.names, vec![entry("alpha"), entry("beta")]);
+        assert!(!try_nameexists(&deps, "beta".to_string()).unwrap());
     }
 }
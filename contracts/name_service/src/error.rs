@@ -0,0 +1,59 @@
+use cosmwasm_std::StdError;
+use snafu::Snafu;
+
+#[derive(Snafu, Debug, PartialEq)]
+#[snafu(visibility = "pub")]
+pub enum ContractError {
+    #[snafu(display("{}", source))]
+    Std { source: StdError },
+
+    #[snafu(display("Unauthorized"))]
+    Unauthorized {},
+
+    #[snafu(display("Name is already taken."))]
+    NameTaken {},
+
+    #[snafu(display("Name not found."))]
+    NameNotFound {},
+
+    #[snafu(display("Not the owner of the name."))]
+    NotOwner {},
+
+    #[snafu(display("Name is reserved."))]
+    NameReserved {},
+
+    #[snafu(display("Name is blocked."))]
+    NameBlocked {},
+
+    #[snafu(display("Name is not reserved."))]
+    NameNotReserved {},
+
+    #[snafu(display("Batch contains no names."))]
+    EmptyBatch {},
+
+    #[snafu(display("Batch contains a name more than once."))]
+    DuplicateName {},
+
+    #[snafu(display("Cannot set approval that is already expired."))]
+    Expired {},
+
+    #[snafu(display("Cannot migrate from {} to {}.", from, to))]
+    CannotMigrate { from: String, to: String },
+}
+
+impl From<StdError> for ContractError {
+    fn from(source: StdError) -> Self {
+        ContractError::Std { source }
+    }
+}
+
+/// The 0.10 entry points only return `StdError`, so other kinds travel as their message.
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std { source } => source,
+            ContractError::Unauthorized {} => StdError::unauthorized(),
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! Typed access to a deployed name service for other contracts: queries go through any
+//! `Querier`, and messages come out as `CosmosMsg`s ready to return from `handle`.
+
+use crate::msg::{HandleMsg, NameResponse, OwnerResponse, QueryMsg, RESOLVER_KEY};
+use crate::state::{resolve_key, Name};
+use cosmwasm_std::{
+    to_binary, to_vec, Binary, Coin, CosmosMsg, Empty, HumanAddr, Querier, QueryRequest, StdError,
+    StdResult, WasmMsg, WasmQuery,
+};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Address of a name service instance.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NameServiceContract(pub HumanAddr);
+
+impl NameServiceContract {
+    pub fn addr(&self) -> HumanAddr {
+        self.0.clone()
+    }
+
+    /// Wraps `msg` in a `WasmMsg::Execute` to this contract.
+    pub fn call(&self, msg: &HandleMsg, send: Vec<Coin>) -> StdResult<CosmosMsg> {
+        Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: self.addr(),
+            msg: to_binary(msg)?,
+            send,
+        }))
+    }
+
+    /// Only the name service owner, governance, may send this.
+    pub fn register_msg(&self, name_c: Name, expires: Option<u64>) -> StdResult<CosmosMsg> {
+        self.call(&HandleMsg::Register { name_c, expires }, vec![])
+    }
+
+    pub fn set_record_msg(
+        &self,
+        name: &str,
+        key: &str,
+        value: Option<String>,
+    ) -> StdResult<CosmosMsg> {
+        let msg = HandleMsg::SetRecord {
+            name: name.to_string(),
+            key: key.to_string(),
+            value,
+        };
+        self.call(&msg, vec![])
+    }
+
+    pub fn query<Q: Querier, T: DeserializeOwned>(
+        &self,
+        querier: &Q,
+        msg: &QueryMsg,
+    ) -> StdResult<T> {
+        querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+            contract_addr: self.addr(),
+            msg: to_binary(msg)?,
+        }))
+    }
+
+    /// The address `name` points to through its `address` record, if registered and set.
+    pub fn resolve<Q: Querier>(&self, querier: &Q, name: &str) -> StdResult<Option<HumanAddr>> {
+        let res = self.owner_is(querier, name)?;
+        let record = res
+            .records
+            .into_iter()
+            .find(|record| record.key == RESOLVER_KEY);
+        Ok(record.map(|record| HumanAddr(record.value)))
+    }
+
+    /// `resolve` without the smart query: reads the record straight from `resolve_key`.
+    pub fn resolve_raw<Q: Querier>(&self, querier: &Q, name: &str) -> StdResult<Option<HumanAddr>> {
+        let request = to_vec(&QueryRequest::<Empty>::Wasm(WasmQuery::Raw {
+            contract_addr: self.addr(),
+            key: Binary(resolve_key(name)),
+        }))?;
+        let value = match querier.raw_query(&request) {
+            Ok(Ok(value)) => value,
+            Ok(Err(StdError::NotFound { .. })) => return Ok(None),
+            Ok(Err(err)) => return Err(err),
+            Err(err) => return Err(StdError::generic_err(format!("Querier error: {}", err))),
+        };
+        decode_resolved(value.as_slice())
+    }
+
+    /// Current owner of `name`, `None` when it is not registered.
+    pub fn owner_of<Q: Querier>(&self, querier: &Q, name: &str) -> StdResult<Option<HumanAddr>> {
+        Ok(self.owner_is(querier, name)?.owner)
+    }
+
+    /// The name `owner` is known by: the one they set with `SetPrimary`, or else the first
+    /// of theirs in ascending order.
+    pub fn primary_name<Q: Querier>(
+        &self,
+        querier: &Q,
+        owner: &HumanAddr,
+    ) -> StdResult<Option<String>> {
+        let res: NameResponse = self.query(
+            querier,
+            &QueryMsg::ValueIs {
+                owner: owner.clone(),
+            },
+        )?;
+        Ok(res.name)
+    }
+
+    fn owner_is<Q: Querier>(&self, querier: &Q, name: &str) -> StdResult<OwnerResponse> {
+        self.query(
+            querier,
+            &QueryMsg::OwnerIs {
+                value: name.to_string(),
+            },
+        )
+    }
+}
+
+/// Reads a value stored at `resolve_key`, where nothing means the name does not resolve.
+pub fn decode_resolved(value: &[u8]) -> StdResult<Option<HumanAddr>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    let address = String::from_utf8(value.to_vec()).map_err(StdError::invalid_utf8)?;
+    Ok(Some(HumanAddr(address)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockNameService;
+    use cosmwasm_std::testing::MockApi;
+    use cosmwasm_std::{from_binary, Api};
+
+    #[test]
+    fn answers_through_the_querier() {
+        let mut querier = MockNameService::new("names");
+        querier
+            .register("alice", &HumanAddr::from("owner"))
+            .unwrap();
+        querier.set_record("alice", RESOLVER_KEY, "wallet").unwrap();
+        let names = NameServiceContract(HumanAddr::from("names"));
+
+        assert_eq!(
+            names.owner_of(&querier, "alice").unwrap(),
+            Some(HumanAddr::from("owner"))
+        );
+        assert_eq!(
+            names.resolve(&querier, "alice").unwrap(),
+            Some(HumanAddr::from("wallet"))
+        );
+        assert_eq!(
+            names
+                .primary_name(&querier, &HumanAddr::from("owner"))
+                .unwrap(),
+            Some("alice".to_string())
+        );
+        assert_eq!(names.owner_of(&querier, "bob").unwrap(), None);
+        assert_eq!(names.resolve(&querier, "bob").unwrap(), None);
+
+        let elsewhere = NameServiceContract(HumanAddr::from("other"));
+        assert!(elsewhere.owner_of(&querier, "alice").is_err());
+    }
+
+    #[test]
+    fn resolves_from_raw_storage() {
+        // The key is part of the interface other contracts build on; do not change it.
+        assert_eq!(resolve_key("alice"), b"\x00\x07resolvealice".to_vec());
+        assert_eq!(
+            resolve_key("sub.terra"),
+            b"\x00\x07resolvesub.terra".to_vec()
+        );
+        assert_eq!(
+            decode_resolved(b"terra1abc").unwrap(),
+            Some(HumanAddr::from("terra1abc"))
+        );
+        assert_eq!(decode_resolved(b"").unwrap(), None);
+        assert!(decode_resolved(&[0xff]).is_err());
+
+        let mut querier = MockNameService::new("names");
+        querier
+            .register("alice", &HumanAddr::from("owner"))
+            .unwrap();
+        let names = NameServiceContract(HumanAddr::from("names"));
+        assert_eq!(names.resolve_raw(&querier, "alice").unwrap(), None);
+        querier.set_record("alice", RESOLVER_KEY, "wallet").unwrap();
+        assert_eq!(
+            names.resolve_raw(&querier, "alice").unwrap(),
+            names.resolve(&querier, "alice").unwrap()
+        );
+        assert_eq!(names.resolve_raw(&querier, "bob").unwrap(), None);
+    }
+
+    #[test]
+    fn builds_messages() {
+        let names = NameServiceContract(HumanAddr::from("names"));
+        let owner = MockApi::new(20)
+            .canonical_address(&HumanAddr::from("owner"))
+            .unwrap();
+        let name_c = Name {
+            value: "alice".to_string(),
+            owner,
+        };
+        match names.register_msg(name_c.clone(), Some(5)).unwrap() {
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr,
+                msg,
+                send,
+            }) => {
+                assert_eq!(contract_addr, HumanAddr::from("names"));
+                assert!(send.is_empty());
+                let msg: HandleMsg = from_binary(&msg).unwrap();
+                assert_eq!(
+                    msg,
+                    HandleMsg::Register {
+                        name_c,
+                        expires: Some(5)
+                    }
+                );
+            }
+            msg => panic!("unexpected message {:?}", msg),
+        }
+    }
+}
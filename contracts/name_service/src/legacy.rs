@@ -0,0 +1,127 @@
+//! Storage layout of the first release, kept so `migrate` can read it.
+
+use crate::state::{
+    self, count_subdomain, nsowner_read, nsowner_store, num_names_store, owned_names_store, Name,
+    NameRecord,
+};
+use cosmwasm_std::{StdResult, Storage};
+use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub static CONFIG_KEY: &[u8] = b"config";
+
+/// Every registered name, held in a single vector.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NamesS {
+    pub names_vector: Vec<Name>,
+}
+
+pub fn names_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, NamesS> {
+    singleton(storage, CONFIG_KEY)
+}
+pub fn names_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, NamesS> {
+    singleton_read(storage, CONFIG_KEY)
+}
+
+/// Moves the names vector into per-name records, and makes the owner, which was always the
+/// governance contract that instantiated us with a hook to itself, the listener. The first
+/// release stored no terms, so the records have no `expires` until governance renews them;
+/// governance keeps the terms it enforces in its own registrations.
+pub fn migrate_v0<S: Storage>(storage: &mut S) -> StdResult<()> {
+    let names = names_read(storage).load()?.names_vector;
+    num_names_store(storage).save(&(names.len() as u64))?;
+    for name in names {
+        owned_names_store(storage, &name.owner).save(name.value.as_bytes(), &true)?;
+        count_subdomain(storage, &name.value, true)?;
+        state::names_store(storage).save(
+            name.value.as_bytes(),
+            &NameRecord {
+                owner: name.owner,
+                approvals: vec![],
+                records: vec![],
+                expires: None,
+                registered_at: None,
+            },
+        )?;
+    }
+    names_store(storage).remove();
+
+    let mut nsowner = nsowner_read(storage).load()?;
+    nsowner.listener = Some(nsowner.nameservice_owner.clone());
+    nsowner_store(storage).save(&nsowner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{handle, migrate, try_name_info, try_nameexists, try_owneris};
+    use crate::msg::{HandleMsg, MigrateMsg};
+    use crate::nft::query_num_tokens;
+    use crate::state::{contract_version_read, current_version, OWNER_KEY};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{Api, HumanAddr};
+
+    #[derive(Serialize, Deserialize)]
+    struct LegacyNSOwner {
+        nameservice_owner: HumanAddr,
+    }
+
+    #[test]
+    fn migrates_names_vector() {
+        let governance = HumanAddr::from("governance");
+        let owner = HumanAddr::from("test1");
+        let mut deps = mock_dependencies(20, &[]);
+        let names_storage = NamesS {
+            names_vector: vec![
+                Name {
+                    value: "Test1Name".to_string(),
+                    owner: deps.api.canonical_address(&owner).unwrap(),
+                },
+                Name {
+                    value: "Test2Name".to_string(),
+                    owner: deps.api.canonical_address(&owner).unwrap(),
+                },
+            ],
+        };
+        names_store(&mut deps.storage).save(&names_storage).unwrap();
+        singleton(&mut deps.storage, OWNER_KEY)
+            .save(&LegacyNSOwner {
+                nameservice_owner: governance.clone(),
+            })
+            .unwrap();
+
+        let res = migrate(&mut deps, mock_env(governance.clone(), &[]), MigrateMsg {});
+        assert_eq!(&res.is_err(), &false);
+
+        assert!(try_nameexists(&deps, "Test2Name".to_string()).unwrap());
+        assert_eq!(
+            try_owneris(&deps, "Test1Name".to_string()).unwrap().owner,
+            Some(owner)
+        );
+        assert_eq!(query_num_tokens(&deps).unwrap().count, 2);
+        assert_eq!(
+            nsowner_read(&deps.storage).load().unwrap().listener,
+            Some(governance.clone())
+        );
+        assert_eq!(names_read(&deps.storage).may_load().unwrap(), None);
+        assert_eq!(
+            contract_version_read(&deps.storage).load().unwrap(),
+            current_version()
+        );
+
+        let res = migrate(&mut deps, mock_env(governance.clone(), &[]), MigrateMsg {});
+        assert_eq!(&res.is_err(), &false);
+        assert_eq!(query_num_tokens(&deps).unwrap().count, 2);
+
+        let info = try_name_info(&deps, "Test1Name".to_string()).unwrap();
+        assert_eq!(info.expires, None);
+        let renew = HandleMsg::Renew {
+            name: "Test1Name".to_string(),
+            expires: Some(5000),
+        };
+        let _res = handle(&mut deps, mock_env(governance, &[]), renew);
+        let info = try_name_info(&deps, "Test1Name".to_string()).unwrap();
+        assert_eq!(info.expires, Some(5000));
+    }
+}
@@ -1,7 +1,13 @@
 pub mod contract;
+pub mod error;
+pub mod helpers;
+pub mod legacy;
 pub mod msg;
+pub mod nft;
 pub mod state;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod testing;
 //mod tests;
 
 #[cfg(target_arch = "wasm32")]
-cosmwasm_std::create_entry_points!(contract);
+cosmwasm_std::create_entry_points_with_migration!(contract);
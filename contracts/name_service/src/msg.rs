@@ -1,5 +1,6 @@
-use crate::state::Name;
+use crate::state::{Name, NameStatus, Record, VersionEntry};
 use cosmwasm_std::{Binary, HumanAddr};
+use cw0::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -12,21 +13,227 @@ pub struct InitHook {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub struct NSInitMsg {
+    /// Executed once when the contract is instantiated.
     pub hook: Option<InitHook>,
+    /// Contract sent an `NSHookMsg` on every registration and NFT transfer, so it must
+    /// handle both variants. Nothing is sent when unset.
+    #[serde(default)]
+    pub listener: Option<HumanAddr>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum HandleMsg {
-    Register { name_c: Name },
-    Deregister { name_c: Name },
+    /// `expires` is the end of the registrar's term, kept for lookups only.
+    Register {
+        name_c: Name,
+        expires: Option<u64>,
+    },
+    Deregister {
+        name_c: Name,
+    },
+    RegisterReserved {
+        name_c: Name,
+        expires: Option<u64>,
+    },
+    /// Registrar only: moves the end of the term of `name` after a renewal.
+    Renew {
+        name: String,
+        expires: Option<u64>,
+    },
+    /// Registers all `names` or fails as a whole; data is a `NamesResponse`.
+    RegisterBatch {
+        names: Vec<Name>,
+        expires: Option<u64>,
+    },
+    /// Deregisters all `names` or fails as a whole; data is a `NamesResponse`.
+    DeregisterBatch {
+        names: Vec<Name>,
+    },
+    SetNameStatus {
+        names: Vec<String>,
+        status: NameStatus,
+    },
     TestPurposes {},
+    /// Sets `key` on a name, or removes it when `value` is `None`.
+    /// Open to the owner, spenders approved for the name and the owner's operators.
+    SetRecord {
+        name: String,
+        key: String,
+        value: Option<String>,
+    },
+    /// Makes `name` the one `ValueIs` answers for its owner, who alone may set it. Cleared
+    /// when the name is transferred or deregistered.
+    SetPrimary {
+        name: String,
+    },
+    // CW721 interface, where each registered name is a token with `token_id` equal to the name
+    TransferNft {
+        recipient: HumanAddr,
+        token_id: String,
+    },
+    SendNft {
+        contract: HumanAddr,
+        token_id: String,
+        msg: Option<Binary>,
+    },
+    Approve {
+        spender: HumanAddr,
+        token_id: String,
+        expires: Option<Expiration>,
+    },
+    Revoke {
+        spender: HumanAddr,
+        token_id: String,
+    },
+    ApproveAll {
+        operator: HumanAddr,
+        expires: Option<Expiration>,
+    },
+    RevokeAll {
+        operator: HumanAddr,
+    },
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
-    NameExists { value: String },
-    OwnerIs { value: String },
-    ValueIs { owner: HumanAddr },
+    NameExists {
+        value: String,
+    },
+    /// Returns `OwnerResponse`, with no owner for unregistered names.
+    OwnerIs {
+        value: String,
+    },
+    /// Returns `NameResponse` with the primary name of `owner`, or else the first name they
+    /// hold, if any.
+    ValueIs {
+        owner: HumanAddr,
+    },
+    NameStatusIs {
+        value: String,
+    },
+    RecordsOf {
+        value: String,
+    },
+    /// Everything known about a registered name, as a `NameInfoResponse`.
+    NameInfo {
+        name: String,
+    },
+    /// Registered names in ascending order, starting after `start_after`.
+    ListNames {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    NamesByOwner {
+        owner: HumanAddr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Versions the contract has run since it started keeping them, oldest first, as a
+    /// `VersionHistoryResponse`.
+    VersionHistory {},
+    // CW721 interface
+    OwnerOf {
+        token_id: String,
+    },
+    ApprovedForAll {
+        owner: HumanAddr,
+        start_after: Option<HumanAddr>,
+        limit: Option<u32>,
+    },
+    NumTokens {},
+    ContractInfo {},
+    NftInfo {
+        token_id: String,
+    },
+    AllNftInfo {
+        token_id: String,
+    },
+    Tokens {
+        owner: HumanAddr,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    AllTokens {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnerResponse {
+    pub owner: Option<HumanAddr>,
+    pub expires: Option<u64>,
+    pub records: Vec<Record>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NameResponse {
+    pub name: Option<String>,
+}
+
+/// Record key holding the address a name resolves to.
+pub const RESOLVER_KEY: &str = "address";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NameInfoResponse {
+    pub name: String,
+    pub owner: HumanAddr,
+    /// Value of the `address` record, if set.
+    pub resolves_to: Option<String>,
+    pub records: Vec<Record>,
+    pub registered_at: Option<u64>,
+    /// End of the term as last reported by the registrar, which remains authoritative.
+    pub expires: Option<u64>,
+    /// Registered names of the form `<label>.<name>`.
+    pub subdomains: u64,
+    /// Whether the owner made this their primary name with `SetPrimary`.
+    pub primary: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NameEntry {
+    pub name: String,
+    pub owner: HumanAddr,
+}
+
+/// Response to `ListNames` and `NamesByOwner`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NamesResponse {
+    pub names: Vec<NameEntry>,
+}
+
+/// Sent to the listener set at init when a name is registered or changes owner through the
+/// NFT interface.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NSHookMsg {
+    NameRegistered { name: String, owner: HumanAddr },
+    NameTransferred { name: String, owner: HumanAddr },
+}
+
+/// Delivered to the receiving contract of `SendNft`, under a `receive_nft` variant.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub struct Cw721ReceiveMsg {
+    pub sender: HumanAddr,
+    pub token_id: String,
+    pub msg: Option<Binary>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiverHandleMsg {
+    ReceiveNft(Cw721ReceiveMsg),
+}
+
+/// Response to `VersionHistory`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VersionHistoryResponse {
+    pub versions: Vec<VersionEntry>,
 }
@@ -0,0 +1,648 @@
+use crate::error::ContractError;
+use crate::msg::{Cw721ReceiveMsg, NSHookMsg, ReceiverHandleMsg};
+use crate::state::{
+    clear_primary, names_read, names_store, nsowner_read, num_names_read, operators_read,
+    operators_store, owned_names_read, owned_names_store, resolve_store, Approval, NameRecord,
+};
+use cosmwasm_std::{
+    log, to_binary, Api, Binary, CanonicalAddr, CosmosMsg, Env, Extern, HandleResponse, HumanAddr,
+    Order, Querier, StdError, StdResult, Storage, WasmMsg,
+};
+use cw0::Expiration;
+use cw721::{
+    AllNftInfoResponse, ApprovedForAllResponse, ContractInfoResponse, NftInfoResponse,
+    NumTokensResponse, OwnerOfResponse, TokensResponse,
+};
+
+pub const CONTRACT_NAME: &str = "Terra Name Service";
+pub const CONTRACT_SYMBOL: &str = "NAME";
+
+pub(crate) const DEFAULT_LIMIT: u32 = 10;
+pub(crate) const MAX_LIMIT: u32 = 30;
+
+/// Loads the record of `name`, reporting an unknown name as `NameNotFound`.
+pub(crate) fn load_record<S: Storage>(
+    storage: &S,
+    name: &str,
+) -> Result<NameRecord, ContractError> {
+    match names_read(storage).may_load(name.as_bytes())? {
+        Some(record) => Ok(record),
+        None => Err(ContractError::NameNotFound {}),
+    }
+}
+
+pub fn handle_transfer_nft<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    recipient: HumanAddr,
+    token_id: String,
+) -> Result<HandleResponse, ContractError> {
+    let messages = transfer_nft(deps, &env, &recipient, &token_id)?;
+    Ok(HandleResponse {
+        messages,
+        log: vec![
+            log("action", "transfer_nft"),
+            log("name", token_id),
+            log("sender", env.message.sender),
+            log("owner", recipient),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_send_nft<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    contract: HumanAddr,
+    token_id: String,
+    msg: Option<Binary>,
+) -> Result<HandleResponse, ContractError> {
+    let mut messages = transfer_nft(deps, &env, &contract, &token_id)?;
+    let log = vec![
+        log("action", "send_nft"),
+        log("name", &token_id),
+        log("sender", &env.message.sender),
+        log("owner", &contract),
+    ];
+    let receive = ReceiverHandleMsg::ReceiveNft(Cw721ReceiveMsg {
+        sender: env.message.sender,
+        token_id,
+        msg,
+    });
+    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: contract,
+        msg: to_binary(&receive)?,
+        send: vec![],
+    }));
+    Ok(HandleResponse {
+        messages,
+        log,
+        data: None,
+    })
+}
+
+/// Moves `token_id` to `recipient` and tells the listener about it. Approvals, records and
+/// the primary name choice belong to the previous owner, so all are cleared, which also
+/// stops the name resolving.
+fn transfer_nft<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: &Env,
+    recipient: &HumanAddr,
+    token_id: &str,
+) -> Result<Vec<CosmosMsg>, ContractError> {
+    let mut record = load_record(&deps.storage, token_id)?;
+    check_can_send(deps, env, &record)?;
+
+    let new_owner = deps.api.canonical_address(recipient)?;
+    owned_names_store(&mut deps.storage, &record.owner).remove(token_id.as_bytes());
+    clear_primary(&mut deps.storage, &record.owner, token_id)?;
+    owned_names_store(&mut deps.storage, &new_owner).save(token_id.as_bytes(), &true)?;
+    record.owner = new_owner;
+    record.approvals = vec![];
+    record.records = vec![];
+    names_store(&mut deps.storage).save(token_id.as_bytes(), &record)?;
+    resolve_store(&mut deps.storage, token_id, None);
+
+    let mut messages = vec![];
+    if let Some(listener) = nsowner_read(&deps.storage).load()?.listener {
+        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: listener,
+            msg: to_binary(&NSHookMsg::NameTransferred {
+                name: token_id.to_string(),
+                owner: recipient.clone(),
+            })?,
+            send: vec![],
+        }));
+    }
+    Ok(messages)
+}
+
+pub fn handle_approve<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    token_id: String,
+    expires: Option<Expiration>,
+) -> Result<HandleResponse, ContractError> {
+    let mut record = load_record(&deps.storage, &token_id)?;
+    check_can_approve(deps, &env, &record)?;
+
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    let spender_raw = deps.api.canonical_address(&spender)?;
+    record
+        .approvals
+        .retain(|approval| approval.spender != spender_raw);
+    record.approvals.push(Approval {
+        spender: spender_raw,
+        expires,
+    });
+    names_store(&mut deps.storage).save(token_id.as_bytes(), &record)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "approve"),
+            log("name", token_id),
+            log("spender", spender),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_revoke<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    spender: HumanAddr,
+    token_id: String,
+) -> Result<HandleResponse, ContractError> {
+    let mut record = load_record(&deps.storage, &token_id)?;
+    check_can_approve(deps, &env, &record)?;
+
+    let spender_raw = deps.api.canonical_address(&spender)?;
+    record
+        .approvals
+        .retain(|approval| approval.spender != spender_raw);
+    names_store(&mut deps.storage).save(token_id.as_bytes(), &record)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "revoke"),
+            log("name", token_id),
+            log("spender", spender),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_approve_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    operator: HumanAddr,
+    expires: Option<Expiration>,
+) -> Result<HandleResponse, ContractError> {
+    let expires = expires.unwrap_or_default();
+    if expires.is_expired(&env.block) {
+        return Err(ContractError::Expired {});
+    }
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let operator_raw = deps.api.canonical_address(&operator)?;
+    operators_store(&mut deps.storage, &owner).save(operator_raw.as_slice(), &expires)?;
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "approve_all"),
+            log("owner", env.message.sender),
+            log("operator", operator),
+        ],
+        data: None,
+    })
+}
+
+pub fn handle_revoke_all<S: Storage, A: Api, Q: Querier>(
+    deps: &mut Extern<S, A, Q>,
+    env: Env,
+    operator: HumanAddr,
+) -> Result<HandleResponse, ContractError> {
+    let owner = deps.api.canonical_address(&env.message.sender)?;
+    let operator_raw = deps.api.canonical_address(&operator)?;
+    operators_store(&mut deps.storage, &owner).remove(operator_raw.as_slice());
+    Ok(HandleResponse {
+        messages: vec![],
+        log: vec![
+            log("action", "revoke_all"),
+            log("owner", env.message.sender),
+            log("operator", operator),
+        ],
+        data: None,
+    })
+}
+
+/// The owner and its unexpired operators may change approvals.
+fn check_can_approve<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    record: &NameRecord,
+) -> Result<(), ContractError> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender == record.owner || is_operator(deps, env, record, &sender)? {
+        return Ok(());
+    }
+    Err(ContractError::Unauthorized {})
+}
+
+/// On top of those who may approve, unexpired spenders of the token may transfer it
+/// and edit its records.
+pub fn check_can_send<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    record: &NameRecord,
+) -> Result<(), ContractError> {
+    let sender = deps.api.canonical_address(&env.message.sender)?;
+    if sender == record.owner
+        || record
+            .approvals
+            .iter()
+            .any(|approval| approval.spender == sender && !approval.expires.is_expired(&env.block))
+        || is_operator(deps, env, record, &sender)?
+    {
+        return Ok(());
+    }
+    Err(ContractError::Unauthorized {})
+}
+
+fn is_operator<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    env: &Env,
+    record: &NameRecord,
+    sender: &CanonicalAddr,
+) -> StdResult<bool> {
+    match operators_read(&deps.storage, &record.owner).may_load(sender.as_slice())? {
+        Some(expires) => Ok(!expires.is_expired(&env.block)),
+        None => Ok(false),
+    }
+}
+
+pub fn query_owner_of<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token_id: String,
+) -> Result<OwnerOfResponse, ContractError> {
+    let record = load_record(&deps.storage, &token_id)?;
+    let approvals = record
+        .approvals
+        .iter()
+        .map(|approval| {
+            Ok(cw721::Approval {
+                spender: deps.api.human_address(&approval.spender)?,
+                expires: approval.expires,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(OwnerOfResponse {
+        owner: deps.api.human_address(&record.owner)?,
+        approvals,
+    })
+}
+
+pub fn query_approved_for_all<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    start_after: Option<HumanAddr>,
+    limit: Option<u32>,
+) -> StdResult<ApprovedForAllResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = match start_after {
+        Some(start_after) => Some(exclusive(
+            deps.api.canonical_address(&start_after)?.as_slice(),
+        )),
+        None => None,
+    };
+    let owner = deps.api.canonical_address(&owner)?;
+    let operators = operators_read(&deps.storage, &owner)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (operator, expires) = item?;
+            Ok(cw721::Approval {
+                spender: deps.api.human_address(&operator.into())?,
+                expires,
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    Ok(ApprovedForAllResponse { operators })
+}
+
+pub fn query_num_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+) -> StdResult<NumTokensResponse> {
+    let count = num_names_read(&deps.storage).load()?;
+    Ok(NumTokensResponse { count })
+}
+
+pub fn query_contract_info() -> ContractInfoResponse {
+    ContractInfoResponse {
+        name: CONTRACT_NAME.to_string(),
+        symbol: CONTRACT_SYMBOL.to_string(),
+    }
+}
+
+pub fn query_nft_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token_id: String,
+) -> Result<NftInfoResponse, ContractError> {
+    load_record(&deps.storage, &token_id)?;
+    Ok(NftInfoResponse {
+        description: format!("Registration of the name \"{}\"", token_id),
+        name: token_id,
+        image: None,
+    })
+}
+
+pub fn query_all_nft_info<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    token_id: String,
+) -> Result<AllNftInfoResponse, ContractError> {
+    Ok(AllNftInfoResponse {
+        access: query_owner_of(deps, token_id.clone())?,
+        info: query_nft_info(deps, token_id)?,
+    })
+}
+
+pub fn query_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    owner: HumanAddr,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|name| exclusive(name.as_bytes()));
+    let owner = deps.api.canonical_address(&owner)?;
+    let tokens = owned_names_read(&deps.storage, &owner)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| String::from_utf8(item?.0).map_err(StdError::invalid_utf8))
+        .collect::<StdResult<_>>()?;
+    Ok(TokensResponse { tokens })
+}
+
+pub fn query_all_tokens<S: Storage, A: Api, Q: Querier>(
+    deps: &Extern<S, A, Q>,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(|name| exclusive(name.as_bytes()));
+    let tokens = names_read(&deps.storage)
+        .range(start.as_deref(), None, Order::Ascending)
+        .take(limit)
+        .map(|item| String::from_utf8(item?.0).map_err(StdError::invalid_utf8))
+        .collect::<StdResult<_>>()?;
+    Ok(TokensResponse { tokens })
+}
+
+/// Smallest key sorting after `key`, to turn a `start_after` into an inclusive range start.
+pub(crate) fn exclusive(key: &[u8]) -> Vec<u8> {
+    let mut start = key.to_vec();
+    start.push(0);
+    start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contract::{handle, init, query, try_owneris, try_recordsof};
+    use crate::msg::HandleMsg::{
+        Approve, ApproveAll, Register, SendNft, SetRecord, TestPurposes, TransferNft,
+    };
+    use crate::msg::QueryMsg::{AllTokens, OwnerOf, Tokens};
+    use crate::msg::{InitHook, NSInitMsg};
+    use crate::state::{resolve_key, Name, Record};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+    use cosmwasm_std::{from_binary, ReadonlyStorage};
+
+    #[test]
+    fn names_are_transferable_tokens() {
+        let operator_address = HumanAddr::from("test1");
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env(operator_address.clone(), &[]);
+        let listener = env.contract.address.clone();
+        let msg1 = NSInitMsg {
+            hook: Some(InitHook {
+                contract_addr: listener.clone(),
+                msg: to_binary(&TestPurposes {}).unwrap(),
+            }),
+            listener: Some(listener.clone()),
+        };
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        for value in &["alpha", "beta"] {
+            let msg = Register {
+                name_c: Name {
+                    value: value.to_string(),
+                    owner: deps.api.canonical_address(&alice).unwrap(),
+                },
+                expires: None,
+            };
+            let _res = handle(&mut deps, env.clone(), msg);
+        }
+
+        let res2 = query(
+            &deps,
+            Tokens {
+                owner: alice.clone(),
+                start_after: None,
+                limit: None,
+            },
+        )
+        .unwrap();
+        let res2_value: TokensResponse = from_binary(&res2).unwrap();
+        assert_eq!(res2_value.tokens, vec!["alpha", "beta"]);
+
+        let msg3 = TransferNft {
+            recipient: bob.clone(),
+            token_id: "alpha".to_string(),
+        };
+        let res3 = handle(&mut deps, mock_env(bob.clone(), &[]), msg3.clone());
+        assert_eq!(res3, Err(ContractError::Unauthorized {}.into()));
+
+        let msg4 = Approve {
+            spender: bob.clone(),
+            token_id: "alpha".to_string(),
+            expires: None,
+        };
+        let _res4 = handle(&mut deps, mock_env(alice.clone(), &[]), msg4);
+        let msg4_record = SetRecord {
+            name: "alpha".to_string(),
+            key: "address".to_string(),
+            value: Some("terra1alice".to_string()),
+        };
+        handle(&mut deps, mock_env(alice.clone(), &[]), msg4_record).unwrap();
+        let res5 = query(
+            &deps,
+            OwnerOf {
+                token_id: "alpha".to_string(),
+            },
+        )
+        .unwrap();
+        let res5_value: OwnerOfResponse = from_binary(&res5).unwrap();
+        assert_eq!(res5_value.owner, alice);
+        assert_eq!(res5_value.approvals[0].spender, bob);
+
+        let res6 = handle(&mut deps, mock_env(bob.clone(), &[]), msg3).unwrap();
+        assert_eq!(
+            res6.messages,
+            vec![CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: listener,
+                msg: to_binary(&NSHookMsg::NameTransferred {
+                    name: "alpha".to_string(),
+                    owner: bob.clone(),
+                })
+                .unwrap(),
+                send: vec![],
+            })]
+        );
+        assert_eq!(
+            res6.log,
+            vec![
+                log("action", "transfer_nft"),
+                log("name", "alpha"),
+                log("sender", &bob),
+                log("owner", &bob),
+            ]
+        );
+        assert_eq!(
+            try_owneris(&deps, "alpha".to_string()).unwrap().owner,
+            Some(bob.clone())
+        );
+        let res7 = query_owner_of(&deps, "alpha".to_string()).unwrap();
+        assert!(res7.approvals.is_empty());
+        // The previous owner's address must not keep receiving for the name.
+        assert_eq!(try_recordsof(&deps, "alpha".to_string()).unwrap(), vec![]);
+        assert_eq!(deps.storage.get(&resolve_key("alpha")), None);
+
+        let _res8 = handle(
+            &mut deps,
+            mock_env(alice.clone(), &[]),
+            ApproveAll {
+                operator: bob.clone(),
+                expires: None,
+            },
+        );
+        let msg9 = SendNft {
+            contract: operator_address.clone(),
+            token_id: "beta".to_string(),
+            msg: None,
+        };
+        let res9 = handle(&mut deps, mock_env(bob.clone(), &[]), msg9).unwrap();
+        assert_eq!(res9.messages.len(), 2);
+        assert_eq!(
+            res9.messages[1],
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: operator_address.clone(),
+                msg: to_binary(&ReceiverHandleMsg::ReceiveNft(Cw721ReceiveMsg {
+                    sender: bob,
+                    token_id: "beta".to_string(),
+                    msg: None,
+                }))
+                .unwrap(),
+                send: vec![],
+            })
+        );
+        assert_eq!(
+            try_owneris(&deps, "beta".to_string()).unwrap().owner,
+            Some(operator_address)
+        );
+
+        let res10 = query(
+            &deps,
+            AllTokens {
+                start_after: Some("alpha".to_string()),
+                limit: None,
+            },
+        )
+        .unwrap();
+        let res10_value: TokensResponse = from_binary(&res10).unwrap();
+        assert_eq!(res10_value.tokens, vec!["beta"]);
+        assert_eq!(query_num_tokens(&deps).unwrap().count, 2);
+    }
+
+    #[test]
+    fn approved_addresses_edit_records() {
+        let alice = HumanAddr::from("alice");
+        let bob = HumanAddr::from("bob");
+        let carol = HumanAddr::from("carol");
+        let mut deps = mock_dependencies(20, &[]);
+        let env = mock_env("test1", &[]);
+        let msg1 = NSInitMsg {
+            hook: Some(InitHook {
+                contract_addr: env.contract.address.clone(),
+                msg: to_binary(&TestPurposes {}).unwrap(),
+            }),
+            listener: Some(env.contract.address.clone()),
+        };
+        let _res1 = init(&mut deps, env.clone(), msg1);
+        for value in &["alpha", "beta"] {
+            let msg = Register {
+                name_c: Name {
+                    value: value.to_string(),
+                    owner: deps.api.canonical_address(&alice).unwrap(),
+                },
+                expires: None,
+            };
+            let _res = handle(&mut deps, env.clone(), msg);
+        }
+        let set_record = |name: &str, value: Option<&str>| SetRecord {
+            name: name.to_string(),
+            key: "address".to_string(),
+            value: value.map(String::from),
+        };
+
+        let res2 = handle(
+            &mut deps,
+            mock_env(bob.clone(), &[]),
+            set_record("alpha", Some("terra1bob")),
+        );
+        assert_eq!(&res2.is_err(), &true);
+
+        let msg3 = Approve {
+            spender: bob.clone(),
+            token_id: "alpha".to_string(),
+            expires: Some(Expiration::AtHeight(env.block.height + 10)),
+        };
+        let _res3 = handle(&mut deps, mock_env(alice.clone(), &[]), msg3);
+        let res4 = handle(
+            &mut deps,
+            mock_env(bob.clone(), &[]),
+            set_record("alpha", Some("terra1bob")),
+        );
+        assert_eq!(&res4.is_err(), &false);
+        let res5 = handle(
+            &mut deps,
+            mock_env(bob.clone(), &[]),
+            set_record("beta", Some("terra1bob")),
+        );
+        assert_eq!(&res5.is_err(), &true);
+
+        let mut later = mock_env(bob, &[]);
+        later.block.height += 10;
+        let res6 = handle(&mut deps, later, set_record("alpha", None));
+        assert_eq!(&res6.is_err(), &true);
+        let records = try_recordsof(&deps, "alpha".to_string()).unwrap();
+        assert_eq!(
+            records,
+            vec![Record {
+                key: "address".to_string(),
+                value: "terra1bob".to_string(),
+            }]
+        );
+
+        let msg7 = ApproveAll {
+            operator: carol.clone(),
+            expires: None,
+        };
+        let _res7 = handle(&mut deps, mock_env(alice.clone(), &[]), msg7);
+        let res8 = handle(
+            &mut deps,
+            mock_env(carol.clone(), &[]),
+            set_record("beta", None),
+        );
+        assert_eq!(&res8.is_err(), &false);
+
+        let res9 = handle(
+            &mut deps,
+            mock_env(carol.clone(), &[]),
+            set_record("gamma", None),
+        );
+        assert_eq!(res9, Err(ContractError::NameNotFound {}.into()));
+        let msg10 = TransferNft {
+            recipient: carol,
+            token_id: "gamma".to_string(),
+        };
+        let res10 = handle(&mut deps, mock_env(alice, &[]), msg10);
+        assert_eq!(res10, Err(ContractError::NameNotFound {}.into()));
+        let res11 = try_recordsof(&deps, "gamma".to_string());
+        assert_eq!(res11, Err(ContractError::NameNotFound {}));
+    }
+}
@@ -1,10 +1,34 @@
-use cosmwasm_std::{CanonicalAddr, HumanAddr, Storage};
-use cosmwasm_storage::{singleton, singleton_read, ReadonlySingleton, Singleton};
+use cosmwasm_std::{CanonicalAddr, Env, HumanAddr, ReadonlyStorage, StdResult, Storage};
+use cosmwasm_storage::{
+    bucket, bucket_read, singleton, singleton_read, to_length_prefixed, Bucket, ReadonlyBucket,
+    ReadonlySingleton, Singleton,
+};
+use cw0::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-pub static CONFIG_KEY: &[u8] = b"config";
 pub static OWNER_KEY: &[u8] = b"owner_key";
+pub static NAME_STATUS_KEY: &[u8] = b"name_status";
+pub static NAMES_KEY: &[u8] = b"names";
+pub static OWNED_NAMES_KEY: &[u8] = b"owned_names";
+pub static OPERATORS_KEY: &[u8] = b"operators";
+pub static NUM_NAMES_KEY: &[u8] = b"num_names";
+pub static SUBDOMAINS_KEY: &[u8] = b"subdomains";
+pub static CONTRACT_INFO_KEY: &[u8] = b"contract_info";
+pub static VERSION_HISTORY_KEY: &[u8] = b"version_history";
+pub static RESOLVE_KEY: &[u8] = b"resolve";
+pub static PRIMARY_KEY: &[u8] = b"primary";
+
+pub const CONTRACT_NAME: &str = env!("CARGO_PKG_NAME");
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Which code wrote the storage, so `migrate` knows the layout it is upgrading from.
+/// The first release predates it and has no entry.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ContractVersion {
+    pub contract: String,
+    pub version: String,
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Name {
@@ -14,22 +38,234 @@ pub struct Name {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct NSOwner {
     pub nameservice_owner: HumanAddr,
+    /// Contract from the init message, told about registrations and ownership changes made
+    /// through the NFT interface.
+    #[serde(default)]
+    pub listener: Option<HumanAddr>,
+}
+
+/// Reserved names can only be assigned through `RegisterReserved`, blocked ones never.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NameStatus {
+    Open,
+    Reserved,
+    Blocked,
+}
+
+impl NameStatus {
+    /// Same spelling as the JSON encoding, for use in logs.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameStatus::Open => "open",
+            NameStatus::Reserved => "reserved",
+            NameStatus::Blocked => "blocked",
+        }
+    }
+}
+
+/// A registered name, which is also the CW721 token with `token_id` equal to the name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct NameRecord {
+    pub owner: CanonicalAddr,
+    pub approvals: Vec<Approval>,
+    pub records: Vec<Record>,
+    /// End of the registrar's term, kept for lookups only: the registrar enforces its own
+    /// terms, and this is unknown for names migrated from the first release until the
+    /// registrar next sends `Renew`.
+    #[serde(default)]
+    pub expires: Option<u64>,
+    /// Block time of the registration, unknown for names migrated from the first release.
+    #[serde(default)]
+    pub registered_at: Option<u64>,
+}
+
+/// A key/value entry attached to a name, such as the address it resolves to.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Record {
+    pub key: String,
+    pub value: String,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
-pub struct NamesS {
-    pub names_vector: Vec<Name>,
+pub struct Approval {
+    pub spender: CanonicalAddr,
+    pub expires: Expiration,
+}
+
+pub fn names_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, NameRecord> {
+    bucket(NAMES_KEY, storage)
+}
+pub fn names_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, NameRecord> {
+    bucket_read(NAMES_KEY, storage)
+}
+
+/// Index of the names held by `owner`, keyed by name.
+pub fn owned_names_store<'a, S: Storage>(
+    storage: &'a mut S,
+    owner: &CanonicalAddr,
+) -> Bucket<'a, S, bool> {
+    Bucket::multilevel(&[OWNED_NAMES_KEY, owner.as_slice()], storage)
+}
+pub fn owned_names_read<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    owner: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, bool> {
+    ReadonlyBucket::multilevel(&[OWNED_NAMES_KEY, owner.as_slice()], storage)
 }
-pub fn names_store<S: Storage>(storage: &mut S) -> Singleton<S, NamesS> {
-    singleton(storage, CONFIG_KEY)
+
+/// Operators allowed to manage every name of `owner`, keyed by operator address.
+pub fn operators_store<'a, S: Storage>(
+    storage: &'a mut S,
+    owner: &CanonicalAddr,
+) -> Bucket<'a, S, Expiration> {
+    Bucket::multilevel(&[OPERATORS_KEY, owner.as_slice()], storage)
+}
+pub fn operators_read<'a, S: ReadonlyStorage>(
+    storage: &'a S,
+    owner: &CanonicalAddr,
+) -> ReadonlyBucket<'a, S, Expiration> {
+    ReadonlyBucket::multilevel(&[OPERATORS_KEY, owner.as_slice()], storage)
+}
+
+pub fn num_names_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, u64> {
+    singleton(storage, NUM_NAMES_KEY)
+}
+pub fn num_names_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, u64> {
+    singleton_read(storage, NUM_NAMES_KEY)
+}
+
+/// Number of registered names directly under a parent, keyed by the parent name.
+pub fn subdomains_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, u64> {
+    bucket(SUBDOMAINS_KEY, storage)
 }
-pub fn names_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, NamesS> {
-    singleton_read(storage, CONFIG_KEY)
+pub fn subdomains_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, u64> {
+    bucket_read(SUBDOMAINS_KEY, storage)
 }
 
-pub fn nsowner_store<S: Storage>(storage: &mut S) -> Singleton<S, NSOwner> {
+/// `"sub.terra"` is a subdomain of `"terra"`; names without a dot have no parent.
+pub fn parent_name(name: &str) -> Option<&str> {
+    match name.find('.') {
+        Some(dot) if dot + 1 < name.len() => Some(&name[dot + 1..]),
+        _ => None,
+    }
+}
+
+/// Keeps the subdomain count of the parent of `name` in step with its registration.
+pub fn count_subdomain<S: Storage>(storage: &mut S, name: &str, registered: bool) -> StdResult<()> {
+    let parent = match parent_name(name) {
+        Some(parent) => parent,
+        None => return Ok(()),
+    };
+    let count = subdomains_read(storage)
+        .may_load(parent.as_bytes())?
+        .unwrap_or(0);
+    match (registered, count) {
+        (true, _) => subdomains_store(storage).save(parent.as_bytes(), &(count + 1)),
+        (false, 0) | (false, 1) => {
+            subdomains_store(storage).remove(parent.as_bytes());
+            Ok(())
+        }
+        (false, _) => subdomains_store(storage).save(parent.as_bytes(), &(count - 1)),
+    }
+}
+
+/// Raw storage key of the address `name` resolves to: the length-prefixed namespace
+/// `resolve` followed by the name, as in a `cosmwasm_storage` bucket. The value stored
+/// there is the `address` record itself in UTF-8, not JSON, so other contracts can read it
+/// with a `WasmQuery::Raw`. Key and value are part of the contract's interface.
+pub fn resolve_key(name: &str) -> Vec<u8> {
+    let mut key = to_length_prefixed(RESOLVE_KEY);
+    key.extend_from_slice(name.as_bytes());
+    key
+}
+
+/// Mirrors the `address` record of `name` at `resolve_key`, removing it with `None`.
+pub fn resolve_store<S: Storage>(storage: &mut S, name: &str, address: Option<&str>) {
+    match address {
+        Some(address) => storage.set(&resolve_key(name), address.as_bytes()),
+        None => storage.remove(&resolve_key(name)),
+    }
+}
+
+/// Name each owner picked with `SetPrimary`, keyed by the owner address.
+pub fn primary_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, String> {
+    bucket(PRIMARY_KEY, storage)
+}
+pub fn primary_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, String> {
+    bucket_read(PRIMARY_KEY, storage)
+}
+
+/// Forgets the primary name of `owner` if it is `name`, which is leaving them.
+pub fn clear_primary<S: Storage>(
+    storage: &mut S,
+    owner: &CanonicalAddr,
+    name: &str,
+) -> StdResult<()> {
+    if primary_read(storage).may_load(owner.as_slice())?.as_deref() == Some(name) {
+        primary_store(storage).remove(owner.as_slice());
+    }
+    Ok(())
+}
+
+pub fn nsowner_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, NSOwner> {
     singleton(storage, OWNER_KEY)
 }
-pub fn nsowner_read<S: Storage>(storage: &S) -> ReadonlySingleton<S, NSOwner> {
+pub fn nsowner_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, NSOwner> {
     singleton_read(storage, OWNER_KEY)
 }
+
+pub fn name_status_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, NameStatus> {
+    bucket(NAME_STATUS_KEY, storage)
+}
+pub fn name_status_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, NameStatus> {
+    bucket_read(NAME_STATUS_KEY, storage)
+}
+
+pub fn contract_version_store<S: Storage>(storage: &mut S) -> Singleton<'_, S, ContractVersion> {
+    singleton(storage, CONTRACT_INFO_KEY)
+}
+pub fn contract_version_read<S: Storage>(storage: &S) -> ReadonlySingleton<'_, S, ContractVersion> {
+    singleton_read(storage, CONTRACT_INFO_KEY)
+}
+
+pub fn current_version() -> ContractVersion {
+    ContractVersion {
+        contract: CONTRACT_NAME.to_string(),
+        version: CONTRACT_VERSION.to_string(),
+    }
+}
+
+/// A version the contract ran from the block at `height` on, recorded by `init` and every
+/// `migrate`. The chain does not tell a contract its code id, so the version stands in for it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VersionEntry {
+    pub contract: String,
+    pub version: String,
+    pub height: u64,
+    pub time: u64,
+}
+
+/// Past and current versions, keyed by the big-endian height they took over at.
+pub fn version_history_store<S: Storage>(storage: &mut S) -> Bucket<'_, S, VersionEntry> {
+    bucket(VERSION_HISTORY_KEY, storage)
+}
+pub fn version_history_read<S: Storage>(storage: &S) -> ReadonlyBucket<'_, S, VersionEntry> {
+    bucket_read(VERSION_HISTORY_KEY, storage)
+}
+
+/// Stores the current version and appends it to the history at the block of `env`.
+pub fn save_version<S: Storage>(storage: &mut S, env: &Env) -> StdResult<ContractVersion> {
+    let version = current_version();
+    contract_version_store(storage).save(&version)?;
+    version_history_store(storage).save(
+        &env.block.height.to_be_bytes(),
+        &VersionEntry {
+            contract: version.contract.clone(),
+            version: version.version.clone(),
+            height: env.block.height,
+            time: env.block.time,
+        },
+    )?;
+    Ok(version)
+}
@@ -0,0 +1,100 @@
+//! A querier for unit tests of contracts that read the name service. It answers smart and
+//! raw queries to one address from a real name service instance kept in memory, and passes
+//! every other request on to the querier it wraps.
+
+use crate::contract::{handle, init, query};
+use crate::msg::{HandleMsg, NSInitMsg, QueryMsg};
+use crate::state::Name;
+use cosmwasm_std::testing::{mock_env, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    from_binary, from_slice, Api, Binary, Empty, Extern, HumanAddr, Querier, QuerierResult,
+    QueryRequest, ReadonlyStorage, StdError, StdResult, WasmQuery,
+};
+
+/// Sender of the registrations made by `register`, which owns the instance.
+const REGISTRAR: &str = "registrar";
+
+pub struct MockNameService<Q: Querier = MockQuerier> {
+    address: HumanAddr,
+    deps: Extern<MockStorage, MockApi, MockQuerier>,
+    base: Q,
+}
+
+impl MockNameService {
+    pub fn new<U: Into<HumanAddr>>(address: U) -> Self {
+        MockNameService::with_base(address, MockQuerier::new(&[]))
+    }
+}
+
+impl<Q: Querier> MockNameService<Q> {
+    pub fn with_base<U: Into<HumanAddr>>(address: U, base: Q) -> Self {
+        let mut deps = Extern {
+            storage: MockStorage::default(),
+            api: MockApi::new(20),
+            querier: MockQuerier::new(&[]),
+        };
+        init(
+            &mut deps,
+            mock_env(REGISTRAR, &[]),
+            NSInitMsg {
+                hook: None,
+                listener: None,
+            },
+        )
+        .expect("name service init");
+        MockNameService {
+            address: address.into(),
+            deps,
+            base,
+        }
+    }
+
+    pub fn address(&self) -> &HumanAddr {
+        &self.address
+    }
+
+    pub fn register(&mut self, name: &str, owner: &HumanAddr) -> StdResult<()> {
+        let name_c = Name {
+            value: name.to_string(),
+            owner: self.deps.api.canonical_address(owner)?,
+        };
+        let msg = HandleMsg::Register {
+            name_c,
+            expires: None,
+        };
+        handle(&mut self.deps, mock_env(REGISTRAR, &[]), msg).map(|_| ())
+    }
+
+    /// Sets `key` on a registered name as its owner.
+    pub fn set_record(&mut self, name: &str, key: &str, value: &str) -> StdResult<()> {
+        let owner = match crate::contract::try_owneris(&self.deps, name.to_string())?.owner {
+            Some(owner) => owner,
+            None => return Err(StdError::not_found(name)),
+        };
+        let msg = HandleMsg::SetRecord {
+            name: name.to_string(),
+            key: key.to_string(),
+            value: Some(value.to_string()),
+        };
+        handle(&mut self.deps, mock_env(owner, &[]), msg).map(|_| ())
+    }
+}
+
+impl<Q: Querier> Querier for MockNameService<Q> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        match from_slice::<QueryRequest<Empty>>(bin_request) {
+            Ok(QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }))
+                if contract_addr == self.address =>
+            {
+                Ok(from_binary::<QueryMsg>(&msg).and_then(|msg| query(&self.deps, msg)))
+            }
+            Ok(QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }))
+                if contract_addr == self.address =>
+            {
+                let value = self.deps.storage.get(key.as_slice()).unwrap_or_default();
+                Ok(Ok(Binary(value)))
+            }
+            _ => self.base.raw_query(bin_request),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Read-only JSON API over an indexed database.
+//!
+//! | path                    | answer                                                  |
+//! | ----------------------- | ------------------------------------------------------- |
+//! | `/status`               | contracts, latest height and counts                     |
+//! | `/names?owner=ADDRESS`  | registered names, all or those of one owner             |
+//! | `/names/NAME`           | owner, records, status and subscription of a name       |
+//! | `/names/NAME/events`    | every log that mentions the name                        |
+//! | `/payments`             | each payer's total, as governance's `ListPayments`      |
+//! | `/payments/ADDRESS`     | one payer's total                                       |
+//! | `/history`              | governance owner actions: name statuses, reservations, migrations |
+
+use crate::db::{Db, Event};
+use crate::error::Error;
+use percent_encoding::percent_decode_str;
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+pub fn serve(db: &Db, address: &str) -> Result<(), Error> {
+    let server = Server::http(address).map_err(|err| Error::Listen {
+        address: address.to_string(),
+        reason: err.to_string(),
+    })?;
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+    for request in server.incoming_requests() {
+        let (status, body) = match request.method() {
+            Method::Get => respond(db, request.url()),
+            _ => (405, json!({ "error": "only GET is supported" })),
+        };
+        let response = Response::from_string(body.to_string())
+            .with_status_code(status)
+            .with_header(content_type.clone());
+        // A client that went away is no reason to stop serving the others.
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+/// Status code and body for a GET of `url`.
+pub fn respond(db: &Db, url: &str) -> (u16, Value) {
+    let (path, query) = match url.split_once('?') {
+        Some((path, query)) => (path, query),
+        None => (url, ""),
+    };
+    let decoded: Vec<String> = path
+        .split('/')
+        .filter(|part| !part.is_empty())
+        .map(|part| percent_decode_str(part).decode_utf8_lossy().into_owned())
+        .collect();
+    let segments: Vec<&str> = decoded.iter().map(String::as_str).collect();
+    let found = match segments.as_slice() {
+        ["status"] => Some(json!({
+            "governance": db.governance,
+            "name_service": db.name_service,
+            "height": db.height,
+            "txs": db.txs.len(),
+            "names": db.names.len(),
+        })),
+        ["names"] => {
+            let owner = param(query, "owner");
+            let names: Vec<_> = db
+                .names
+                .iter()
+                .filter(|(_, entry)| owner.as_ref().is_none_or(|owner| &entry.owner == owner))
+                .map(|(name, _)| name_json(db, name))
+                .collect();
+            Some(json!({ "names": names }))
+        }
+        ["names", name] if db.names.contains_key(*name) => Some(name_json(db, name)),
+        ["names", name, "events"] => {
+            let events: Vec<_> = db.events.iter().filter(|e| e.mentions(name)).collect();
+            Some(json!({ "events": events_json(events) }))
+        }
+        ["payments"] => {
+            let payments: Vec<_> = db
+                .payments
+                .iter()
+                .map(|(address, amount)| json!({ "address": address, "amount": amount }))
+                .collect();
+            Some(json!({ "payments": payments }))
+        }
+        ["payments", address] => Some(json!({
+            "address": address,
+            "amount": db.payments.get(*address).copied().unwrap_or_default(),
+        })),
+        ["history"] => Some(json!({ "history": events_json(db.history().collect()) })),
+        _ => None,
+    };
+    match found {
+        Some(body) => (200, body),
+        None => (404, json!({ "error": format!("nothing at {}", path) })),
+    }
+}
+
+/// The decoded value of `key` in a query string, where `+` stands for a space.
+fn param(query: &str, key: &str) -> Option<String> {
+    form_urlencoded::parse(query.as_bytes())
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+}
+
+fn name_json(db: &Db, name: &str) -> Value {
+    let entry = &db.names[name];
+    json!({
+        "name": name,
+        "owner": entry.owner,
+        "records": entry.records,
+        "registered_height": entry.registered_height,
+        "status": db.statuses.get(name).map_or("open", String::as_str),
+        "subscription": db.subscriptions.get(name),
+    })
+}
+
+fn events_json(events: Vec<&Event>) -> Vec<Value> {
+    events
+        .into_iter()
+        .map(|event| {
+            let attributes: serde_json::Map<_, _> = event
+                .attributes
+                .iter()
+                .map(|attr| (attr.key.clone(), json!(attr.value)))
+                .collect();
+            json!({
+                "height": event.height,
+                "txhash": event.txhash,
+                "contract": event.contract,
+                "attributes": attributes,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+
+    fn db() -> Db {
+        let attributes = json!([
+            {"key": "contract_address", "value": "gov"},
+            {"key": "action", "value": "signup"},
+            {"key": "owner", "value": "names"},
+        ]);
+        let signup = json!({"height": "1", "txhash": "A", "logs": [{"events": [
+            {"type": "from_contract", "attributes": attributes},
+        ]}]});
+        let attributes = json!([
+            {"key": "contract_address", "value": "gov"},
+            {"key": "action", "value": "subscribe"},
+            {"key": "name", "value": "alice"},
+            {"key": "owner", "value": "user"},
+            {"key": "amount", "value": "100"},
+            {"key": "denom", "value": "uluna"},
+            {"key": "premium", "value": "0"},
+            {"key": "expires", "value": "1000"},
+            {"key": "contract_address", "value": "names"},
+            {"key": "action", "value": "register"},
+            {"key": "name", "value": "alice"},
+            {"key": "owner", "value": "user"},
+            {"key": "contract_address", "value": "gov"},
+            {"key": "action", "value": "name_registered"},
+            {"key": "name", "value": "alice"},
+            {"key": "owner", "value": "user"},
+            {"key": "expires", "value": "1000"},
+        ]);
+        let subscribe = json!({"height": "2", "txhash": "B", "logs": [{"events": [
+            {"type": "from_contract", "attributes": attributes},
+        ]}]});
+        let mut db = Db::new("gov".to_string(), None);
+        let logs = serde_json::to_vec(&json!([signup, subscribe])).unwrap();
+        db.index(input::read(&logs).unwrap()).unwrap();
+        db
+    }
+
+    #[test]
+    fn answers_each_route() {
+        let db = db();
+        let (status, body) = respond(&db, "/status");
+        assert_eq!(status, 200);
+        assert_eq!(body["name_service"], json!("names"));
+        assert_eq!(body["height"], json!(2));
+
+        let (_, body) = respond(&db, "/names/alice");
+        assert_eq!(body["owner"], json!("user"));
+        assert_eq!(body["status"], json!("open"));
+        assert_eq!(body["subscription"]["paid"], json!("100"));
+        let (_, body) = respond(&db, "/names?owner=user");
+        assert_eq!(body["names"][0]["name"], json!("alice"));
+        let (_, body) = respond(&db, "/names?owner=other");
+        assert_eq!(body["names"], json!([]));
+        let (_, body) = respond(&db, "/names?owner=%75ser");
+        assert_eq!(body["names"][0]["name"], json!("alice"));
+        let (status, body) = respond(&db, "/names/%61lice");
+        assert_eq!(status, 200);
+        assert_eq!(body["name"], json!("alice"));
+
+        let (_, body) = respond(&db, "/names/alice/events");
+        let events = body["events"].as_array().unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1]["attributes"]["action"], json!("register"));
+
+        let (_, body) = respond(&db, "/payments");
+        assert_eq!(
+            body["payments"],
+            json!([{"address": "user", "amount": "100"}])
+        );
+        let (_, body) = respond(&db, "/payments/nobody");
+        assert_eq!(body["amount"], json!("0"));
+        let (_, body) = respond(&db, "/history");
+        assert_eq!(body["history"], json!([]));
+
+        assert_eq!(respond(&db, "/names/bob").0, 404);
+        assert_eq!(respond(&db, "/unknown").0, 404);
+    }
+}
@@ -0,0 +1,839 @@
+//! State rebuilt from contract logs. Name service logs give the names, owners and records;
+//! governance logs give the subscriptions and payments, kept the way governance keeps them.
+
+use crate::error::{Error, Io};
+use crate::input::{Attribute, ContractLog};
+use cosmwasm_std::Uint128;
+use serde::{Deserialize, Serialize};
+use snafu::ResultExt;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Db {
+    pub governance: String,
+    /// Learned from governance's `signup` unless given up front.
+    pub name_service: Option<String>,
+    /// Height of the latest indexed transaction.
+    pub height: u64,
+    /// Indexed transaction hashes, so indexing the same logs twice changes nothing.
+    pub txs: BTreeSet<String>,
+    pub names: BTreeMap<String, NameEntry>,
+    /// Reserved and blocked names; all others are open.
+    pub statuses: BTreeMap<String, String>,
+    pub subscriptions: BTreeMap<String, Subscription>,
+    /// Subscriptions the name service has not confirmed yet.
+    pub pending: BTreeMap<String, Subscription>,
+    pub payments: BTreeMap<String, Uint128>,
+    /// Referral earnings by referrer, as governance's `ListReferrers`.
+    #[serde(default)]
+    pub referrals: BTreeMap<String, Referral>,
+    /// Undelegated stakes not yet claimed, by staker, as summed from governance's `Unbonding`.
+    #[serde(default)]
+    pub unbonding: BTreeMap<String, Uint128>,
+    /// Every log of either contract, oldest first.
+    pub events: Vec<Event>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct NameEntry {
+    pub owner: String,
+    pub records: BTreeMap<String, String>,
+    pub registered_height: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Subscription {
+    pub owner: String,
+    pub payer: String,
+    pub paid: Uint128,
+    pub expires: u64,
+    /// Set on names held by staking.
+    #[serde(default)]
+    pub stake: Option<Stake>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Stake {
+    pub validator: String,
+    pub amount: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Referral {
+    pub referrals: u64,
+    pub earned: Uint128,
+    pub claimed: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    pub height: u64,
+    pub txhash: String,
+    pub contract: String,
+    pub action: String,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Event {
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|attr| attr.key == key)
+            .map(|attr| attr.value.as_str())
+    }
+
+    /// Whether `name` is the event's name or one of a batch.
+    pub fn mentions(&self, name: &str) -> bool {
+        self.get("name")
+            .is_some_and(|names| names.split(',').any(|entry| entry == name))
+    }
+}
+
+/// Governance actions only its owner may take, which make up its history.
+const OWNER_ACTIONS: &[&str] = &["set_name_status", "register_reserved", "migrate"];
+
+impl Db {
+    pub fn new(governance: String, name_service: Option<String>) -> Self {
+        Db {
+            governance,
+            name_service,
+            ..Db::default()
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Db>, Error> {
+        match fs::read(path) {
+            Ok(contents) => Ok(Some(serde_json::from_slice(&contents)?)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(source) => Err(Error::Io {
+                path: path.to_path_buf(),
+                source,
+            }),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, serde_json::to_vec(self)?).context(Io { path })
+    }
+
+    /// Applies the logs of transactions not indexed yet, in order.
+    pub fn index(&mut self, logs: Vec<ContractLog>) -> Result<(), Error> {
+        let indexed = self.txs.clone();
+        for log in logs {
+            if indexed.contains(&log.txhash) {
+                continue;
+            }
+            self.height = self.height.max(log.height);
+            self.txs.insert(log.txhash.clone());
+            self.apply(log)?;
+        }
+        Ok(())
+    }
+
+    /// Owner actions on governance, oldest first.
+    pub fn history(&self) -> impl Iterator<Item = &Event> {
+        self.events.iter().filter(move |event| {
+            event.contract == self.governance && OWNER_ACTIONS.contains(&event.action.as_str())
+        })
+    }
+
+    fn apply(&mut self, log: ContractLog) -> Result<(), Error> {
+        let governance = log.contract == self.governance;
+        if !governance && self.name_service.as_ref() != Some(&log.contract) {
+            return Ok(());
+        }
+        let action = match log.attributes.iter().find(|attr| attr.key == "action") {
+            Some(attr) => attr.value.clone(),
+            None => return Ok(()),
+        };
+        let event = Event {
+            height: log.height,
+            txhash: log.txhash,
+            contract: log.contract,
+            action,
+            attributes: log.attributes,
+        };
+        if governance {
+            self.apply_governance(&event)?;
+        } else {
+            self.apply_name_service(&event)?;
+        }
+        self.events.push(event);
+        Ok(())
+    }
+
+    fn apply_governance(&mut self, event: &Event) -> Result<(), Error> {
+        match event.action.as_str() {
+            "signup" if self.name_service.is_none() => {
+                self.name_service = Some(attr(event, "owner")?.to_string());
+            }
+            "subscribe" => {
+                let name = attr(event, "name")?;
+                let owner = attr(event, "owner")?;
+                let paid = paid(event)?;
+                self.subscribe(name, owner, paid, number(event, "expires")?);
+                self.refer(event, 1)?;
+            }
+            "subscribe_batch" => {
+                let names: Vec<_> = attr(event, "name")?.split(',').collect();
+                let paid: Vec<_> = attr(event, "paid")?.split(',').collect();
+                if paid.len() != names.len() {
+                    return Err(invalid(event, "paid"));
+                }
+                let owner = attr(event, "owner")?;
+                let expires = number(event, "expires")?;
+                for (name, paid) in names.iter().zip(paid) {
+                    let paid = paid.parse().map_err(|_| invalid(event, "paid"))?;
+                    self.subscribe(name, owner, paid, expires);
+                }
+                self.refer(event, names.len() as u64)?;
+            }
+            "renew" => {
+                let owner = attr(event, "owner")?;
+                let paid = amount(event, "amount")?;
+                self.renew(attr(event, "name")?, owner, paid, number(event, "expires")?);
+            }
+            "renew_batch" => {
+                let names: Vec<_> = attr(event, "name")?.split(',').collect();
+                let paid: Vec<_> = attr(event, "paid")?.split(',').collect();
+                let expires: Vec<_> = attr(event, "expires")?.split(',').collect();
+                if paid.len() != names.len() {
+                    return Err(invalid(event, "paid"));
+                }
+                if expires.len() != names.len() {
+                    return Err(invalid(event, "expires"));
+                }
+                let owner = attr(event, "owner")?;
+                for ((name, paid), expires) in names.iter().zip(paid).zip(expires) {
+                    let paid = paid.parse().map_err(|_| invalid(event, "paid"))?;
+                    let expires = expires.parse().map_err(|_| invalid(event, "expires"))?;
+                    self.renew(name, owner, paid, expires);
+                }
+            }
+            // Staked names never expire.
+            "stake" => {
+                let name = attr(event, "name")?;
+                self.subscribe(name, attr(event, "owner")?, 0, u64::MAX);
+                let stake = Stake {
+                    validator: attr(event, "validator")?.to_string(),
+                    amount: Uint128(amount(event, "stake")?),
+                };
+                if let Some(subscription) = self.pending.get_mut(name) {
+                    subscription.stake = Some(stake);
+                }
+            }
+            "claim_stake" => {
+                let owner = attr(event, "owner")?;
+                let waiting = self.unbonding.get(owner).map_or(0, |total| total.u128());
+                match waiting.saturating_sub(amount(event, "amount")?) {
+                    0 => self.unbonding.remove(owner),
+                    waiting => self.unbonding.insert(owner.to_string(), Uint128(waiting)),
+                };
+            }
+            "register_reserved" => {
+                let name = attr(event, "name")?;
+                self.subscribe(name, attr(event, "owner")?, 0, number(event, "expires")?);
+            }
+            "name_registered" => {
+                if let Some(subscription) = self.pending.remove(attr(event, "name")?) {
+                    self.add_payment(&subscription.payer, subscription.paid.u128(), true);
+                    self.subscriptions
+                        .insert(attr(event, "name")?.to_string(), subscription);
+                }
+            }
+            "name_transferred" => {
+                if let Some(subscription) = self.subscriptions.get_mut(attr(event, "name")?) {
+                    subscription.owner = attr(event, "owner")?.to_string();
+                }
+            }
+            "claim_referral" => {
+                let referral = self
+                    .referrals
+                    .entry(attr(event, "referrer")?.to_string())
+                    .or_default();
+                referral.claimed = referral.earned;
+            }
+            "unsubscribe" | "unsubscribe_batch" | "sweep" => {
+                let names: Vec<_> = attr(event, "name")?
+                    .split(',')
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                let slashed = match event.get("slashed") {
+                    Some(slashed) => slashed
+                        .split(',')
+                        .map(|amount| amount.parse().map_err(|_| invalid(event, "slashed")))
+                        .collect::<Result<Vec<u128>, _>>()?,
+                    None => vec![0; names.len()],
+                };
+                if slashed.len() != names.len() {
+                    return Err(invalid(event, "slashed"));
+                }
+                for (name, slashed) in names.into_iter().zip(slashed) {
+                    self.release(name, slashed);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn apply_name_service(&mut self, event: &Event) -> Result<(), Error> {
+        match event.action.as_str() {
+            "register" | "register_reserved" => {
+                self.register(attr(event, "name")?, attr(event, "owner")?, event.height)
+            }
+            "register_batch" => {
+                let names = attr(event, "name")?.split(',');
+                for (name, owner) in names.zip(attr(event, "owner")?.split(',')) {
+                    self.register(name, owner, event.height);
+                }
+            }
+            "deregister" | "deregister_batch" => {
+                for name in attr(event, "name")?.split(',') {
+                    self.names.remove(name);
+                }
+            }
+            "transfer_nft" | "send_nft" => {
+                if let Some(entry) = self.names.get_mut(attr(event, "name")?) {
+                    entry.owner = attr(event, "owner")?.to_string();
+                    entry.records.clear();
+                }
+            }
+            "set_record" => {
+                if let Some(entry) = self.names.get_mut(attr(event, "name")?) {
+                    let key = attr(event, "key")?.to_string();
+                    match event.get("value") {
+                        Some(value) => entry.records.insert(key, value.to_string()),
+                        None => entry.records.remove(&key),
+                    };
+                }
+            }
+            "set_name_status" => {
+                let status = attr(event, "status")?;
+                for name in attr(event, "name")?.split(',') {
+                    if status == "open" {
+                        self.statuses.remove(name);
+                    } else {
+                        self.statuses.insert(name.to_string(), status.to_string());
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn register(&mut self, name: &str, owner: &str, height: u64) {
+        let entry = NameEntry {
+            owner: owner.to_string(),
+            records: BTreeMap::new(),
+            registered_height: height,
+        };
+        self.names.insert(name.to_string(), entry);
+    }
+
+    /// A name is only subscribed to again once it lapsed, which ends its subscription.
+    fn subscribe(&mut self, name: &str, owner: &str, paid: u128, expires: u64) {
+        self.release(name, 0);
+        let subscription = Subscription {
+            owner: owner.to_string(),
+            payer: owner.to_string(),
+            paid: Uint128(paid),
+            expires,
+            stake: None,
+        };
+        self.pending.insert(name.to_string(), subscription);
+    }
+
+    /// Extends the subscription of `name`, whose owner becomes the payer of all it holds.
+    fn renew(&mut self, name: &str, owner: &str, paid: u128, expires: u64) {
+        if let Some(subscription) = self.subscriptions.remove(name) {
+            self.add_payment(&subscription.payer, subscription.paid.u128(), false);
+            let renewed = Subscription {
+                payer: owner.to_string(),
+                paid: Uint128(subscription.paid.u128() + paid),
+                expires,
+                ..subscription
+            };
+            self.add_payment(&renewed.payer, renewed.paid.u128(), true);
+            self.subscriptions.insert(name.to_string(), renewed);
+        }
+    }
+
+    /// Credits the referrer of `event` with its `referral` over `count` subscriptions.
+    fn refer(&mut self, event: &Event, count: u64) -> Result<(), Error> {
+        if let Some(referrer) = event.get("referrer") {
+            let amount = amount(event, "referral")?;
+            let referral = self.referrals.entry(referrer.to_string()).or_default();
+            referral.referrals += count;
+            referral.earned = Uint128(referral.earned.u128() + amount);
+        }
+        Ok(())
+    }
+
+    /// Ends the subscription of `name`, whose stake, if any, starts unbonding for its staker,
+    /// the payer, less what a slash took off it.
+    fn release(&mut self, name: &str, slashed: u128) {
+        if let Some(subscription) = self.subscriptions.remove(name) {
+            self.add_payment(&subscription.payer, subscription.paid.u128(), false);
+            if let Some(stake) = subscription.stake {
+                let waiting = self.unbonding.entry(subscription.payer).or_default();
+                *waiting += Uint128(stake.amount.u128().saturating_sub(slashed));
+            }
+        }
+    }
+
+    fn add_payment(&mut self, payer: &str, paid: u128, add: bool) {
+        let total = self.payments.get(payer).map_or(0, |total| total.u128());
+        let total = if add {
+            total + paid
+        } else {
+            total.saturating_sub(paid)
+        };
+        if total == 0 {
+            self.payments.remove(payer);
+        } else {
+            self.payments.insert(payer.to_string(), Uint128(total));
+        }
+    }
+}
+
+fn attr<'a>(event: &'a Event, key: &'static str) -> Result<&'a str, Error> {
+    event.get(key).ok_or_else(|| invalid(event, key))
+}
+
+fn amount(event: &Event, key: &'static str) -> Result<u128, Error> {
+    attr(event, key)?.parse().map_err(|_| invalid(event, key))
+}
+
+/// What a subscription keeps of `amount` once the premium went to the treasury and the
+/// referral to the referrer.
+fn paid(event: &Event) -> Result<u128, Error> {
+    let premium = amount(event, "premium")?;
+    let referral = match event.get("referrer") {
+        Some(_) => amount(event, "referral")?,
+        None => 0,
+    };
+    amount(event, "amount")?
+        .checked_sub(premium)
+        .and_then(|held| held.checked_sub(referral))
+        .ok_or_else(|| invalid(event, "referral"))
+}
+
+fn number(event: &Event, key: &'static str) -> Result<u64, Error> {
+    attr(event, key)?.parse().map_err(|_| invalid(event, key))
+}
+
+fn invalid(event: &Event, key: &'static str) -> Error {
+    Error::InvalidAttribute {
+        txhash: event.txhash.clone(),
+        action: event.action.clone(),
+        key,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input;
+    use cosmwasm_std::{Coin, Decimal, HumanAddr};
+    use governance::msg::{
+        HandleMsg as GovHandleMsg, InitMsg, PaymentsResponse, QueryMsg as GovQueryMsg,
+        ReferrersResponse, SubscriptionInfoResponse, UnbondingResponse,
+    };
+    use governance::state::{Decay, Premium, Staking};
+    use multitest::{Code, Router, UNBONDING_PERIOD};
+    use name_service::msg::{
+        HandleMsg as NSHandleMsg, NamesResponse, OwnerResponse, QueryMsg as NSQueryMsg,
+        RESOLVER_KEY,
+    };
+    use name_service::state::NameStatus;
+    use serde::Serialize;
+
+    const LUNA: &str = "uluna";
+
+    struct Flow {
+        router: Router,
+        governance: HumanAddr,
+        name_service: HumanAddr,
+    }
+
+    impl Flow {
+        fn new() -> Self {
+            let mut router = Router::new();
+            let msg = InitMsg {
+                nameservice_code_id: Code::NameService as u64,
+                treasury: Some(HumanAddr::from("treasury")),
+                base_price: Uint128(100),
+                registration_period: 1000,
+                grace_period: 100,
+                premium: Some(Premium {
+                    start: Uint128(1000),
+                    window: 100,
+                    decay: Decay::Linear,
+                }),
+                sweep_reward: Some(Uint128(10)),
+                referral_share: Some(Decimal::percent(10)),
+                voucher_signer: None,
+                staking: Some(Staking {
+                    validators: vec![HumanAddr::from("validator")],
+                    stake: Uint128(500),
+                    unbonding_period: UNBONDING_PERIOD,
+                }),
+            };
+            let admin = HumanAddr::from("admin");
+            let governance = router
+                .instantiate(Code::Governance, &admin, &msg, &[])
+                .unwrap();
+            let name_service = router
+                .query(&governance, &GovQueryMsg::GetNameServiceAddress {})
+                .unwrap();
+            for user in &["alice", "bob", "carol"] {
+                router.set_balance(&HumanAddr::from(*user), &[Coin::new(10_000, LUNA)]);
+            }
+            Flow {
+                router,
+                governance,
+                name_service,
+            }
+        }
+
+        fn governance<T: Serialize>(&mut self, sender: &str, msg: &T, amount: u128) -> bool {
+            let funds = match amount {
+                0 => vec![],
+                amount => vec![Coin::new(amount, LUNA)],
+            };
+            let sender = HumanAddr::from(sender);
+            let contract = self.governance.clone();
+            self.router.execute(&sender, &contract, msg, &funds).is_ok()
+        }
+
+        fn name_service(&mut self, sender: &str, msg: &NSHandleMsg) -> bool {
+            let sender = HumanAddr::from(sender);
+            let contract = self.name_service.clone();
+            self.router.execute(&sender, &contract, msg, &[]).is_ok()
+        }
+
+        /// What the router's transactions look like fetched from the LCD.
+        fn logs(&self) -> Vec<u8> {
+            serde_json::to_vec(&json_txs(self.router.txs())).unwrap()
+        }
+    }
+
+    fn json_txs(txs: &[multitest::TxResponse]) -> serde_json::Value {
+        serde_json::json!({ "txs": txs })
+    }
+
+    fn subscribe(name: &str) -> GovHandleMsg {
+        GovHandleMsg::Subscribe {
+            name: name.to_string(),
+            periods: None,
+            referrer: None,
+            voucher: None,
+        }
+    }
+
+    fn stake(name: &str) -> GovHandleMsg {
+        GovHandleMsg::Stake {
+            name: name.to_string(),
+        }
+    }
+
+    fn unsubscribe(name: &str) -> GovHandleMsg {
+        GovHandleMsg::Unsubscribe {
+            name: name.to_string(),
+        }
+    }
+
+    /// Runs subscriptions, stakes, records, transfers, batches, reservations, a renewal, a
+    /// lapse and a failed transaction through both contracts.
+    fn run_flows(flow: &mut Flow) {
+        assert!(flow.governance("alice", &subscribe("alice"), 100));
+        assert!(flow.governance("bob", &subscribe("bob"), 150));
+        let record = NSHandleMsg::SetRecord {
+            name: "alice".to_string(),
+            key: RESOLVER_KEY.to_string(),
+            value: Some("terra1alice".to_string()),
+        };
+        assert!(flow.name_service("alice", &record));
+        let avatar = NSHandleMsg::SetRecord {
+            name: "bob".to_string(),
+            key: "avatar".to_string(),
+            value: Some("ipfs://bob".to_string()),
+        };
+        assert!(flow.name_service("bob", &avatar));
+        let cleared = NSHandleMsg::SetRecord {
+            name: "bob".to_string(),
+            key: "avatar".to_string(),
+            value: None,
+        };
+        assert!(flow.name_service("bob", &cleared));
+        let resolved = NSHandleMsg::SetRecord {
+            name: "bob".to_string(),
+            key: RESOLVER_KEY.to_string(),
+            value: Some("terra1bob".to_string()),
+        };
+        assert!(flow.name_service("bob", &resolved));
+        let transfer = NSHandleMsg::TransferNft {
+            recipient: HumanAddr::from("carol"),
+            token_id: "bob".to_string(),
+        };
+        assert!(flow.name_service("bob", &transfer));
+
+        let batch = GovHandleMsg::SubscribeBatch {
+            names: vec!["x".to_string(), "y".to_string(), "z".to_string()],
+            periods: None,
+            referrer: Some(HumanAddr::from("alice")),
+        };
+        assert!(flow.governance("carol", &batch, 301));
+        let unbatch = GovHandleMsg::UnsubscribeBatch {
+            names: vec!["y".to_string(), "z".to_string()],
+        };
+        assert!(flow.governance("carol", &unbatch, 0));
+        assert!(flow.governance("carol", &subscribe("carol"), 100));
+        assert!(flow.governance(
+            "carol",
+            &GovHandleMsg::Unsubscribe {
+                name: "carol".to_string()
+            },
+            0
+        ));
+
+        let reserve = GovHandleMsg::SetNameStatus {
+            names: vec!["vip".to_string(), "spam".to_string()],
+            status: NameStatus::Reserved,
+        };
+        assert!(flow.governance("admin", &reserve, 0));
+        let block = GovHandleMsg::SetNameStatus {
+            names: vec!["spam".to_string()],
+            status: NameStatus::Blocked,
+        };
+        assert!(flow.governance("admin", &block, 0));
+        let assign = GovHandleMsg::RegisterReserved {
+            name: "vip".to_string(),
+            owner: HumanAddr::from("bob"),
+        };
+        assert!(flow.governance("admin", &assign, 0));
+        assert!(!flow.governance("bob", &subscribe("spam"), 100));
+        let prepaid = GovHandleMsg::Subscribe {
+            name: "long".to_string(),
+            periods: Some(3),
+            referrer: Some(HumanAddr::from("bob")),
+            voucher: None,
+        };
+        assert!(flow.governance("carol", &prepaid, 300));
+        assert!(flow.governance("alice", &GovHandleMsg::ClaimReferral {}, 0));
+        assert!(flow.governance("bob", &stake("held"), 500));
+        assert!(flow.governance("carol", &stake("gone"), 500));
+        assert!(flow.governance("carol", &stake("later"), 600));
+        assert!(flow.governance("carol", &unsubscribe("gone"), 0));
+        // carol renews the name bob paid for before transferring it.
+        let renew = GovHandleMsg::Renew {
+            name: "bob".to_string(),
+            periods: None,
+        };
+        assert!(!flow.governance("bob", &renew, 100));
+        assert!(flow.governance("carol", &renew, 100));
+        let renew_batch = GovHandleMsg::RenewBatch {
+            names: vec!["x".to_string(), "long".to_string()],
+            periods: None,
+        };
+        assert!(flow.governance("carol", &renew_batch, 250));
+
+        // alice lapses and bob takes her name half way through the premium window.
+        flow.router.advance(1000 + 100 + 50);
+        assert!(flow.governance("bob", &subscribe("alice"), 600));
+        // The stake of "gone" has unbonded; that of "later" only starts to, less a slash.
+        assert!(flow.governance("carol", &GovHandleMsg::ClaimStake {}, 0));
+        let validator = HumanAddr::from("validator");
+        flow.router.slash(&validator, Decimal::percent(10));
+        assert!(flow.governance("carol", &unsubscribe("later"), 0));
+        // Everything else bought one period and is swept, except carol's prepaid and renewed
+        // names, "x" among them.
+        let sweep = GovHandleMsg::Sweep { limit: None };
+        assert!(flow.governance("carol", &sweep, 0));
+    }
+
+    fn assert_matches_chain(db: &Db, flow: &Flow) {
+        let listed: NamesResponse = flow
+            .router
+            .query(
+                &flow.name_service,
+                &NSQueryMsg::ListNames {
+                    start_after: None,
+                    limit: Some(30),
+                },
+            )
+            .unwrap();
+        let names: Vec<_> = listed
+            .names
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect();
+        assert_eq!(db.names.keys().cloned().collect::<Vec<_>>(), names);
+
+        for name in &names {
+            let owner: OwnerResponse = flow
+                .router
+                .query(
+                    &flow.name_service,
+                    &NSQueryMsg::OwnerIs {
+                        value: name.clone(),
+                    },
+                )
+                .unwrap();
+            let entry = &db.names[name];
+            assert_eq!(Some(HumanAddr(entry.owner.clone())), owner.owner);
+            let records: BTreeMap<_, _> = owner
+                .records
+                .into_iter()
+                .map(|record| (record.key, record.value))
+                .collect();
+            assert_eq!(entry.records, records);
+
+            let info: SubscriptionInfoResponse = flow
+                .router
+                .query(
+                    &flow.governance,
+                    &GovQueryMsg::SubscriptionInfo { name: name.clone() },
+                )
+                .unwrap();
+            let subscription = &db.subscriptions[name];
+            assert_eq!(subscription.owner, info.owner.0);
+            assert_eq!(subscription.payer, info.payer.0);
+            assert_eq!(subscription.paid, info.paid);
+            assert_eq!(subscription.expires, info.expires);
+            let stake = info.stake.map(|stake| Stake {
+                validator: stake.validator.0,
+                amount: stake.amount,
+            });
+            assert_eq!(subscription.stake, stake);
+        }
+        assert_eq!(db.subscriptions.len(), names.len());
+        assert!(db.pending.is_empty());
+
+        let payments: PaymentsResponse = flow
+            .router
+            .query(
+                &flow.governance,
+                &GovQueryMsg::ListPayments {
+                    start_after: None,
+                    limit: Some(30),
+                },
+            )
+            .unwrap();
+        let payments: BTreeMap<_, _> = payments
+            .payments
+            .into_iter()
+            .map(|entry| (entry.address.0, entry.amount))
+            .collect();
+        assert_eq!(db.payments, payments);
+
+        let referrers: ReferrersResponse = flow
+            .router
+            .query(
+                &flow.governance,
+                &GovQueryMsg::ListReferrers {
+                    start_after: None,
+                    limit: Some(30),
+                },
+            )
+            .unwrap();
+        let referrals: BTreeMap<_, _> = referrers
+            .referrers
+            .into_iter()
+            .map(|entry| {
+                let referral = Referral {
+                    referrals: entry.referrals,
+                    earned: entry.earned,
+                    claimed: entry.claimed,
+                };
+                (entry.referrer.0, referral)
+            })
+            .collect();
+        assert_eq!(db.referrals, referrals);
+
+        for user in &["alice", "bob", "carol"] {
+            let res: UnbondingResponse = flow
+                .router
+                .query(
+                    &flow.governance,
+                    &GovQueryMsg::Unbonding {
+                        address: HumanAddr::from(*user),
+                    },
+                )
+                .unwrap();
+            let waiting: u128 = res.entries.iter().map(|entry| entry.amount.u128()).sum();
+            let indexed = db.unbonding.get(*user).copied().unwrap_or_default();
+            assert_eq!(indexed.u128(), waiting, "unbonding of {}", user);
+        }
+
+        for (name, status) in &db.statuses {
+            let stored: NameStatus = flow
+                .router
+                .query(
+                    &flow.name_service,
+                    &NSQueryMsg::NameStatusIs {
+                        value: name.clone(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(stored.as_str(), status);
+        }
+    }
+
+    #[test]
+    fn rebuilds_the_state_of_both_contracts() {
+        let mut flow = Flow::new();
+        run_flows(&mut flow);
+
+        let mut db = Db::new(flow.governance.0.clone(), None);
+        db.index(input::read(&flow.logs()).unwrap()).unwrap();
+        assert_eq!(db.name_service, Some(flow.name_service.0.clone()));
+        let names: Vec<_> = db.names.keys().map(String::as_str).collect();
+        assert_eq!(names, vec!["alice", "bob", "held", "long", "x"]);
+        assert_eq!(db.names["alice"].owner, "bob");
+        assert_eq!(db.subscriptions["bob"].payer, "carol");
+        assert_eq!(db.subscriptions["bob"].paid, Uint128(250));
+        assert_eq!(db.names["long"].owner, "carol");
+        assert_eq!(db.subscriptions["long"].paid, Uint128(370));
+        assert_eq!(db.subscriptions["x"].paid, Uint128(241));
+        assert_eq!(db.referrals["alice"].claimed, Uint128(30));
+        assert_eq!(db.subscriptions["held"].expires, u64::MAX);
+        assert_eq!(db.unbonding["carol"], Uint128(540));
+        assert_eq!(db.statuses["spam"], "blocked");
+        assert_matches_chain(&db, &flow);
+
+        let history: Vec<_> = db.history().map(|event| event.action.as_str()).collect();
+        assert_eq!(
+            history,
+            vec!["set_name_status", "set_name_status", "register_reserved"]
+        );
+
+        // Indexing the same transactions again, or in two parts, ends in the same state.
+        let again = db.clone();
+        db.index(input::read(&flow.logs()).unwrap()).unwrap();
+        assert_eq!(db, again);
+        let txs = flow.router.txs();
+        let (first, second) = txs.split_at(txs.len() / 2);
+        let mut parts = Db::new(flow.governance.0.clone(), None);
+        for part in &[first, second] {
+            let logs = serde_json::to_vec(&json_txs(part)).unwrap();
+            parts.index(input::read(&logs).unwrap()).unwrap();
+        }
+        assert_eq!(parts, db);
+    }
+
+    #[test]
+    fn ignores_other_contracts() {
+        let mut flow = Flow::new();
+        run_flows(&mut flow);
+        let mut db = Db::new("elsewhere".to_string(), None);
+        db.index(input::read(&flow.logs()).unwrap()).unwrap();
+        assert!(db.names.is_empty());
+        assert!(db.events.is_empty());
+        assert_eq!(db.txs.len(), flow.router.txs().len());
+    }
+}
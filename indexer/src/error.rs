@@ -0,0 +1,44 @@
+use snafu::Snafu;
+use std::path::PathBuf;
+
+#[derive(Snafu, Debug)]
+#[snafu(visibility = "pub")]
+pub enum Error {
+    #[snafu(display("Transaction {} has no valid height.", txhash))]
+    InvalidHeight { txhash: String },
+
+    #[snafu(display("Transaction {}: {} has an invalid {}.", txhash, action, key))]
+    InvalidAttribute {
+        txhash: String,
+        action: String,
+        key: &'static str,
+    },
+
+    #[snafu(display("{} is indexed for governance {}, not {}.", path.display(), indexed, given))]
+    OtherGovernance {
+        path: PathBuf,
+        indexed: String,
+        given: String,
+    },
+
+    #[snafu(display("--governance is required to start a new database."))]
+    MissingGovernance {},
+
+    #[snafu(display("Cannot read {}: {}", path.display(), source))]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("Cannot listen on {}: {}", address, reason))]
+    Listen { address: String, reason: String },
+
+    #[snafu(display("{}", source))]
+    Json { source: serde_json::Error },
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(source: serde_json::Error) -> Self {
+        Error::Json { source }
+    }
+}
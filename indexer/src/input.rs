@@ -0,0 +1,180 @@
+//! Reads transactions as the LCD returns them and splits their contract events into the
+//! logs of each contract call. Accepts a single transaction, an array of them, a search
+//! result with `txs` or `tx_responses`, or any sequence of those one after another.
+
+use crate::error::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Terra reports contract logs in `from_contract` events, wasmd in `wasm` events.
+const CONTRACT_EVENTS: &[&str] = &["from_contract", "wasm"];
+const ADDRESS_KEYS: &[&str] = &["contract_address", "_contract_address"];
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Attribute {
+    pub key: String,
+    pub value: String,
+}
+
+/// The attributes one contract logged in one call.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContractLog {
+    pub height: u64,
+    pub txhash: String,
+    pub contract: String,
+    pub attributes: Vec<Attribute>,
+}
+
+#[derive(Deserialize)]
+struct Tx {
+    height: Value,
+    txhash: String,
+    #[serde(default)]
+    code: Option<u32>,
+    #[serde(default)]
+    logs: Option<Vec<MsgLog>>,
+}
+
+#[derive(Deserialize)]
+struct MsgLog {
+    #[serde(default)]
+    events: Vec<Event>,
+}
+
+#[derive(Deserialize)]
+struct Event {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    attributes: Vec<Attribute>,
+}
+
+/// Contract logs in the order they were written. Failed transactions log nothing.
+pub fn read(input: &[u8]) -> Result<Vec<ContractLog>, Error> {
+    let mut logs = vec![];
+    for value in serde_json::Deserializer::from_slice(input).into_iter::<Value>() {
+        for tx in transactions(value?) {
+            logs.extend(contract_logs(serde_json::from_value(tx)?)?);
+        }
+    }
+    Ok(logs)
+}
+
+fn transactions(value: Value) -> Vec<Value> {
+    match value {
+        Value::Array(txs) => txs,
+        Value::Object(mut object) => {
+            match object
+                .remove("tx_responses")
+                .or_else(|| object.remove("txs"))
+            {
+                Some(Value::Array(txs)) => txs,
+                _ => vec![Value::Object(object)],
+            }
+        }
+        other => vec![other],
+    }
+}
+
+fn contract_logs(tx: Tx) -> Result<Vec<ContractLog>, Error> {
+    if tx.code.unwrap_or(0) != 0 {
+        return Ok(vec![]);
+    }
+    let height = match &tx.height {
+        Value::String(height) => height.parse().ok(),
+        Value::Number(height) => height.as_u64(),
+        _ => None,
+    };
+    let height = height.ok_or_else(|| Error::InvalidHeight {
+        txhash: tx.txhash.clone(),
+    })?;
+
+    let mut logs: Vec<ContractLog> = vec![];
+    let events = tx.logs.into_iter().flatten().flat_map(|log| log.events);
+    for event in events.filter(|event| CONTRACT_EVENTS.contains(&event.kind.as_str())) {
+        for attribute in event.attributes {
+            if ADDRESS_KEYS.contains(&attribute.key.as_str()) {
+                logs.push(ContractLog {
+                    height,
+                    txhash: tx.txhash.clone(),
+                    contract: attribute.value,
+                    attributes: vec![],
+                });
+            } else if let Some(log) = logs.last_mut() {
+                log.attributes.push(attribute);
+            }
+        }
+    }
+    Ok(logs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn tx(hash: &str, height: Value, attributes: Value) -> Value {
+        json!({
+            "height": height,
+            "txhash": hash,
+            "logs": [{"msg_index": 0, "events": [
+                {"type": "message", "attributes": [{"key": "sender", "value": "user"}]},
+                {"type": "from_contract", "attributes": attributes},
+            ]}],
+        })
+    }
+
+    #[test]
+    fn splits_events_by_contract() {
+        let attributes = json!([
+            {"key": "contract_address", "value": "gov"},
+            {"key": "action", "value": "subscribe"},
+            {"key": "contract_address", "value": "names"},
+            {"key": "action", "value": "register"},
+        ]);
+        let logs = read(&serde_json::to_vec(&tx("A", json!("7"), attributes)).unwrap()).unwrap();
+        assert_eq!(logs.len(), 2);
+        assert_eq!(logs[0].contract, "gov");
+        assert_eq!(logs[0].height, 7);
+        assert_eq!(logs[1].contract, "names");
+        assert_eq!(
+            logs[1].attributes,
+            vec![Attribute {
+                key: "action".to_string(),
+                value: "register".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn reads_every_layout() {
+        let one = |hash: &str| {
+            tx(
+                hash,
+                json!(1),
+                json!([{"key": "contract_address", "value": "gov"}]),
+            )
+        };
+        let mut failed = one("F");
+        failed["code"] = json!(5);
+        let input = format!(
+            "{}\n{}\n{}\n{}",
+            one("A"),
+            json!([one("B"), failed]),
+            json!({"total_count": "1", "txs": [one("C")]}),
+            json!({"tx_responses": [one("D")]}),
+        );
+        let hashes: Vec<_> = read(input.as_bytes())
+            .unwrap()
+            .into_iter()
+            .map(|log| log.txhash)
+            .collect();
+        assert_eq!(hashes, vec!["A", "B", "C", "D"]);
+
+        let bad = tx("E", json!("x"), json!([]));
+        assert!(matches!(
+            read(&serde_json::to_vec(&bad).unwrap()),
+            Err(Error::InvalidHeight { .. })
+        ));
+    }
+}
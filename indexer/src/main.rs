@@ -0,0 +1,103 @@
+mod api;
+mod db;
+mod error;
+mod input;
+
+use crate::db::Db;
+use crate::error::{Error, Io};
+use clap::{Parser, Subcommand};
+use snafu::ResultExt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::PathBuf;
+use std::process;
+
+/// Rebuilds name and payment state from governance and name service logs, and serves it
+/// as JSON.
+#[derive(Parser)]
+#[command(name = "tns-indexer", version)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Adds the transactions in FILES, or stdin, to the database
+    Index {
+        /// JSON file holding the indexed state; created when missing
+        #[arg(long)]
+        db: PathBuf,
+        /// Governance address; required for a new database
+        #[arg(long)]
+        governance: Option<String>,
+        /// Name service address; otherwise learned from governance's signup
+        #[arg(long)]
+        name_service: Option<String>,
+        files: Vec<PathBuf>,
+    },
+    /// Serves the database over HTTP until stopped
+    Serve {
+        #[arg(long)]
+        db: PathBuf,
+        #[arg(long, default_value = "127.0.0.1:3030")]
+        listen: String,
+    },
+}
+
+fn main() {
+    if let Err(err) = run(Cli::parse()) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<(), Error> {
+    match cli.command {
+        Command::Index {
+            db: path,
+            governance,
+            name_service,
+            files,
+        } => {
+            let mut db = match (Db::load(&path)?, governance) {
+                (Some(db), Some(given)) if db.governance != given => {
+                    return Err(Error::OtherGovernance {
+                        path,
+                        indexed: db.governance,
+                        given,
+                    })
+                }
+                (Some(db), _) => db,
+                (None, Some(governance)) => Db::new(governance, name_service),
+                (None, None) => return Err(Error::MissingGovernance {}),
+            };
+            if files.is_empty() {
+                let mut input = vec![];
+                io::stdin()
+                    .read_to_end(&mut input)
+                    .context(Io { path: "stdin" })?;
+                db.index(input::read(&input)?)?;
+            }
+            for file in files {
+                let contents = fs::read(&file).context(Io { path: file })?;
+                db.index(input::read(&contents)?)?;
+            }
+            db.save(&path)?;
+            eprintln!(
+                "indexed {} transactions up to height {}",
+                db.txs.len(),
+                db.height
+            );
+            Ok(())
+        }
+        Command::Serve { db: path, listen } => {
+            let db = Db::load(&path)?.ok_or(Error::Io {
+                path,
+                source: io::ErrorKind::NotFound.into(),
+            })?;
+            eprintln!("serving on http://{}", listen);
+            api::serve(&db, &listen)
+        }
+    }
+}
@@ -0,0 +1,669 @@
+//! An in-process chain for governance and the name service. Messages a contract returns are
+//! executed right after it, depth first, against the other contract and a shared bank, and
+//! a failure anywhere reverts the whole call like a failed transaction would. Calls that
+//! succeed are kept as transactions with the logs a node would report for them.
+//!
+//! Staking is modelled just far enough for governance: delegations, unbonding that pays
+//! out once [`UNBONDING_PERIOD`] has passed, slashes with [`Router::slash`], and rewards
+//! credited with [`Router::accrue_rewards`] and paid to the delegator's withdraw address.
+
+use cosmwasm_std::testing::{mock_env, MockApi, MockStorage};
+use cosmwasm_std::{
+    from_slice, log, to_binary, AllBalanceResponse, BalanceResponse, BankMsg, BankQuery, Binary,
+    BlockInfo, Coin, ContractInfo, CosmosMsg, Decimal, Empty, Env, Extern, FullDelegation,
+    HandleResponse, HumanAddr, LogAttribute, MessageInfo, Order, Querier, QuerierResult,
+    QueryRequest, ReadonlyStorage, StakingMsg, StakingQuery, StdError, StdResult, Storage,
+    SystemError, Uint128, WasmMsg, WasmQuery,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+const CANONICAL_LENGTH: usize = 20;
+const BONDED_DENOM: &str = "uluna";
+/// Holds bonded and unbonding coins.
+const STAKING_MODULE: &str = "staking";
+
+/// Seconds between an undelegation and its stake returning to the delegator.
+pub const UNBONDING_PERIOD: u64 = 1000;
+
+/// The contracts the router can instantiate; the discriminant is the code id.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Code {
+    Governance = 1,
+    NameService = 2,
+}
+
+impl Code {
+    fn from_id(code_id: u64) -> StdResult<Code> {
+        match code_id {
+            1 => Ok(Code::Governance),
+            2 => Ok(Code::NameService),
+            _ => Err(StdError::generic_err(format!(
+                "No code with id {}",
+                code_id
+            ))),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Instance {
+    code: Code,
+    storage: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+impl Instance {
+    fn load(&self) -> MockStorage {
+        let mut storage = MockStorage::new();
+        for (key, value) in &self.storage {
+            storage.set(key, value);
+        }
+        storage
+    }
+}
+
+/// Everything a failed call has to roll back.
+#[derive(Clone, Default)]
+struct Chain {
+    contracts: HashMap<HumanAddr, Instance>,
+    balances: HashMap<HumanAddr, BTreeMap<String, u128>>,
+    /// Bonded amounts keyed by delegator and validator.
+    delegations: HashMap<(HumanAddr, HumanAddr), u128>,
+    /// Unclaimed rewards keyed like `delegations`.
+    rewards: HashMap<(HumanAddr, HumanAddr), u128>,
+    withdraw_addresses: HashMap<HumanAddr, HumanAddr>,
+    unbonding: Vec<Unbonding>,
+}
+
+#[derive(Clone)]
+struct Unbonding {
+    delegator: HumanAddr,
+    amount: u128,
+    release: u64,
+}
+
+type Deps<'a> = Extern<MockStorage, MockApi, ChainQuerier<'a>>;
+
+/// The answer to `StakingQuery::Delegation`, which cosmwasm-std 0.10 does not export.
+#[derive(Serialize)]
+struct DelegationResponse {
+    delegation: Option<FullDelegation>,
+}
+
+/// A committed transaction as the LCD `txs` endpoints return it. All contracts the message
+/// ran log into one `from_contract` event, each introduced by its `contract_address`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TxResponse {
+    pub height: String,
+    pub txhash: String,
+    pub logs: Vec<MsgLog>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MsgLog {
+    pub msg_index: u32,
+    pub events: Vec<Event>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub attributes: Vec<LogAttribute>,
+}
+
+pub struct Router {
+    chain: Chain,
+    block: BlockInfo,
+    instantiated: u64,
+    txs: Vec<TxResponse>,
+    /// `from_contract` attributes of the transaction being executed.
+    logs: Vec<LogAttribute>,
+}
+
+impl Default for Router {
+    fn default() -> Self {
+        Router {
+            chain: Chain::default(),
+            block: mock_env("router", &[]).block,
+            instantiated: 0,
+            txs: vec![],
+            logs: vec![],
+        }
+    }
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn block_time(&self) -> u64 {
+        self.block.time
+    }
+
+    /// Moves the block on, paying out the unbonding stakes that have matured.
+    pub fn advance(&mut self, seconds: u64) {
+        self.block.time += seconds;
+        self.block.height += 1;
+        self.chain.release(self.block.time);
+    }
+
+    pub fn set_balance(&mut self, address: &HumanAddr, coins: &[Coin]) {
+        let balance = self.chain.balances.entry(address.clone()).or_default();
+        balance.clear();
+        for coin in coins {
+            balance.insert(coin.denom.clone(), coin.amount.u128());
+        }
+    }
+
+    pub fn balance(&self, address: &HumanAddr, denom: &str) -> u128 {
+        self.chain.balance(address, denom)
+    }
+
+    /// What `delegator` has bonded to `validator`.
+    pub fn delegation(&self, delegator: &HumanAddr, validator: &HumanAddr) -> u128 {
+        let key = (delegator.clone(), validator.clone());
+        self.chain
+            .delegations
+            .get(&key)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// What `delegator` has undelegated and not yet got back.
+    pub fn unbonding(&self, delegator: &HumanAddr) -> u128 {
+        self.chain
+            .unbonding
+            .iter()
+            .filter(|entry| &entry.delegator == delegator)
+            .map(|entry| entry.amount)
+            .sum()
+    }
+
+    /// Credits `amount` of rewards to the delegation, to be paid out the next time it is
+    /// withdrawn from or changed.
+    pub fn accrue_rewards(&mut self, delegator: &HumanAddr, validator: &HumanAddr, amount: u128) {
+        let key = (delegator.clone(), validator.clone());
+        *self.chain.rewards.entry(key).or_default() += amount;
+    }
+
+    /// Burns `fraction` of every delegation to `validator`, as the chain does to punish it.
+    /// Stakes already unbonding are left whole.
+    pub fn slash(&mut self, validator: &HumanAddr, fraction: Decimal) {
+        let mut burned = 0;
+        for ((_, delegated_to), amount) in self.chain.delegations.iter_mut() {
+            if delegated_to == validator {
+                let slashed = (Uint128(*amount) * fraction).u128();
+                *amount -= slashed;
+                burned += slashed;
+            }
+        }
+        let module = HumanAddr::from(STAKING_MODULE);
+        let left = self.chain.balance(&module, BONDED_DENOM) - burned;
+        self.chain
+            .balances
+            .entry(module)
+            .or_default()
+            .insert(BONDED_DENOM.to_string(), left);
+    }
+
+    pub fn instantiate<T: Serialize>(
+        &mut self,
+        code: Code,
+        sender: &HumanAddr,
+        msg: &T,
+        funds: &[Coin],
+    ) -> StdResult<HumanAddr> {
+        let msg = to_binary(msg)?;
+        self.transact(|router| router.instantiate_code(code, sender, &msg, funds))
+    }
+
+    pub fn execute<T: Serialize>(
+        &mut self,
+        sender: &HumanAddr,
+        contract: &HumanAddr,
+        msg: &T,
+        funds: &[Coin],
+    ) -> StdResult<HandleResponse> {
+        let msg = to_binary(msg)?;
+        self.transact(|router| router.execute_contract(sender, contract, &msg, funds))
+    }
+
+    pub fn query<T: Serialize, R: DeserializeOwned>(
+        &self,
+        contract: &HumanAddr,
+        msg: &T,
+    ) -> StdResult<R> {
+        from_slice(self.query_binary(contract, msg)?.as_slice())
+    }
+
+    /// Every call that succeeded so far, oldest first.
+    pub fn txs(&self) -> &[TxResponse] {
+        &self.txs
+    }
+
+    /// The undecoded response, for callers without a response type to hand.
+    pub fn query_binary<T: Serialize>(&self, contract: &HumanAddr, msg: &T) -> StdResult<Binary> {
+        self.chain.query_contract(contract, &to_binary(msg)?)
+    }
+
+    fn transact<R>(&mut self, call: impl FnOnce(&mut Router) -> StdResult<R>) -> StdResult<R> {
+        let snapshot = self.chain.clone();
+        self.logs.clear();
+        let res = call(self);
+        match res {
+            Ok(_) => {
+                let event = Event {
+                    kind: "from_contract".to_string(),
+                    attributes: self.logs.drain(..).collect(),
+                };
+                self.txs.push(TxResponse {
+                    height: self.block.height.to_string(),
+                    txhash: format!("{:064X}", self.txs.len() + 1),
+                    logs: vec![MsgLog {
+                        msg_index: 0,
+                        events: vec![event],
+                    }],
+                });
+            }
+            Err(_) => self.chain = snapshot,
+        }
+        res
+    }
+
+    fn record(&mut self, contract: &HumanAddr, attributes: Vec<LogAttribute>) {
+        self.logs.push(log("contract_address", contract));
+        self.logs.extend(attributes);
+    }
+
+    fn env(&self, sender: &HumanAddr, contract: &HumanAddr, funds: &[Coin]) -> Env {
+        Env {
+            block: self.block.clone(),
+            message: MessageInfo {
+                sender: sender.clone(),
+                sent_funds: funds.to_vec(),
+            },
+            contract: ContractInfo {
+                address: contract.clone(),
+            },
+        }
+    }
+
+    fn instantiate_code(
+        &mut self,
+        code: Code,
+        sender: &HumanAddr,
+        msg: &Binary,
+        funds: &[Coin],
+    ) -> StdResult<HumanAddr> {
+        self.instantiated += 1;
+        let address = HumanAddr(format!("contract{}", self.instantiated));
+        self.chain.contracts.insert(
+            address.clone(),
+            Instance {
+                code,
+                storage: BTreeMap::new(),
+            },
+        );
+        self.chain.transfer(sender, &address, funds)?;
+
+        let env = self.env(sender, &address, funds);
+        let (res, storage) = {
+            let mut deps = self.chain.deps(&address)?;
+            let res = match code {
+                Code::Governance => {
+                    governance::contract::init(&mut deps, env, from_slice(msg.as_slice())?)?
+                }
+                Code::NameService => {
+                    name_service::contract::init(&mut deps, env, from_slice(msg.as_slice())?)?
+                }
+            };
+            (res, deps.storage)
+        };
+        self.chain.save(&address, &storage);
+        self.record(&address, res.log);
+        self.dispatch(&address, res.messages)?;
+        Ok(address)
+    }
+
+    fn execute_contract(
+        &mut self,
+        sender: &HumanAddr,
+        contract: &HumanAddr,
+        msg: &Binary,
+        funds: &[Coin],
+    ) -> StdResult<HandleResponse> {
+        self.chain.transfer(sender, contract, funds)?;
+        let env = self.env(sender, contract, funds);
+        let (res, storage) = {
+            let mut deps = self.chain.deps(contract)?;
+            let res = match self.chain.code(contract)? {
+                Code::Governance => {
+                    governance::contract::handle(&mut deps, env, from_slice(msg.as_slice())?)?
+                }
+                Code::NameService => {
+                    name_service::contract::handle(&mut deps, env, from_slice(msg.as_slice())?)?
+                }
+            };
+            (res, deps.storage)
+        };
+        self.chain.save(contract, &storage);
+        self.record(contract, res.log.clone());
+        self.dispatch(contract, res.messages.clone())?;
+        Ok(res)
+    }
+
+    fn dispatch(&mut self, sender: &HumanAddr, messages: Vec<CosmosMsg>) -> StdResult<()> {
+        for msg in messages {
+            match msg {
+                CosmosMsg::Bank(BankMsg::Send {
+                    from_address,
+                    to_address,
+                    amount,
+                }) => {
+                    if &from_address != sender {
+                        return Err(StdError::unauthorized());
+                    }
+                    self.chain.transfer(&from_address, &to_address, &amount)?;
+                }
+                CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr,
+                    msg,
+                    send,
+                }) => {
+                    self.execute_contract(sender, &contract_addr, &msg, &send)?;
+                }
+                CosmosMsg::Wasm(WasmMsg::Instantiate {
+                    code_id, msg, send, ..
+                }) => {
+                    self.instantiate_code(Code::from_id(code_id)?, sender, &msg, &send)?;
+                }
+                CosmosMsg::Staking(StakingMsg::Delegate { validator, amount }) => {
+                    bonded(&amount)?;
+                    self.chain.withdraw_rewards(sender, &validator);
+                    self.chain
+                        .delegate(sender, &validator, amount.amount.u128())?;
+                }
+                CosmosMsg::Staking(StakingMsg::Undelegate { validator, amount }) => {
+                    bonded(&amount)?;
+                    self.chain.withdraw_rewards(sender, &validator);
+                    let release = self.block.time + UNBONDING_PERIOD;
+                    self.chain
+                        .undelegate(sender, &validator, amount.amount.u128(), release)?;
+                }
+                CosmosMsg::Staking(StakingMsg::Withdraw {
+                    validator,
+                    recipient,
+                }) => {
+                    let key = (sender.clone(), validator.clone());
+                    if !self.chain.delegations.contains_key(&key) {
+                        return Err(StdError::not_found(format!(
+                            "delegation of {} to {}",
+                            sender, validator
+                        )));
+                    }
+                    if let Some(recipient) = recipient {
+                        self.chain
+                            .withdraw_addresses
+                            .insert(sender.clone(), recipient);
+                    }
+                    self.chain.withdraw_rewards(sender, &validator);
+                }
+                msg => {
+                    return Err(StdError::generic_err(format!(
+                        "Unsupported message: {:?}",
+                        msg
+                    )))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Chain {
+    fn code(&self, contract: &HumanAddr) -> StdResult<Code> {
+        match self.contracts.get(contract) {
+            Some(instance) => Ok(instance.code),
+            None => Err(StdError::not_found(format!("contract {}", contract))),
+        }
+    }
+
+    /// Dependencies for `contract`, holding a copy of its storage and querying this chain.
+    fn deps(&self, contract: &HumanAddr) -> StdResult<Deps<'_>> {
+        let storage = match self.contracts.get(contract) {
+            Some(instance) => instance.load(),
+            None => return Err(StdError::not_found(format!("contract {}", contract))),
+        };
+        Ok(Extern {
+            storage,
+            api: MockApi::new(CANONICAL_LENGTH),
+            querier: ChainQuerier { chain: self },
+        })
+    }
+
+    fn save(&mut self, contract: &HumanAddr, storage: &MockStorage) {
+        if let Some(instance) = self.contracts.get_mut(contract) {
+            instance.storage = storage.range(None, None, Order::Ascending).collect();
+        }
+    }
+
+    fn query_contract(&self, contract: &HumanAddr, msg: &Binary) -> StdResult<Binary> {
+        let deps = self.deps(contract)?;
+        match self.code(contract)? {
+            Code::Governance => governance::contract::query(&deps, from_slice(msg.as_slice())?),
+            Code::NameService => name_service::contract::query(&deps, from_slice(msg.as_slice())?),
+        }
+    }
+
+    fn balance(&self, address: &HumanAddr, denom: &str) -> u128 {
+        self.balances
+            .get(address)
+            .and_then(|balance| balance.get(denom))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn credit(&mut self, address: &HumanAddr, denom: &str, amount: u128) {
+        *self
+            .balances
+            .entry(address.clone())
+            .or_default()
+            .entry(denom.to_string())
+            .or_default() += amount;
+    }
+
+    fn delegate(
+        &mut self,
+        delegator: &HumanAddr,
+        validator: &HumanAddr,
+        amount: u128,
+    ) -> StdResult<()> {
+        let funds = [Coin::new(amount, BONDED_DENOM)];
+        // Bonded coins leave the delegator's balance for the staking module's.
+        self.transfer(delegator, &HumanAddr::from(STAKING_MODULE), &funds)?;
+        let key = (delegator.clone(), validator.clone());
+        *self.delegations.entry(key).or_default() += amount;
+        Ok(())
+    }
+
+    fn undelegate(
+        &mut self,
+        delegator: &HumanAddr,
+        validator: &HumanAddr,
+        amount: u128,
+        release: u64,
+    ) -> StdResult<()> {
+        let key = (delegator.clone(), validator.clone());
+        let bonded = self.delegations.get(&key).copied().unwrap_or_default();
+        let left = bonded.checked_sub(amount).ok_or_else(|| {
+            StdError::generic_err(format!(
+                "{} has less than {}{} bonded to {}",
+                delegator, amount, BONDED_DENOM, validator
+            ))
+        })?;
+        match left {
+            0 => self.delegations.remove(&key),
+            left => self.delegations.insert(key, left),
+        };
+        self.unbonding.push(Unbonding {
+            delegator: delegator.clone(),
+            amount,
+            release,
+        });
+        Ok(())
+    }
+
+    /// Pays the delegation's rewards to the delegator's withdraw address, as the chain does
+    /// before every change to a delegation.
+    fn withdraw_rewards(&mut self, delegator: &HumanAddr, validator: &HumanAddr) {
+        let key = (delegator.clone(), validator.clone());
+        let amount = self.rewards.remove(&key).unwrap_or_default();
+        let recipient = self
+            .withdraw_addresses
+            .get(delegator)
+            .unwrap_or(delegator)
+            .clone();
+        self.credit(&recipient, BONDED_DENOM, amount);
+    }
+
+    /// Returns the stakes whose unbonding has finished by `time` to their delegators.
+    fn release(&mut self, time: u64) {
+        let (released, waiting) = self
+            .unbonding
+            .drain(..)
+            .partition::<Vec<_>, _>(|entry| entry.release <= time);
+        self.unbonding = waiting;
+        let module = HumanAddr::from(STAKING_MODULE);
+        for entry in released {
+            let funds = [Coin::new(entry.amount, BONDED_DENOM)];
+            // The module holds every bonded and unbonding coin, so this cannot fail.
+            let _ = self.transfer(&module, &entry.delegator, &funds);
+        }
+    }
+
+    fn transfer(&mut self, from: &HumanAddr, to: &HumanAddr, coins: &[Coin]) -> StdResult<()> {
+        for coin in coins {
+            let amount = coin.amount.u128();
+            let left = self
+                .balance(from, &coin.denom)
+                .checked_sub(amount)
+                .ok_or_else(|| {
+                    StdError::generic_err(format!(
+                        "{} has less than {}{}",
+                        from, amount, coin.denom
+                    ))
+                })?;
+            let received = self.balance(to, &coin.denom) + amount;
+            let balances = &mut self.balances;
+            balances
+                .entry(from.clone())
+                .or_default()
+                .insert(coin.denom.clone(), left);
+            balances
+                .entry(to.clone())
+                .or_default()
+                .insert(coin.denom.clone(), received);
+        }
+        Ok(())
+    }
+}
+
+/// Only the bonded denom can be delegated.
+fn bonded(amount: &Coin) -> StdResult<()> {
+    match amount.denom.as_str() {
+        BONDED_DENOM => Ok(()),
+        denom => Err(StdError::generic_err(format!(
+            "Cannot delegate {}, only {}",
+            denom, BONDED_DENOM
+        ))),
+    }
+}
+
+/// Answers bank queries from the shared bank, delegation queries from the staking module and
+/// wasm queries from the stored contracts.
+pub struct ChainQuerier<'a> {
+    chain: &'a Chain,
+}
+
+impl Querier for ChainQuerier<'_> {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(request) => request,
+            Err(err) => {
+                return Err(SystemError::InvalidRequest {
+                    error: err.to_string(),
+                    request: Binary::from(bin_request),
+                })
+            }
+        };
+        match request {
+            QueryRequest::Bank(BankQuery::Balance { address, denom }) => {
+                let amount = Coin {
+                    amount: Uint128(self.chain.balance(&address, &denom)),
+                    denom,
+                };
+                Ok(to_binary(&BalanceResponse { amount }))
+            }
+            QueryRequest::Bank(BankQuery::AllBalances { address }) => {
+                let amount = self
+                    .chain
+                    .balances
+                    .get(&address)
+                    .into_iter()
+                    .flatten()
+                    .filter(|(_, amount)| **amount != 0)
+                    .map(|(denom, amount)| Coin::new(*amount, denom))
+                    .collect();
+                Ok(to_binary(&AllBalanceResponse { amount }))
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => {
+                if !self.chain.contracts.contains_key(&contract_addr) {
+                    return Err(SystemError::NoSuchContract {
+                        addr: contract_addr,
+                    });
+                }
+                Ok(self.chain.query_contract(&contract_addr, &msg))
+            }
+            QueryRequest::Wasm(WasmQuery::Raw { contract_addr, key }) => {
+                match self.chain.contracts.get(&contract_addr) {
+                    Some(instance) => Ok(Ok(Binary(
+                        instance
+                            .storage
+                            .get(key.as_slice())
+                            .cloned()
+                            .unwrap_or_default(),
+                    ))),
+                    None => Err(SystemError::NoSuchContract {
+                        addr: contract_addr,
+                    }),
+                }
+            }
+            QueryRequest::Staking(StakingQuery::Delegation {
+                delegator,
+                validator,
+            }) => {
+                let key = (delegator.clone(), validator.clone());
+                let delegation = self.chain.delegations.get(&key).map(|amount| {
+                    let rewards = self.chain.rewards.get(&key).copied().unwrap_or_default();
+                    FullDelegation {
+                        delegator,
+                        validator,
+                        amount: Coin::new(*amount, BONDED_DENOM),
+                        can_redelegate: Coin::new(*amount, BONDED_DENOM),
+                        accumulated_rewards: Coin::new(rewards, BONDED_DENOM),
+                    }
+                });
+                Ok(to_binary(&DelegationResponse { delegation }))
+            }
+            QueryRequest::Staking(_) => Err(SystemError::UnsupportedRequest {
+                kind: "staking".to_string(),
+            }),
+            QueryRequest::Custom(_) => Err(SystemError::UnsupportedRequest {
+                kind: "custom".to_string(),
+            }),
+        }
+    }
+}
@@ -0,0 +1,326 @@
+//! End-to-end flows between governance and the name service it instantiates, run through
+//! the in-process router so every dispatched message and transfer really happens.
+
+use cosmwasm_std::{from_binary, Coin, HumanAddr, StdResult, Uint128};
+use governance::msg::QueryMsg as GovQueryMsg;
+use governance::msg::{
+    HandleMsg, InitMsg, PaidAmountResponse, SubscribeBatchResponse, SubscriptionInfoResponse,
+};
+use governance::state::{Decay, Premium, Staking};
+use multitest::{Code, Router, UNBONDING_PERIOD};
+use name_service::msg::{NamesResponse, OwnerResponse, QueryMsg as NSQueryMsg};
+use name_service::state::NameStatus;
+
+const LUNA: &str = "uluna";
+
+struct Setup {
+    router: Router,
+    governance: HumanAddr,
+    name_service: HumanAddr,
+}
+
+fn setup(premium: Option<Premium>, staking: Option<Staking>) -> Setup {
+    let mut router = Router::new();
+    let admin = HumanAddr::from("admin");
+    let msg = InitMsg {
+        nameservice_code_id: Code::NameService as u64,
+        treasury: Some(HumanAddr::from("treasury")),
+        base_price: Uint128(100),
+        registration_period: 1000,
+        grace_period: 100,
+        premium,
+        sweep_reward: None,
+        referral_share: None,
+        voucher_signer: None,
+        staking,
+    };
+    let governance = router
+        .instantiate(Code::Governance, &admin, &msg, &[])
+        .unwrap();
+    let name_service = router
+        .query(&governance, &GovQueryMsg::GetNameServiceAddress {})
+        .unwrap();
+    Setup {
+        router,
+        governance,
+        name_service,
+    }
+}
+
+fn owner_of(setup: &Setup, name: &str) -> Option<HumanAddr> {
+    let res: OwnerResponse = setup
+        .router
+        .query(
+            &setup.name_service,
+            &NSQueryMsg::OwnerIs {
+                value: name.to_string(),
+            },
+        )
+        .unwrap();
+    res.owner
+}
+
+fn subscription(setup: &Setup, name: &str) -> StdResult<SubscriptionInfoResponse> {
+    setup.router.query(
+        &setup.governance,
+        &GovQueryMsg::SubscriptionInfo {
+            name: name.to_string(),
+        },
+    )
+}
+
+#[test]
+fn init_hook_signs_the_name_service_up() {
+    let setup = setup(None, None);
+    assert_ne!(setup.name_service, setup.governance);
+    assert_ne!(setup.name_service, HumanAddr::default());
+    let list = NSQueryMsg::ListNames {
+        start_after: None,
+        limit: None,
+    };
+    let res: NamesResponse = setup.router.query(&setup.name_service, &list).unwrap();
+    assert!(res.names.is_empty());
+}
+
+#[test]
+fn subscription_registers_and_refunds() {
+    let mut setup = setup(None, None);
+    let user = HumanAddr::from("user");
+    setup.router.set_balance(&user, &[Coin::new(1000, LUNA)]);
+
+    let msg = HandleMsg::Subscribe {
+        name: "alice".to_string(),
+        periods: None,
+        referrer: None,
+        voucher: None,
+    };
+    let funds = [Coin::new(1000, LUNA)];
+    setup
+        .router
+        .execute(&user, &setup.governance, &msg, &funds)
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), Some(user.clone()));
+    let info = subscription(&setup, "alice").unwrap();
+    assert!(!info.pending);
+    assert_eq!(info.refund, Uint128(100));
+    let paid: PaidAmountResponse = setup
+        .router
+        .query(
+            &setup.governance,
+            &GovQueryMsg::PaidAmountIs {
+                address: user.clone(),
+            },
+        )
+        .unwrap();
+    assert_eq!(paid.amount, Uint128(1000));
+    assert_eq!(setup.router.balance(&user, LUNA), 0);
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 1000);
+
+    // The transaction logs every contract it ran, in execution order.
+    let tx = setup.router.txs().last().unwrap();
+    let actions: Vec<_> = tx.logs[0].events[0]
+        .attributes
+        .iter()
+        .filter(|attr| attr.key == "contract_address" || attr.key == "action")
+        .map(|attr| attr.value.as_str())
+        .collect();
+    assert_eq!(
+        actions,
+        vec![
+            setup.governance.as_str(),
+            "subscribe",
+            setup.name_service.as_str(),
+            "register",
+            setup.governance.as_str(),
+            "name_registered",
+        ]
+    );
+
+    let msg = HandleMsg::Unsubscribe {
+        name: "alice".to_string(),
+    };
+    setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[])
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), None);
+    assert!(subscription(&setup, "alice").is_err());
+    assert_eq!(setup.router.balance(&user, LUNA), 100);
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 900);
+}
+
+#[test]
+fn failed_registration_reverts_the_payment() {
+    let mut setup = setup(None, None);
+    let admin = HumanAddr::from("admin");
+    let user = HumanAddr::from("user");
+    setup.router.set_balance(&user, &[Coin::new(1000, LUNA)]);
+
+    let block = HandleMsg::SetNameStatus {
+        names: vec!["blocked".to_string()],
+        status: NameStatus::Blocked,
+    };
+    setup
+        .router
+        .execute(&admin, &setup.governance, &block, &[])
+        .unwrap();
+
+    // Governance accepts the payment; the name service rejects the registration it sends.
+    let msg = HandleMsg::Subscribe {
+        name: "blocked".to_string(),
+        periods: None,
+        referrer: None,
+        voucher: None,
+    };
+    let txs = setup.router.txs().len();
+    let res = setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[Coin::new(1000, LUNA)]);
+    assert!(res.is_err());
+    assert_eq!(setup.router.txs().len(), txs);
+    assert!(subscription(&setup, "blocked").is_err());
+    assert_eq!(setup.router.balance(&user, LUNA), 1000);
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 0);
+}
+
+#[test]
+fn lapsed_name_moves_and_pays_the_treasury() {
+    let mut setup = setup(
+        Some(Premium {
+            start: Uint128(1000),
+            window: 100,
+            decay: Decay::Linear,
+        }),
+        None,
+    );
+    let first = HumanAddr::from("first");
+    let second = HumanAddr::from("second");
+    setup.router.set_balance(&first, &[Coin::new(100, LUNA)]);
+    setup.router.set_balance(&second, &[Coin::new(1000, LUNA)]);
+
+    let msg = HandleMsg::Subscribe {
+        name: "alice".to_string(),
+        periods: None,
+        referrer: None,
+        voucher: None,
+    };
+    setup
+        .router
+        .execute(&first, &setup.governance, &msg, &[Coin::new(100, LUNA)])
+        .unwrap();
+    let expires = subscription(&setup, "alice").unwrap().expires;
+    assert_eq!(expires, setup.router.block_time() + 1000);
+
+    // Half way through the premium window the premium has decayed to 500.
+    setup.router.advance(1000 + 100 + 50);
+    setup
+        .router
+        .execute(&second, &setup.governance, &msg, &[Coin::new(600, LUNA)])
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), Some(second.clone()));
+    let info = subscription(&setup, "alice").unwrap();
+    assert_eq!(info.owner, second);
+    assert_eq!(info.paid, Uint128(100));
+    assert_eq!(
+        setup.router.balance(&HumanAddr::from("treasury"), LUNA),
+        500
+    );
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 200);
+}
+
+#[test]
+fn batch_subscription_round_trip() {
+    let mut setup = setup(None, None);
+    let user = HumanAddr::from("user");
+    setup.router.set_balance(&user, &[Coin::new(300, LUNA)]);
+    let names = vec!["alice".to_string(), "bob".to_string()];
+
+    let msg = HandleMsg::SubscribeBatch {
+        names: names.clone(),
+        periods: None,
+        referrer: None,
+    };
+    let res = setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[Coin::new(300, LUNA)])
+        .unwrap();
+    let data: SubscribeBatchResponse = from_binary(&res.data.unwrap()).unwrap();
+    assert_eq!(data.results.len(), 2);
+    for name in &names {
+        assert_eq!(owner_of(&setup, name), Some(user.clone()));
+        assert!(!subscription(&setup, name).unwrap().pending);
+    }
+
+    let msg = HandleMsg::UnsubscribeBatch { names };
+    setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[])
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), None);
+    assert_eq!(owner_of(&setup, "bob"), None);
+    assert_eq!(setup.router.balance(&user, LUNA), 30);
+}
+
+#[test]
+fn staked_name_returns_its_stake_after_unbonding() {
+    let validator = HumanAddr::from("validator");
+    let staking = Staking {
+        validators: vec![validator.clone()],
+        stake: Uint128(500),
+        unbonding_period: UNBONDING_PERIOD,
+    };
+    let mut setup = setup(None, Some(staking));
+    let user = HumanAddr::from("user");
+    let treasury = HumanAddr::from("treasury");
+    setup.router.set_balance(&user, &[Coin::new(500, LUNA)]);
+
+    let msg = HandleMsg::Stake {
+        name: "alice".to_string(),
+    };
+    setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[Coin::new(500, LUNA)])
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), Some(user.clone()));
+    assert_eq!(setup.router.delegation(&setup.governance, &validator), 500);
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 0);
+
+    // Rewards go to the treasury, whoever asks for them.
+    setup
+        .router
+        .accrue_rewards(&setup.governance, &validator, 30);
+    setup
+        .router
+        .execute(
+            &user,
+            &setup.governance,
+            &HandleMsg::WithdrawRewards {},
+            &[],
+        )
+        .unwrap();
+    assert_eq!(setup.router.balance(&treasury, LUNA), 30);
+
+    let msg = HandleMsg::Unsubscribe {
+        name: "alice".to_string(),
+    };
+    setup
+        .router
+        .execute(&user, &setup.governance, &msg, &[])
+        .unwrap();
+    assert_eq!(owner_of(&setup, "alice"), None);
+    assert_eq!(setup.router.delegation(&setup.governance, &validator), 0);
+    assert_eq!(setup.router.unbonding(&setup.governance), 500);
+    let claim = HandleMsg::ClaimStake {};
+    assert!(setup
+        .router
+        .execute(&user, &setup.governance, &claim, &[])
+        .is_err());
+
+    setup.router.advance(UNBONDING_PERIOD);
+    setup
+        .router
+        .execute(&user, &setup.governance, &claim, &[])
+        .unwrap();
+    assert_eq!(setup.router.balance(&user, LUNA), 500);
+    assert_eq!(setup.router.balance(&setup.governance, LUNA), 0);
+}
@@ -0,0 +1,531 @@
+//! Random sequences of subscriptions, stakes, transfers, sweeps, referral claims, allowlist
+//! changes and direct name service calls over a few addresses and names, checking after every
+//! step that the two contracts still agree with each other, with the bank and with staking.
+
+use cosmwasm_std::{Binary, CanonicalAddr, Coin, Decimal, HumanAddr, Uint128};
+use governance::msg::{
+    AllowlistEntry, HandleMsg, InitMsg, PaymentsResponse, PriceResponse, QueryMsg,
+    ReferralResponse, SubscriptionInfoResponse, SubscriptionState, SubscriptionStatusResponse,
+    UnbondingResponse,
+};
+use governance::state::{Decay, Premium, Staking};
+use multitest::{Code, Router, UNBONDING_PERIOD};
+use name_service::msg::{
+    HandleMsg as NSHandleMsg, NamesResponse, OwnerResponse, QueryMsg as NSQueryMsg,
+};
+use name_service::state::Name;
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+const LUNA: &str = "uluna";
+const USERS: [&str; 4] = ["user0", "user1", "user2", "user3"];
+const NAMES: [&str; 4] = ["alpha", "beta", "gamma", "delta"];
+const TREASURY: &str = "treasury";
+const BASE_PRICE: u128 = 100;
+const START_BALANCE: u128 = 1_000_000;
+const ADMIN: &str = "admin";
+const VALIDATORS: [&str; 2] = ["val0", "val1"];
+const STAKE: u128 = 200;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Subscribe {
+        user: usize,
+        name: usize,
+        sent: u128,
+        periods: Option<u64>,
+        referrer: Option<usize>,
+    },
+    Unsubscribe {
+        user: usize,
+        name: usize,
+    },
+    Stake {
+        user: usize,
+        name: usize,
+        sent: u128,
+    },
+    ClaimStake {
+        user: usize,
+    },
+    /// Accrues `reward` on every delegation, then withdraws it to the treasury.
+    WithdrawRewards {
+        user: usize,
+        reward: u128,
+    },
+    SubscribeBatch {
+        user: usize,
+        names: Vec<usize>,
+        sent: u128,
+    },
+    UnsubscribeBatch {
+        user: usize,
+        names: Vec<usize>,
+    },
+    Transfer {
+        user: usize,
+        name: usize,
+        to: usize,
+    },
+    Register {
+        user: usize,
+        name: usize,
+    },
+    Deregister {
+        user: usize,
+        name: usize,
+    },
+    Sweep {
+        user: usize,
+    },
+    ClaimReferral {
+        user: usize,
+    },
+    Allow {
+        user: usize,
+        quota: u32,
+    },
+    Advance {
+        seconds: u64,
+    },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let user = 0..USERS.len();
+    let name = 0..NAMES.len();
+    let names = proptest::collection::vec(0..NAMES.len(), 1..4);
+    prop_oneof![
+        3 => (
+            user.clone(),
+            name.clone(),
+            1..600u128,
+            proptest::option::of(0..4u64),
+            proptest::option::of(user.clone()),
+        )
+            .prop_map(|(user, name, sent, periods, referrer)| Op::Subscribe {
+                user,
+                name,
+                sent,
+                periods,
+                referrer,
+            }),
+        2 => (user.clone(), name.clone()).prop_map(|(user, name)| Op::Unsubscribe { user, name }),
+        2 => (user.clone(), name.clone(), 1..600u128)
+            .prop_map(|(user, name, sent)| Op::Stake { user, name, sent }),
+        1 => user.clone().prop_map(|user| Op::ClaimStake { user }),
+        1 => (user.clone(), 0..50u128)
+            .prop_map(|(user, reward)| Op::WithdrawRewards { user, reward }),
+        1 => (user.clone(), names.clone(), 1..1500u128)
+            .prop_map(|(user, names, sent)| Op::SubscribeBatch { user, names, sent }),
+        1 => (user.clone(), names).prop_map(|(user, names)| Op::UnsubscribeBatch { user, names }),
+        2 => (user.clone(), name.clone(), user.clone())
+            .prop_map(|(user, name, to)| Op::Transfer { user, name, to }),
+        1 => (user.clone(), name.clone()).prop_map(|(user, name)| Op::Register { user, name }),
+        1 => (user.clone(), name).prop_map(|(user, name)| Op::Deregister { user, name }),
+        1 => user.clone().prop_map(|user| Op::Sweep { user }),
+        1 => user.clone().prop_map(|user| Op::ClaimReferral { user }),
+        1 => (user, 0..3u32).prop_map(|(user, quota)| Op::Allow { user, quota }),
+        2 => (0..1500u64).prop_map(|seconds| Op::Advance { seconds }),
+    ]
+}
+
+struct Setup {
+    router: Router,
+    governance: HumanAddr,
+    name_service: HumanAddr,
+    /// Rewards paid out, which add to the supply.
+    rewarded: u128,
+}
+
+impl Setup {
+    fn new() -> Self {
+        let mut router = Router::new();
+        let msg = InitMsg {
+            nameservice_code_id: Code::NameService as u64,
+            treasury: Some(HumanAddr::from(TREASURY)),
+            base_price: Uint128(BASE_PRICE),
+            registration_period: 1000,
+            grace_period: 100,
+            premium: Some(Premium {
+                start: Uint128(300),
+                window: 200,
+                decay: Decay::Linear,
+            }),
+            sweep_reward: Some(Uint128(20)),
+            referral_share: Some(Decimal::percent(10)),
+            voucher_signer: None,
+            staking: Some(Staking {
+                validators: VALIDATORS
+                    .iter()
+                    .map(|validator| HumanAddr::from(*validator))
+                    .collect(),
+                stake: Uint128(STAKE),
+                unbonding_period: UNBONDING_PERIOD,
+            }),
+        };
+        let admin = HumanAddr::from(ADMIN);
+        let governance = router
+            .instantiate(Code::Governance, &admin, &msg, &[])
+            .unwrap();
+        let name_service = router
+            .query(&governance, &QueryMsg::GetNameServiceAddress {})
+            .unwrap();
+        for user in &USERS {
+            router.set_balance(&HumanAddr::from(*user), &[Coin::new(START_BALANCE, LUNA)]);
+        }
+        Setup {
+            router,
+            governance,
+            name_service,
+            rewarded: 0,
+        }
+    }
+
+    fn subscription(&self, name: &str) -> Option<SubscriptionInfoResponse> {
+        let msg = QueryMsg::SubscriptionInfo {
+            name: name.to_string(),
+        };
+        self.router.query(&self.governance, &msg).ok()
+    }
+
+    fn state(&self, name: &str) -> Option<SubscriptionState> {
+        let msg = QueryMsg::SubscriptionStatus {
+            name: name.to_string(),
+            time: self.router.block_time(),
+        };
+        let res: Option<SubscriptionStatusResponse> =
+            self.router.query(&self.governance, &msg).ok();
+        res.map(|res| res.state)
+    }
+
+    fn referral(&self, user: usize) -> ReferralResponse {
+        let msg = QueryMsg::ReferralEarnings {
+            referrer: HumanAddr::from(USERS[user]),
+        };
+        self.router.query(&self.governance, &msg).unwrap()
+    }
+
+    fn allowance(&self, user: usize) -> AllowlistEntry {
+        let msg = QueryMsg::Allowance {
+            address: HumanAddr::from(USERS[user]),
+        };
+        self.router.query(&self.governance, &msg).unwrap()
+    }
+
+    /// The user's unbonding stake that can be claimed now.
+    fn claimable(&self, user: usize) -> u128 {
+        let msg = QueryMsg::Unbonding {
+            address: HumanAddr::from(USERS[user]),
+        };
+        let res: UnbondingResponse = self.router.query(&self.governance, &msg).unwrap();
+        res.entries
+            .iter()
+            .filter(|entry| entry.release <= self.router.block_time())
+            .map(|entry| entry.amount.u128())
+            .sum()
+    }
+
+    fn owner(&self, name: &str) -> Option<HumanAddr> {
+        let msg = NSQueryMsg::OwnerIs {
+            value: name.to_string(),
+        };
+        let res: OwnerResponse = self.router.query(&self.name_service, &msg).unwrap();
+        res.owner
+    }
+
+    /// The total `Subscribe` would charge for `name` now, or `None` while it is taken.
+    fn price(&self, name: &str) -> Option<u128> {
+        let msg = QueryMsg::PriceIs {
+            name: name.to_string(),
+            time: Some(self.router.block_time()),
+        };
+        let res: Option<PriceResponse> = self.router.query(&self.governance, &msg).ok();
+        res.map(|res| res.total.u128())
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match op {
+            Op::Subscribe {
+                user,
+                name,
+                sent,
+                periods,
+                referrer,
+            } => {
+                let allowance = self.allowance(*user);
+                let price = match periods.unwrap_or(1) {
+                    _ if *referrer == Some(*user) => None,
+                    0 => None,
+                    periods => self.price(NAMES[*name]).map(|price| {
+                        let base = Uint128(BASE_PRICE * periods as u128);
+                        price + base.u128() - BASE_PRICE - (base * allowance.discount).u128()
+                    }),
+                };
+                let msg = HandleMsg::Subscribe {
+                    name: NAMES[*name].to_string(),
+                    periods: *periods,
+                    referrer: referrer.map(|referrer| HumanAddr::from(USERS[referrer])),
+                    voucher: None,
+                };
+                let ok = self.governance(*user, &msg, *sent);
+                assert_eq!(ok, price.is_some_and(|price| price <= *sent), "{:?}", op);
+                if ok && allowance.quota > 0 {
+                    assert_eq!(self.allowance(*user).quota, allowance.quota - 1);
+                }
+            }
+            Op::Unsubscribe { user, name } => {
+                let owned = self.owned_by(NAMES[*name], *user);
+                let msg = HandleMsg::Unsubscribe {
+                    name: NAMES[*name].to_string(),
+                };
+                assert_eq!(self.governance(*user, &msg, 0), owned, "{:?}", op);
+            }
+            Op::Stake { user, name, sent } => {
+                let price = self
+                    .price(NAMES[*name])
+                    .map(|price| price - BASE_PRICE + STAKE);
+                let msg = HandleMsg::Stake {
+                    name: NAMES[*name].to_string(),
+                };
+                let ok = self.governance(*user, &msg, *sent);
+                assert_eq!(ok, price.is_some_and(|price| price <= *sent), "{:?}", op);
+            }
+            Op::ClaimStake { user } => {
+                let claimable = self.claimable(*user);
+                let before = self.router.balance(&HumanAddr::from(USERS[*user]), LUNA);
+                let ok = self.governance(*user, &HandleMsg::ClaimStake {}, 0);
+                assert_eq!(ok, claimable != 0, "{:?}", op);
+                let after = self.router.balance(&HumanAddr::from(USERS[*user]), LUNA);
+                assert_eq!(after - before, claimable, "{:?}", op);
+            }
+            Op::WithdrawRewards { user, reward } => {
+                let mut accrued = 0;
+                for validator in &VALIDATORS {
+                    let validator = HumanAddr::from(*validator);
+                    if self.router.delegation(&self.governance, &validator) != 0 {
+                        self.router
+                            .accrue_rewards(&self.governance, &validator, *reward);
+                        accrued += reward;
+                    }
+                }
+                let treasury = HumanAddr::from(TREASURY);
+                let before = self.router.balance(&treasury, LUNA);
+                let ok = self.governance(*user, &HandleMsg::WithdrawRewards {}, 0);
+                assert!(ok, "{:?}", op);
+                assert_eq!(self.router.balance(&treasury, LUNA), before + accrued);
+                self.rewarded += accrued;
+            }
+            Op::SubscribeBatch { user, names, sent } => {
+                let mut distinct = names.clone();
+                distinct.sort_unstable();
+                distinct.dedup();
+                // The allowlist discount covers the first names, as many as the quota allows.
+                let allowance = self.allowance(*user);
+                let discount = (Uint128(BASE_PRICE) * allowance.discount).u128();
+                let allowed = (allowance.quota as usize).min(names.len());
+                let price: Option<u128> = names
+                    .iter()
+                    .map(|name| self.price(NAMES[*name]))
+                    .sum::<Option<u128>>()
+                    .map(|price| price - discount * allowed as u128);
+                let msg = HandleMsg::SubscribeBatch {
+                    names: names.iter().map(|name| NAMES[*name].to_string()).collect(),
+                    periods: None,
+                    referrer: None,
+                };
+                let expected =
+                    distinct.len() == names.len() && price.is_some_and(|price| price <= *sent);
+                let ok = self.governance(*user, &msg, *sent);
+                assert_eq!(ok, expected, "{:?}", op);
+                if ok {
+                    let quota = allowance.quota - allowed as u32;
+                    assert_eq!(self.allowance(*user).quota, quota, "{:?}", op);
+                }
+            }
+            Op::UnsubscribeBatch { user, names } => {
+                let mut distinct = names.clone();
+                distinct.sort_unstable();
+                distinct.dedup();
+                let expected = distinct.len() == names.len()
+                    && names.iter().all(|name| self.owned_by(NAMES[*name], *user));
+                let msg = HandleMsg::UnsubscribeBatch {
+                    names: names.iter().map(|name| NAMES[*name].to_string()).collect(),
+                };
+                assert_eq!(self.governance(*user, &msg, 0), expected, "{:?}", op);
+            }
+            Op::Transfer { user, name, to } => {
+                let owned = self.owner(NAMES[*name]) == Some(HumanAddr::from(USERS[*user]));
+                let msg = NSHandleMsg::TransferNft {
+                    recipient: HumanAddr::from(USERS[*to]),
+                    token_id: NAMES[*name].to_string(),
+                };
+                let res = self.router.execute(
+                    &HumanAddr::from(USERS[*user]),
+                    &self.name_service,
+                    &msg,
+                    &[],
+                );
+                assert_eq!(res.is_ok(), owned, "{:?}", op);
+            }
+            Op::Register { user, name } | Op::Deregister { user, name } => {
+                let name_c = Name {
+                    value: NAMES[*name].to_string(),
+                    owner: CanonicalAddr(Binary(vec![b'u'; 20])),
+                };
+                let msg = match op {
+                    Op::Register { .. } => NSHandleMsg::Register {
+                        name_c,
+                        expires: None,
+                    },
+                    _ => NSHandleMsg::Deregister { name_c },
+                };
+                // Only governance may register or deregister names.
+                let user = HumanAddr::from(USERS[*user]);
+                let res = self.router.execute(&user, &self.name_service, &msg, &[]);
+                assert!(res.is_err(), "{:?}", op);
+            }
+            Op::Sweep { user } => {
+                let msg = HandleMsg::Sweep { limit: None };
+                assert!(self.governance(*user, &msg, 0), "{:?}", op);
+                for name in &NAMES {
+                    assert_ne!(
+                        self.state(name),
+                        Some(SubscriptionState::Lapsed),
+                        "{}",
+                        name
+                    );
+                }
+            }
+            Op::ClaimReferral { user } => {
+                let unclaimed = self.referral(*user).unclaimed;
+                let msg = HandleMsg::ClaimReferral {};
+                let ok = self.governance(*user, &msg, 0);
+                assert_eq!(ok, !unclaimed.is_zero(), "{:?}", op);
+            }
+            Op::Allow { user, quota } => {
+                let msg = HandleMsg::SetAllowlist {
+                    entries: vec![AllowlistEntry {
+                        address: HumanAddr::from(USERS[*user]),
+                        quota: *quota,
+                        discount: Decimal::percent(50),
+                    }],
+                };
+                let admin = HumanAddr::from(ADMIN);
+                let res = self.router.execute(&admin, &self.governance, &msg, &[]);
+                assert!(res.is_ok(), "{:?}", op);
+                // Only the owner manages the allowlist.
+                assert!(!self.governance(*user, &msg, 0), "{:?}", op);
+            }
+            Op::Advance { seconds } => self.router.advance(*seconds),
+        }
+    }
+
+    fn governance(&mut self, user: usize, msg: &HandleMsg, sent: u128) -> bool {
+        let funds = match sent {
+            0 => vec![],
+            sent => vec![Coin::new(sent, LUNA)],
+        };
+        let user = HumanAddr::from(USERS[user]);
+        self.router
+            .execute(&user, &self.governance, msg, &funds)
+            .is_ok()
+    }
+
+    fn owned_by(&self, name: &str, user: usize) -> bool {
+        self.subscription(name)
+            .is_some_and(|info| info.owner.as_str() == USERS[user])
+    }
+
+    fn check_invariants(&self) {
+        let mut supply = self.router.balance(&self.governance, LUNA);
+        supply += self.router.balance(&HumanAddr::from(TREASURY), LUNA);
+        for user in &USERS {
+            supply += self.router.balance(&HumanAddr::from(*user), LUNA);
+        }
+        for validator in &VALIDATORS {
+            supply += self
+                .router
+                .delegation(&self.governance, &HumanAddr::from(*validator));
+        }
+        supply += self.router.unbonding(&self.governance);
+        assert_eq!(supply, START_BALANCE * USERS.len() as u128 + self.rewarded);
+
+        let mut paid: BTreeMap<String, u128> = BTreeMap::new();
+        let mut staked: BTreeMap<String, u128> = BTreeMap::new();
+        let mut refunds = 0;
+        for name in &NAMES {
+            let owner = self.owner(name);
+            match self.subscription(name) {
+                Some(info) => {
+                    assert!(!info.pending, "{} is left pending", name);
+                    assert_eq!(owner, Some(info.owner.clone()), "owners of {}", name);
+                    *paid.entry(info.payer.0).or_default() += info.paid.u128();
+                    refunds += info.refund.u128();
+                    if let Some(stake) = info.stake {
+                        *staked.entry(stake.validator.0).or_default() += stake.amount.u128();
+                    }
+                }
+                None => assert_eq!(owner, None, "{} has no subscription", name),
+            }
+        }
+        paid.retain(|_, paid| *paid != 0);
+
+        let msg = QueryMsg::ListPayments {
+            start_after: None,
+            limit: Some(30),
+        };
+        let res: PaymentsResponse = self.router.query(&self.governance, &msg).unwrap();
+        let payments: BTreeMap<String, u128> = res
+            .payments
+            .into_iter()
+            .map(|entry| (entry.address.0, entry.amount.u128()))
+            .collect();
+        assert_eq!(payments, paid);
+        for validator in &VALIDATORS {
+            let delegated = self
+                .router
+                .delegation(&self.governance, &HumanAddr::from(*validator));
+            let held = staked.get(*validator).copied().unwrap_or_default();
+            assert_eq!(delegated, held, "stake with {}", validator);
+        }
+        // Stakes unbond on the chain no later than governance lets them be claimed.
+        let unclaimed: u128 = (0..USERS.len())
+            .map(|user| self.referral(user).unclaimed.u128() + self.claimable(user))
+            .sum();
+        assert!(self.router.balance(&self.governance, LUNA) >= refunds + unclaimed);
+
+        let msg = NSQueryMsg::ListNames {
+            start_after: None,
+            limit: Some(30),
+        };
+        let listed: NamesResponse = self.router.query(&self.name_service, &msg).unwrap();
+        let mut by_owner = vec![];
+        for user in &USERS {
+            let msg = NSQueryMsg::NamesByOwner {
+                owner: HumanAddr::from(*user),
+                start_after: None,
+                limit: Some(30),
+            };
+            let res: NamesResponse = self.router.query(&self.name_service, &msg).unwrap();
+            by_owner.extend(res.names);
+        }
+        by_owner.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(listed.names, by_owner);
+        for entry in listed.names {
+            assert_eq!(self.owner(&entry.name), Some(entry.owner));
+        }
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn contracts_stay_consistent(ops in proptest::collection::vec(op(), 1..40)) {
+        let mut setup = Setup::new();
+        for op in &ops {
+            setup.apply(op);
+            setup.check_invariants();
+        }
+    }
+}